@@ -0,0 +1,38 @@
+#![feature(test)]
+
+extern crate test;
+
+use neo_vm_rs::{execution_engine::ExecutionEngine, op_code::OpCode, vm::script::Script};
+use test::Bencher;
+
+/// Repeatedly allocates, concatenates and drops 1 KiB buffers.
+fn buffer_script() -> Vec<u8> {
+	let mut script = Vec::new();
+	for _ in 0..256 {
+		script.extend([OpCode::PushInt16 as u8, 0x00, 0x04, OpCode::NewBuffer as u8]);
+		script.extend([OpCode::PushInt16 as u8, 0x00, 0x04, OpCode::NewBuffer as u8]);
+		script.extend([OpCode::Cat as u8, OpCode::Drop as u8]);
+	}
+	script
+}
+
+fn run(script: &[u8], pooled: bool) {
+	let mut engine = ExecutionEngine::new();
+	if pooled {
+		engine.enable_buffer_pool(16);
+	}
+	engine.load_script(Script::new(script.to_vec()), -1, 0).unwrap();
+	engine.execute();
+}
+
+#[bench]
+fn bench_buffers_unpooled(b: &mut Bencher) {
+	let script = buffer_script();
+	b.iter(|| run(&script, false));
+}
+
+#[bench]
+fn bench_buffers_pooled(b: &mut Bencher) {
+	let script = buffer_script();
+	b.iter(|| run(&script, true));
+}
@@ -0,0 +1,32 @@
+#![feature(test)]
+
+extern crate test;
+
+use neo_vm_rs::{execution_engine::ExecutionEngine, op_code::OpCode, vm::script::Script};
+use test::Bencher;
+
+/// Counts from 0 to 10000 in a loop, dispatching five instructions per iteration.
+fn counter_script() -> Vec<u8> {
+	let [lo, hi] = 10000u16.to_le_bytes();
+	vec![
+		OpCode::Push0 as u8,
+		OpCode::Inc as u8,
+		OpCode::Dup as u8,
+		OpCode::PushInt16 as u8,
+		lo,
+		hi,
+		OpCode::Lt as u8,
+		OpCode::JmpIf as u8,
+		-6i8 as u8,
+	]
+}
+
+#[bench]
+fn bench_arithmetic_loop(b: &mut Bencher) {
+	let script = counter_script();
+	b.iter(|| {
+		let mut engine = ExecutionEngine::new();
+		engine.load_script(Script::new(script.clone()), -1, 0).unwrap();
+		engine.execute()
+	});
+}
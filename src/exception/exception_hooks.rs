@@ -0,0 +1,31 @@
+use crate::{
+	exception::exception_handling_context::ExceptionHandlingContext,
+	execution_engine::ExecutionEngine,
+};
+use std::{fmt, rc::Rc};
+
+/// Called with the engine and the exception handling context whose region is entered.
+pub type RegionHook = Rc<dyn Fn(&ExecutionEngine, &ExceptionHandlingContext)>;
+
+/// Callbacks invoked when execution enters a region of a `try`-`catch`-`finally` block.
+#[derive(Clone, Default)]
+pub struct ExceptionHooks {
+	/// Called after `OpCode::Try` or `OpCode::TryL` pushes a new handler.
+	pub on_try_enter: Option<RegionHook>,
+
+	/// Called when a thrown exception transfers control to a `catch` block.
+	pub on_catch_enter: Option<RegionHook>,
+
+	/// Called when an `ENDTRY` or a thrown exception transfers control to a `finally` block.
+	pub on_finally_enter: Option<RegionHook>,
+}
+
+impl fmt::Debug for ExceptionHooks {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("ExceptionHooks")
+			.field("on_try_enter", &self.on_try_enter.is_some())
+			.field("on_catch_enter", &self.on_catch_enter.is_some())
+			.field("on_finally_enter", &self.on_finally_enter.is_some())
+			.finish()
+	}
+}
@@ -1,5 +1,6 @@
 pub mod exception_handling_context;
 pub mod exception_handling_state;
+pub mod exception_hooks;
 
 pub fn add(left: usize, right: usize) -> usize {
 	left + right
@@ -0,0 +1,177 @@
+use crate::{
+	execution_engine::ExecutionEngine, instruction::Instruction, jump_table::JumpTable,
+	stack_item::StackItem, vm_error::VMError,
+};
+
+impl JumpTable {
+	pub fn invert(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let x = engine.pop_int()?;
+		engine.push(StackItem::Integer(!x).into())
+	}
+
+	pub fn and(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let x2 = engine.pop_int()?;
+		let x1 = engine.pop_int()?;
+		engine.push(StackItem::Integer(x1 & x2).into())
+	}
+
+	pub fn or(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let x2 = engine.pop_int()?;
+		let x1 = engine.pop_int()?;
+		engine.push(StackItem::Integer(x1 | x2).into())
+	}
+
+	pub fn xor(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let x2 = engine.pop_int()?;
+		let x1 = engine.pop_int()?;
+		engine.push(StackItem::Integer(x1 ^ x2).into())
+	}
+
+	pub fn equal(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let x2 = engine.pop()?;
+		let x1 = engine.pop()?;
+		let result = x1.borrow().equals(&x2.borrow(), &engine.limits)?;
+		engine.push(StackItem::Boolean(result).into())
+	}
+
+	pub fn not_equal(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let x2 = engine.pop()?;
+		let x1 = engine.pop()?;
+		let result = x1.borrow().equals(&x2.borrow(), &engine.limits)?;
+		engine.push(StackItem::Boolean(!result).into())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::{
+		execution_engine::ExecutionEngine,
+		jump_table::test_helpers::{result, run_script},
+		op_code::OpCode,
+		stack_item::StackItem,
+		vm::script::Script,
+		vm_state::VMState,
+	};
+
+	#[test]
+	fn test_and_or_xor() {
+		let engine = run_script(vec![
+			OpCode::Push12 as u8,
+			OpCode::Push10 as u8,
+			OpCode::And as u8,
+			OpCode::Push12 as u8,
+			OpCode::Push10 as u8,
+			OpCode::Or as u8,
+			OpCode::Push12 as u8,
+			OpCode::Push10 as u8,
+			OpCode::Xor as u8,
+		]);
+		assert_eq!(result(&engine, 0), StackItem::from(6));
+		assert_eq!(result(&engine, 1), StackItem::from(14));
+		assert_eq!(result(&engine, 2), StackItem::from(8));
+	}
+
+	#[test]
+	fn test_invert() {
+		let engine = run_script(vec![
+			OpCode::Push0 as u8,
+			OpCode::Invert as u8,
+			OpCode::Push5 as u8,
+			OpCode::Invert as u8,
+			OpCode::PushM1 as u8,
+			OpCode::Invert as u8,
+		]);
+		assert_eq!(result(&engine, 0), StackItem::from(0));
+		assert_eq!(result(&engine, 1), StackItem::from(-6));
+		assert_eq!(result(&engine, 2), StackItem::from(-1));
+	}
+
+	#[test]
+	fn test_non_integer_operand_faults() {
+		let engine = run_script(vec![OpCode::NewArray0 as u8, OpCode::Invert as u8]);
+		assert_eq!(engine.state, VMState::Fault);
+		let engine = run_script(vec![OpCode::Push1 as u8, OpCode::NewMap as u8, OpCode::And as u8]);
+		assert_eq!(engine.state, VMState::Fault);
+	}
+
+	#[test]
+	fn test_equal() {
+		let engine = run_script(vec![
+			OpCode::Push1 as u8,
+			OpCode::Push1 as u8,
+			OpCode::Equal as u8,
+			OpCode::Push1 as u8,
+			OpCode::PushTrue as u8,
+			OpCode::NotEqual as u8,
+		]);
+		assert_eq!(result(&engine, 0), StackItem::Boolean(true));
+		assert_eq!(result(&engine, 1), StackItem::Boolean(true));
+	}
+
+	#[test]
+	fn test_equal_arrays() {
+		let engine = run_script(vec![
+			OpCode::NewArray0 as u8,
+			OpCode::Dup as u8,
+			OpCode::Equal as u8,
+			OpCode::NewArray0 as u8,
+			OpCode::NewArray0 as u8,
+			OpCode::Equal as u8,
+			OpCode::NewStruct0 as u8,
+			OpCode::NewStruct0 as u8,
+			OpCode::NotEqual as u8,
+		]);
+		assert_eq!(result(&engine, 0), StackItem::Boolean(false));
+		assert_eq!(result(&engine, 1), StackItem::Boolean(false));
+		assert_eq!(result(&engine, 2), StackItem::Boolean(true));
+	}
+
+	#[test]
+	fn test_equal_max_comparable_size() {
+		// Two structs of three equal 4-byte strings each
+		let mut script = Vec::new();
+		for _ in 0..2 {
+			for _ in 0..3 {
+				script.extend([OpCode::PushData1 as u8, 0x04]);
+				script.extend(b"neo!");
+			}
+			script.extend([OpCode::Push3 as u8, OpCode::PackStruct as u8]);
+		}
+		script.push(OpCode::Equal as u8);
+		let run = |max_comparable_size: usize| {
+			let mut engine = ExecutionEngine::new();
+			engine.limits.max_comparable_size = max_comparable_size;
+			engine.load_script(Script::new(script.clone()), -1, 0).unwrap();
+			engine.execute();
+			engine
+		};
+		assert_eq!(run(11).state, VMState::Fault);
+		let engine = run(12);
+		assert_eq!(engine.state, VMState::Halt);
+		assert_eq!(result(&engine, 0), StackItem::Boolean(true));
+	}
+}
@@ -0,0 +1,954 @@
+use crate::{
+	execution_engine::ExecutionEngine,
+	instruction::Instruction,
+	jump_table::{to_usize, JumpTable},
+	ordered_map::OrderedMap,
+	stack_item::StackItem,
+	stack_item_type::StackItemType,
+	vm_error::VMError,
+};
+use num_bigint::BigInt;
+use num_traits::ToPrimitive;
+use std::{cell::RefCell, rc::Rc};
+
+impl JumpTable {
+	pub fn pack_map(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let size = to_usize(&engine.pop()?.borrow().get_integer()?)?;
+		if size > engine.limits.max_stack_size {
+			return Err(VMError::StackOverflow(format!("MaxStackSize exceed: {size}")))
+		}
+		let count = engine.evaluation_stack()?.borrow().count();
+		if size.checked_mul(2).is_none_or(|needed| needed > count) {
+			return Err(VMError::InvalidParameter(format!("The value {size} is out of range.")))
+		}
+		// The pairs are popped from the top, so for a duplicate key the value pushed first wins,
+		// as it is assigned last.
+		let mut map = OrderedMap::with_capacity(size);
+		for _ in 0..size {
+			let key = Self::pop_key(engine)?;
+			let value = engine.pop()?;
+			if map.insert(key, value).is_none() {
+				let mut reference_counter = engine.reference_counter.borrow_mut();
+				reference_counter.add_reference();
+				reference_counter.add_reference();
+			}
+		}
+		engine.push(StackItem::Map(map).into())
+	}
+
+	pub fn pack_struct(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let items = Self::pop_items(engine)?;
+		engine.push(StackItem::Struct(items.into()).into())
+	}
+
+	pub fn pack(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let items = Self::pop_items(engine)?;
+		engine.push(StackItem::Array(items.into()).into())
+	}
+
+	pub fn unpack(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let compound = engine.pop()?;
+		let compound = compound.borrow();
+		let count = match &*compound {
+			StackItem::Map(map) => {
+				for (key, value) in map.iter().rev() {
+					engine.push(value.clone())?;
+					engine.push(key.clone().into())?;
+				}
+				map.len()
+			},
+			StackItem::Array(items) | StackItem::Struct(items) => {
+				for item in items.iter().rev() {
+					engine.push(item.clone())?;
+				}
+				items.len()
+			},
+			item =>
+				return Err(VMError::InvalidType(format!(
+					"Invalid type for UNPACK: {:?}",
+					item.get_type()
+				))),
+		};
+		engine.push(StackItem::from(count).into())
+	}
+
+	pub fn new_array0(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		engine.push(StackItem::Array(Vec::new().into()).into())
+	}
+
+	pub fn new_array(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let items = Self::new_items(engine, StackItem::Null)?;
+		engine.push(StackItem::Array(items.into()).into())
+	}
+
+	pub fn new_array_t(
+		&self,
+		engine: &mut ExecutionEngine,
+		instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let code = instruction.token_u8();
+		// Primitive elements start out with their default value and the other types with
+		// `Null`, except pointers and interop interfaces, which can't be created here.
+		let item = match StackItemType::try_from_u8(code)? {
+			StackItemType::Boolean => StackItem::Boolean(false),
+			StackItemType::Integer => StackItem::Integer(BigInt::default()),
+			StackItemType::ByteString => StackItem::ByteString(Vec::new()),
+			StackItemType::Any
+			| StackItemType::Buffer
+			| StackItemType::Array
+			| StackItemType::Struct
+			| StackItemType::Map => StackItem::Null,
+			StackItemType::Pointer | StackItemType::InteropInterface =>
+				return Err(VMError::InvalidType(format!(
+					"Invalid type for {:?}: {code}",
+					instruction.opcode
+				))),
+		};
+		let items = Self::new_items(engine, item)?;
+		engine.push(StackItem::Array(items.into()).into())
+	}
+
+	pub fn new_struct0(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		engine.push(StackItem::Struct(Vec::new().into()).into())
+	}
+
+	pub fn new_struct(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let items = Self::new_items(engine, StackItem::Null)?;
+		engine.push(StackItem::Struct(items.into()).into())
+	}
+
+	pub fn new_map(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		engine.push(StackItem::Map(OrderedMap::new()).into())
+	}
+
+	pub fn size(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let x = engine.pop()?;
+		let size = match &*x.borrow() {
+			StackItem::Array(items) | StackItem::Struct(items) => items.len(),
+			StackItem::Map(map) => map.len(),
+			item @ (StackItem::Boolean(_)
+			| StackItem::Integer(_)
+			| StackItem::ByteString(_)
+			| StackItem::Buffer(_)) => item.size()?,
+			item =>
+				return Err(VMError::InvalidType(format!(
+					"Invalid type for SIZE: {:?}",
+					item.get_type()
+				))),
+		};
+		engine.push(StackItem::from(size).into())
+	}
+
+	pub fn has_key(
+		&self,
+		engine: &mut ExecutionEngine,
+		instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let key = Self::pop_key(engine)?;
+		let x = engine.pop()?;
+		let result = match &*x.borrow() {
+			StackItem::Map(map) => map.contains_key(&key),
+			item => {
+				let index = key.get_integer()?;
+				if index < BigInt::default() {
+					return Err(VMError::InvalidParameter(format!(
+						"The negative value {index} is invalid for OpCode::{:?}.",
+						instruction.opcode
+					)))
+				}
+				let len = match item {
+					StackItem::Array(items) | StackItem::Struct(items) => items.len(),
+					StackItem::Buffer(bytes) | StackItem::ByteString(bytes) => bytes.len(),
+					item =>
+						return Err(VMError::InvalidType(format!(
+							"Invalid type for HASKEY: {:?}",
+							item.get_type()
+						))),
+				};
+				index < BigInt::from(len)
+			},
+		};
+		engine.push(StackItem::Boolean(result).into())
+	}
+
+	pub fn keys(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let x = engine.pop()?;
+		let keys: Vec<Rc<RefCell<StackItem>>> = match &*x.borrow() {
+			StackItem::Map(map) => map.keys().map(|key| key.clone().into()).collect(),
+			item =>
+				return Err(VMError::InvalidType(format!(
+					"Invalid type for KEYS: {:?}",
+					item.get_type()
+				))),
+		};
+		for _ in 0..keys.len() {
+			engine.reference_counter.borrow_mut().add_reference();
+		}
+		engine.push(StackItem::Array(keys.into()).into())
+	}
+
+	pub fn values(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let x = engine.pop()?;
+		let items: Vec<Rc<RefCell<StackItem>>> = match &*x.borrow() {
+			StackItem::Map(map) => map.values().cloned().collect(),
+			StackItem::Array(items) | StackItem::Struct(items) => items.to_vec(),
+			item =>
+				return Err(VMError::InvalidType(format!(
+					"Invalid type for VALUES: {:?}",
+					item.get_type()
+				))),
+		};
+		let mut values = Vec::with_capacity(items.len());
+		for item in items {
+			values.push(Self::copy_if_struct(engine, item)?);
+			engine.reference_counter.borrow_mut().add_reference();
+		}
+		engine.push(StackItem::Array(values.into()).into())
+	}
+
+	pub fn pick_item(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let key = Self::pop_key(engine)?;
+		let x = engine.pop()?;
+		let item = match &*x.borrow() {
+			StackItem::Array(items) | StackItem::Struct(items) =>
+				items[Self::element_index(&key, items.len())?].clone(),
+			StackItem::Map(map) => map
+				.get(&key)
+				.cloned()
+				.ok_or_else(|| VMError::ItemNotFound(format!("Key {key:?} not found in Map.")))?,
+			item @ (StackItem::Boolean(_) | StackItem::Integer(_) | StackItem::ByteString(_)) => {
+				let bytes = item.get_span()?;
+				StackItem::from(bytes[Self::element_index(&key, bytes.len())?] as i32).into()
+			},
+			StackItem::Buffer(bytes) =>
+				StackItem::from(bytes[Self::element_index(&key, bytes.len())?] as i32).into(),
+			item =>
+				return Err(VMError::InvalidType(format!(
+					"Invalid type for PICKITEM: {:?}",
+					item.get_type()
+				))),
+		};
+		engine.push(item)
+	}
+
+	pub fn append(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let item = engine.pop()?;
+		let item = Self::copy_if_struct(engine, item)?;
+		let x = engine.pop()?;
+		match &mut *x.borrow_mut() {
+			StackItem::Array(items) | StackItem::Struct(items) => {
+				items.check_writable()?;
+				items.push(item)
+			},
+			x =>
+				return Err(VMError::InvalidType(format!(
+					"Invalid type for APPEND: {:?}",
+					x.get_type()
+				))),
+		}
+		engine.reference_counter.borrow_mut().add_reference();
+		Ok(())
+	}
+
+	pub fn set_item(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let value = engine.pop()?;
+		let value = Self::copy_if_struct(engine, value)?;
+		let key = Self::pop_key(engine)?;
+		let x = engine.pop()?;
+		if matches!(*x.borrow(), StackItem::Buffer(_)) {
+			let value = value.borrow();
+			if !value.is_primitive() {
+				return Err(VMError::InvalidType(format!(
+					"Invalid value type for SETITEM on a Buffer: {:?}",
+					value.get_type()
+				)))
+			}
+			return Self::set_byte(&x, &key, &value.get_integer()?)
+		}
+		let replaced = match &mut *x.borrow_mut() {
+			StackItem::Array(items) | StackItem::Struct(items) => {
+				items.check_writable()?;
+				let index = Self::element_index(&key, items.len())?;
+				std::mem::replace(&mut items[index], value)
+			},
+			StackItem::Map(map) => match map.insert(key, value) {
+				Some(replaced) => replaced,
+				None => {
+					let mut reference_counter = engine.reference_counter.borrow_mut();
+					reference_counter.add_reference();
+					reference_counter.add_reference();
+					return Ok(())
+				},
+			},
+			x =>
+				return Err(VMError::InvalidType(format!(
+					"Invalid type for SETITEM: {:?}",
+					x.get_type()
+				))),
+		};
+		engine.reference_counter.borrow_mut().add_zero_referred(&replaced);
+		Ok(())
+	}
+
+	pub fn reverse_items(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let x = engine.pop()?;
+		match &mut *x.borrow_mut() {
+			StackItem::Array(items) | StackItem::Struct(items) => {
+				items.check_writable()?;
+				items.reverse()
+			},
+			StackItem::Buffer(bytes) => bytes.reverse(),
+			x =>
+				return Err(VMError::InvalidType(format!(
+					"Invalid type for REVERSEITEMS: {:?}",
+					x.get_type()
+				))),
+		}
+		Ok(())
+	}
+
+	pub fn remove(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let key = Self::pop_key(engine)?;
+		let x = engine.pop()?;
+		let (removed, count) = match &mut *x.borrow_mut() {
+			StackItem::Array(items) | StackItem::Struct(items) => {
+				items.check_writable()?;
+				let index = Self::element_index(&key, items.len())?;
+				(Some(items.remove(index)), 1)
+			},
+			StackItem::Map(map) => match map.remove(&key) {
+				Some(value) => (Some(value), 2),
+				None => (None, 0),
+			},
+			x =>
+				return Err(VMError::InvalidType(format!(
+					"Invalid type for REMOVE: {:?}",
+					x.get_type()
+				))),
+		};
+		let mut reference_counter = engine.reference_counter.borrow_mut();
+		for _ in 0..count {
+			reference_counter.remove_reference();
+		}
+		if let Some(removed) = removed {
+			reference_counter.add_zero_referred(&removed);
+		}
+		Ok(())
+	}
+
+	pub fn clear_items(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let x = engine.pop()?;
+		let (removed, count) = match &mut *x.borrow_mut() {
+			StackItem::Array(items) | StackItem::Struct(items) => {
+				items.check_writable()?;
+				let removed = std::mem::take(&mut **items);
+				let count = removed.len();
+				(removed, count)
+			},
+			StackItem::Map(map) => {
+				let count = map.len() * 2;
+				let removed = std::mem::take(map).into_iter().map(|(_, value)| value).collect();
+				(removed, count)
+			},
+			x =>
+				return Err(VMError::InvalidType(format!(
+					"Invalid type for CLEARITEMS: {:?}",
+					x.get_type()
+				))),
+		};
+		let mut reference_counter = engine.reference_counter.borrow_mut();
+		for _ in 0..count {
+			reference_counter.remove_reference();
+		}
+		for item in &removed {
+			reference_counter.add_zero_referred(item);
+		}
+		Ok(())
+	}
+
+	pub fn pop_item(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let x = engine.pop()?;
+		let item = match &mut *x.borrow_mut() {
+			StackItem::Array(items) | StackItem::Struct(items) => {
+				items.check_writable()?;
+				items.pop().ok_or_else(|| {
+					VMError::InvalidParameter(
+						"POPITEM can't be executed on an empty array.".to_string(),
+					)
+				})?
+			},
+			x =>
+				return Err(VMError::InvalidType(format!(
+					"Invalid type for POPITEM: {:?}",
+					x.get_type()
+				))),
+		};
+		engine.reference_counter.borrow_mut().remove_reference();
+		engine.push(item)
+	}
+
+	/// Sets the byte at `key` in `buffer` to `value`, which must fit in a signed or unsigned
+	/// byte.
+	fn set_byte(
+		buffer: &Rc<RefCell<StackItem>>,
+		key: &StackItem,
+		value: &BigInt,
+	) -> Result<(), VMError> {
+		let byte = value
+			.to_i16()
+			.filter(|byte| (i8::MIN as i16..=u8::MAX as i16).contains(byte))
+			.ok_or_else(|| {
+			VMError::InvalidParameter(format!("Overflow in SETITEM: {value}"))
+		})?;
+		if let StackItem::Buffer(bytes) = &mut *buffer.borrow_mut() {
+			let index = Self::element_index(key, bytes.len())?;
+			bytes[index] = byte as u8;
+		}
+		Ok(())
+	}
+
+	/// Converts the key of an element access to an index into `len` elements.
+	fn element_index(key: &StackItem, len: usize) -> Result<usize, VMError> {
+		let index = key.get_integer()?;
+		index
+			.to_usize()
+			.filter(|&index| index < len)
+			.ok_or_else(|| VMError::InvalidParameter(format!("The value {index} is out of range.")))
+	}
+
+	/// Copies `item` if it is a struct, as structs are stored by value. Nested structs are
+	/// copied too, up to `MaxStackSize` elements in total; every other item is shared.
+	pub(crate) fn copy_if_struct(
+		engine: &mut ExecutionEngine,
+		item: Rc<RefCell<StackItem>>,
+	) -> Result<Rc<RefCell<StackItem>>, VMError> {
+		if !matches!(*item.borrow(), StackItem::Struct(_)) {
+			return Ok(item)
+		}
+		let mut budget = engine.limits.max_stack_size;
+		Self::copy_struct(engine, &item, &mut budget)
+	}
+
+	fn copy_struct(
+		engine: &mut ExecutionEngine,
+		item: &Rc<RefCell<StackItem>>,
+		budget: &mut usize,
+	) -> Result<Rc<RefCell<StackItem>>, VMError> {
+		let StackItem::Struct(items) = &*item.borrow() else { return Ok(item.clone()) };
+		let mut copy = Vec::with_capacity(items.len());
+		for child in items {
+			*budget = budget.checked_sub(1).ok_or_else(|| {
+				VMError::StackOverflow("Beyond struct subitem clone limits!".to_string())
+			})?;
+			copy.push(Self::copy_struct(engine, child, budget)?);
+			engine.reference_counter.borrow_mut().add_reference();
+		}
+		Ok(StackItem::Struct(copy.into()).into())
+	}
+
+	/// Pops a map key, which must be a primitive item of at most `StackItem::MAX_KEY_SIZE`
+	/// bytes.
+	fn pop_key(engine: &mut ExecutionEngine) -> Result<StackItem, VMError> {
+		let key = engine.pop()?.borrow().clone();
+		key.check_map_key()?;
+		Ok(key)
+	}
+
+	/// Pops the size of a `PACK` or `PACKSTRUCT` followed by that many items.
+	fn pop_items(engine: &mut ExecutionEngine) -> Result<Vec<Rc<RefCell<StackItem>>>, VMError> {
+		let size = to_usize(&engine.pop()?.borrow().get_integer()?)?;
+		if size > engine.evaluation_stack()?.borrow().count() {
+			return Err(VMError::InvalidParameter(format!("The value {size} is out of range.")))
+		}
+		let mut items = Vec::with_capacity(size);
+		for _ in 0..size {
+			items.push(engine.pop()?);
+			engine.reference_counter.borrow_mut().add_reference();
+		}
+		Ok(items)
+	}
+
+	/// Pops the size of a `NEWARRAY` or `NEWSTRUCT` and creates that many copies of `item`.
+	fn new_items(
+		engine: &mut ExecutionEngine,
+		item: StackItem,
+	) -> Result<Vec<Rc<RefCell<StackItem>>>, VMError> {
+		let n = engine.pop()?.borrow().get_integer()?;
+		let n = to_usize(&n)
+			.ok()
+			.filter(|&n| n <= engine.limits.max_stack_size)
+			.ok_or_else(|| VMError::StackOverflow(format!("MaxStackSize exceed: {n}")))?;
+		for _ in 0..n {
+			engine.reference_counter.borrow_mut().add_reference();
+		}
+		Ok((0..n).map(|_| item.clone().into()).collect())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::{
+		execution_engine::ExecutionEngine,
+		jump_table::test_helpers::{result, run_script},
+		op_code::OpCode,
+		stack_item::StackItem,
+		stack_item_type::StackItemType,
+		vm::script::Script,
+		vm_error::VMError,
+		vm_state::VMState,
+	};
+
+	#[test]
+	fn test_pack_map_huge_count_faults() {
+		let mut script = vec![OpCode::Push1 as u8, OpCode::Push2 as u8, OpCode::PushInt128 as u8];
+		script.extend((usize::MAX as u128).to_le_bytes());
+		script.push(OpCode::PackMap as u8);
+		assert_eq!(run_script(script).state, VMState::Fault);
+	}
+
+	#[test]
+	fn test_pack_map_duplicate_keys_keep_last_assigned() {
+		let engine = run_script(vec![
+			OpCode::Push1 as u8,
+			OpCode::Push5 as u8,
+			OpCode::Push2 as u8,
+			OpCode::Push5 as u8,
+			OpCode::Push2 as u8,
+			OpCode::PackMap as u8,
+		]);
+		assert_eq!(engine.state, VMState::Halt);
+		let StackItem::Map(map) = result(&engine, 0) else { panic!("expected a map") };
+		assert_eq!(map.len(), 1);
+		assert_eq!(*map[&StackItem::from(5)].borrow(), StackItem::from(1));
+	}
+
+	#[test]
+	fn test_pack_map_entry_limit() {
+		let mut engine = ExecutionEngine::new();
+		engine.limits.max_stack_size = 4;
+		let script = vec![OpCode::Push5 as u8, OpCode::PackMap as u8];
+		engine.load_script(Script::new(script), -1, 0).unwrap();
+		engine.step().unwrap();
+		assert!(matches!(engine.step(), Err(VMError::StackOverflow(_))));
+		assert_eq!(engine.state, VMState::Fault);
+	}
+
+	#[test]
+	fn test_map_key_size_limit() {
+		for (size, state) in [(64, VMState::Halt), (65, VMState::Fault)] {
+			let mut script = vec![OpCode::PushNull as u8, OpCode::PushData1 as u8, size];
+			script.extend(vec![0x41; size as usize]);
+			script.extend([OpCode::Push1 as u8, OpCode::PackMap as u8]);
+			assert_eq!(run_script(script).state, state);
+		}
+	}
+
+	#[test]
+	fn test_keys_in_insertion_order() {
+		// PACKMAP inserts the pair on top of the stack first.
+		let keys = [10, 3, 16, 7, 1];
+		let mut script = Vec::new();
+		for &key in keys.iter().rev() {
+			script.extend([OpCode::PushNull as u8, OpCode::Push0 as u8 + key]);
+		}
+		script.extend([OpCode::Push5 as u8, OpCode::PackMap as u8, OpCode::Keys as u8]);
+		for _ in 0..10 {
+			let engine = run_script(script.clone());
+			assert_eq!(engine.state, VMState::Halt);
+			let StackItem::Array(items) = result(&engine, 0) else { panic!("expected an array") };
+			let items: Vec<StackItem> = items.iter().map(|item| item.borrow().clone()).collect();
+			assert_eq!(items, keys.map(|key| StackItem::from(key as i32)));
+		}
+	}
+
+	fn items(item: StackItem) -> Vec<StackItem> {
+		match item {
+			StackItem::Array(items) | StackItem::Struct(items) =>
+				items.iter().map(|item| item.borrow().clone()).collect(),
+			item => panic!("expected an array, found {item:?}"),
+		}
+	}
+
+	#[test]
+	fn test_set_and_pick_item() {
+		let engine = run_script(vec![
+			OpCode::Push3 as u8,
+			OpCode::NewArray as u8,
+			OpCode::Dup as u8,
+			OpCode::Push1 as u8,
+			OpCode::Push7 as u8,
+			OpCode::SetItem as u8,
+			OpCode::Dup as u8,
+			OpCode::Push1 as u8,
+			OpCode::PickItem as u8,
+			// Maps
+			OpCode::NewMap as u8,
+			OpCode::Dup as u8,
+			OpCode::Push1 as u8,
+			OpCode::Push2 as u8,
+			OpCode::SetItem as u8,
+			OpCode::Dup as u8,
+			OpCode::Push3 as u8,
+			OpCode::Push4 as u8,
+			OpCode::SetItem as u8,
+			OpCode::Dup as u8,
+			OpCode::Push3 as u8,
+			OpCode::PickItem as u8,
+			// Bytes
+			OpCode::PushData1 as u8,
+			0x02,
+			0x0A,
+			0x0B,
+			OpCode::Push1 as u8,
+			OpCode::PickItem as u8,
+			OpCode::Push2 as u8,
+			OpCode::NewBuffer as u8,
+			OpCode::Dup as u8,
+			OpCode::Push1 as u8,
+			OpCode::PushM1 as u8,
+			OpCode::SetItem as u8,
+		]);
+		assert_eq!(engine.state, VMState::Halt);
+		assert_eq!(result(&engine, 0), StackItem::Buffer(vec![0x00, 0xFF]));
+		assert_eq!(result(&engine, 1), StackItem::from(0x0B));
+		assert_eq!(result(&engine, 2), StackItem::from(4));
+		let StackItem::Map(map) = result(&engine, 3) else { panic!("expected a map") };
+		assert_eq!(map.len(), 2);
+		assert_eq!(result(&engine, 4), StackItem::from(7));
+		assert_eq!(items(result(&engine, 5)), [StackItem::Null, 7.into(), StackItem::Null]);
+	}
+
+	#[test]
+	fn test_pick_item_on_integer() {
+		// The bytes of 0x1234 are [0x34, 0x12].
+		let engine = run_script(vec![
+			OpCode::PushInt16 as u8,
+			0x34,
+			0x12,
+			OpCode::Push1 as u8,
+			OpCode::PickItem as u8,
+		]);
+		assert_eq!(engine.state, VMState::Halt);
+		assert_eq!(result(&engine, 0), StackItem::from(0x12));
+	}
+
+	#[test]
+	fn test_array_index_out_of_range_faults() {
+		for (index, opcode) in [
+			(OpCode::Push2, OpCode::PickItem),
+			(OpCode::PushM1, OpCode::PickItem),
+			(OpCode::Push2, OpCode::Remove),
+		] {
+			let script =
+				vec![OpCode::Push2 as u8, OpCode::NewArray as u8, index as u8, opcode as u8];
+			assert_eq!(run_script(script).state, VMState::Fault);
+		}
+		let engine = run_script(vec![
+			OpCode::Push2 as u8,
+			OpCode::NewArray as u8,
+			OpCode::Push2 as u8,
+			OpCode::Push1 as u8,
+			OpCode::SetItem as u8,
+		]);
+		assert_eq!(engine.state, VMState::Fault);
+		let engine = run_script(vec![OpCode::NewArray0 as u8, OpCode::PopItem as u8]);
+		assert_eq!(engine.state, VMState::Fault);
+	}
+
+	#[test]
+	fn test_map_key_missing() {
+		let engine =
+			run_script(vec![OpCode::NewMap as u8, OpCode::Push1 as u8, OpCode::PickItem as u8]);
+		assert_eq!(engine.state, VMState::Fault);
+
+		// Removing a missing key is not an error.
+		let engine = run_script(vec![
+			OpCode::NewMap as u8,
+			OpCode::Dup as u8,
+			OpCode::Push1 as u8,
+			OpCode::Remove as u8,
+			OpCode::Size as u8,
+		]);
+		assert_eq!(engine.state, VMState::Halt);
+		assert_eq!(result(&engine, 0), StackItem::from(0));
+	}
+
+	#[test]
+	fn test_set_item_byte_overflow_faults() {
+		let engine = run_script(vec![
+			OpCode::Push1 as u8,
+			OpCode::NewBuffer as u8,
+			OpCode::Push0 as u8,
+			OpCode::PushInt16 as u8,
+			0x00,
+			0x01,
+			OpCode::SetItem as u8,
+		]);
+		assert_eq!(engine.state, VMState::Fault);
+	}
+
+	#[test]
+	fn test_set_item_byte_non_primitive_faults() {
+		let values: [&[u8]; 3] = [
+			&[OpCode::Push1 as u8, OpCode::NewBuffer as u8],
+			&[OpCode::NewArray0 as u8],
+			&[OpCode::PushNull as u8],
+		];
+		for value in values {
+			let mut script =
+				vec![OpCode::Push1 as u8, OpCode::NewBuffer as u8, OpCode::Push0 as u8];
+			script.extend(value);
+			script.push(OpCode::SetItem as u8);
+			let engine = run_script(script);
+			assert_eq!(engine.state, VMState::Fault, "{value:?}");
+		}
+	}
+
+	#[test]
+	fn test_append_reverse_pop_and_clear() {
+		let engine = run_script(vec![
+			OpCode::NewArray0 as u8,
+			OpCode::Dup as u8,
+			OpCode::Push1 as u8,
+			OpCode::Append as u8,
+			OpCode::Dup as u8,
+			OpCode::Push2 as u8,
+			OpCode::Append as u8,
+			OpCode::Dup as u8,
+			OpCode::Push3 as u8,
+			OpCode::Append as u8,
+			OpCode::Dup as u8,
+			OpCode::ReverseItems as u8,
+			OpCode::Dup as u8,
+			OpCode::PopItem as u8,
+			OpCode::Over as u8,
+			OpCode::Push0 as u8,
+			OpCode::Remove as u8,
+			OpCode::Over as u8,
+			OpCode::Values as u8,
+			OpCode::Rot as u8,
+			OpCode::Dup as u8,
+			OpCode::ClearItems as u8,
+			OpCode::Size as u8,
+		]);
+		assert_eq!(engine.state, VMState::Halt);
+		assert_eq!(result(&engine, 0), StackItem::from(0));
+		assert_eq!(items(result(&engine, 1)), [StackItem::from(2)]);
+		assert_eq!(result(&engine, 2), StackItem::from(1));
+	}
+
+	#[test]
+	fn test_append_copies_structs() {
+		let engine = run_script(vec![
+			OpCode::NewArray0 as u8,
+			OpCode::Dup as u8,
+			OpCode::Push1 as u8,
+			OpCode::NewStruct as u8,
+			OpCode::Dup as u8,
+			OpCode::Rot as u8,
+			OpCode::Swap as u8,
+			OpCode::Append as u8,
+			// Changing the original struct leaves the appended copy untouched.
+			OpCode::Push0 as u8,
+			OpCode::Push5 as u8,
+			OpCode::SetItem as u8,
+			OpCode::Push0 as u8,
+			OpCode::PickItem as u8,
+			OpCode::Push0 as u8,
+			OpCode::PickItem as u8,
+		]);
+		assert_eq!(engine.state, VMState::Halt);
+		assert_eq!(result(&engine, 0), StackItem::Null);
+	}
+
+	#[test]
+	fn test_append_to_read_only_array_faults() {
+		let mut array = StackItem::Array(vec![StackItem::from(1).into()].into());
+		if let StackItem::Array(items) = &mut array {
+			items.set_read_only();
+		}
+		assert!(array.is_read_only());
+
+		let mut engine = ExecutionEngine::new();
+		engine
+			.load_script(Script::new(vec![OpCode::Push2 as u8, OpCode::Append as u8]), -1, 0)
+			.unwrap();
+		engine.push(array.into()).unwrap();
+		engine.step().unwrap();
+		assert!(matches!(engine.step(), Err(VMError::InvalidParameter(_))));
+		assert_eq!(engine.state, VMState::Fault);
+	}
+
+	#[test]
+	fn test_invalid_types_fault() {
+		for opcode in [OpCode::Append, OpCode::Remove] {
+			let engine = run_script(vec![OpCode::Push1 as u8, OpCode::Push0 as u8, opcode as u8]);
+			assert_eq!(engine.state, VMState::Fault, "{opcode:?}");
+		}
+		for opcode in [
+			OpCode::Values,
+			OpCode::ReverseItems,
+			OpCode::ClearItems,
+			OpCode::PopItem,
+			OpCode::Keys,
+		] {
+			let engine = run_script(vec![OpCode::Push1 as u8, opcode as u8]);
+			assert_eq!(engine.state, VMState::Fault, "{opcode:?}");
+		}
+	}
+
+	#[test]
+	fn test_pack_and_unpack() {
+		let engine = run_script(vec![
+			OpCode::Push1 as u8,
+			OpCode::Push2 as u8,
+			OpCode::Push2 as u8,
+			OpCode::Pack as u8,
+			OpCode::Unpack as u8,
+		]);
+		assert_eq!(engine.state, VMState::Halt);
+		assert_eq!(result(&engine, 0), StackItem::from(2));
+		assert_eq!(result(&engine, 1), StackItem::from(2));
+		assert_eq!(result(&engine, 2), StackItem::from(1));
+	}
+
+	#[test]
+	fn test_pack_map_and_has_key() {
+		let engine = run_script(vec![
+			OpCode::Push5 as u8,
+			OpCode::Push1 as u8,
+			OpCode::Push1 as u8,
+			OpCode::PackMap as u8,
+			OpCode::Dup as u8,
+			OpCode::Push1 as u8,
+			OpCode::HasKey as u8,
+			OpCode::Swap as u8,
+			OpCode::Size as u8,
+		]);
+		assert_eq!(engine.state, VMState::Halt);
+		assert_eq!(result(&engine, 0), StackItem::from(1));
+		assert_eq!(result(&engine, 1), StackItem::Boolean(true));
+	}
+
+	#[test]
+	fn test_new_array_t() {
+		let engine = run_script(vec![
+			OpCode::Push2 as u8,
+			OpCode::NewArrayT as u8,
+			0x21,
+			OpCode::Unpack as u8,
+		]);
+		assert_eq!(engine.state, VMState::Halt);
+		assert_eq!(result(&engine, 0), StackItem::from(2));
+		assert_eq!(result(&engine, 1), StackItem::from(0));
+
+		let engine = run_script(vec![
+			OpCode::Push2 as u8,
+			OpCode::NewArrayT as u8,
+			StackItemType::Boolean as u8,
+		]);
+		assert_eq!(engine.state, VMState::Halt);
+		assert_eq!(
+			items(result(&engine, 0)),
+			[StackItem::Boolean(false), StackItem::Boolean(false)]
+		);
+
+		for code in [StackItemType::Array as u8, StackItemType::Map as u8] {
+			let engine = run_script(vec![OpCode::Push1 as u8, OpCode::NewArrayT as u8, code]);
+			assert_eq!(engine.state, VMState::Halt);
+			assert_eq!(items(result(&engine, 0)), [StackItem::Null]);
+		}
+	}
+
+	#[test]
+	fn test_new_array_t_invalid_type_faults() {
+		let codes =
+			[StackItemType::Pointer as u8, StackItemType::InteropInterface as u8, 0x22, 0xff];
+		for code in codes {
+			let engine = run_script(vec![OpCode::Push1 as u8, OpCode::NewArrayT as u8, code]);
+			assert_eq!(engine.state, VMState::Fault, "{code:#x}");
+		}
+	}
+
+	#[test]
+	fn test_pack_underflow_faults() {
+		let engine = run_script(vec![OpCode::Push1 as u8, OpCode::Pack as u8]);
+		assert_eq!(engine.state, VMState::Fault);
+	}
+}
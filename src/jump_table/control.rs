@@ -0,0 +1,976 @@
+use crate::{
+	exception::{
+		exception_handling_context::ExceptionHandlingContext,
+		exception_handling_state::ExceptionHandlingState,
+	},
+	execution_engine::ExecutionEngine,
+	instruction::Instruction,
+	jump_table::JumpTable,
+	stack_item::StackItem,
+	vm_error::VMError,
+	vm_state::VMState,
+};
+use num_bigint::BigInt;
+use std::{cell::RefCell, rc::Rc};
+
+impl JumpTable {
+	pub fn nop(
+		&self,
+		_engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		Ok(())
+	}
+
+	pub fn jmp(
+		&self,
+		engine: &mut ExecutionEngine,
+		instruction: &Instruction,
+	) -> Result<(), VMError> {
+		self.execute_jump_offset(engine, instruction.token_i8() as i32)
+	}
+
+	pub fn jmp_l(
+		&self,
+		engine: &mut ExecutionEngine,
+		instruction: &Instruction,
+	) -> Result<(), VMError> {
+		self.execute_jump_offset(engine, instruction.token_i32())
+	}
+
+	pub fn jmp_if(
+		&self,
+		engine: &mut ExecutionEngine,
+		instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let condition = engine.pop()?.borrow().get_boolean()?;
+		self.jump_if(engine, condition, instruction.token_i8() as i32)
+	}
+
+	pub fn jmp_if_l(
+		&self,
+		engine: &mut ExecutionEngine,
+		instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let condition = engine.pop()?.borrow().get_boolean()?;
+		self.jump_if(engine, condition, instruction.token_i32())
+	}
+
+	pub fn jmp_if_not(
+		&self,
+		engine: &mut ExecutionEngine,
+		instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let condition = engine.pop()?.borrow().get_boolean()?;
+		self.jump_if(engine, !condition, instruction.token_i8() as i32)
+	}
+
+	pub fn jmp_if_not_l(
+		&self,
+		engine: &mut ExecutionEngine,
+		instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let condition = engine.pop()?.borrow().get_boolean()?;
+		self.jump_if(engine, !condition, instruction.token_i32())
+	}
+
+	pub fn jmp_eq(
+		&self,
+		engine: &mut ExecutionEngine,
+		instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let (x1, x2) = Self::pop_operands(engine)?;
+		self.jump_if(engine, x1 == x2, instruction.token_i8() as i32)
+	}
+
+	pub fn jmp_eq_l(
+		&self,
+		engine: &mut ExecutionEngine,
+		instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let (x1, x2) = Self::pop_operands(engine)?;
+		self.jump_if(engine, x1 == x2, instruction.token_i32())
+	}
+
+	pub fn jmp_ne(
+		&self,
+		engine: &mut ExecutionEngine,
+		instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let (x1, x2) = Self::pop_operands(engine)?;
+		self.jump_if(engine, x1 != x2, instruction.token_i8() as i32)
+	}
+
+	pub fn jmp_ne_l(
+		&self,
+		engine: &mut ExecutionEngine,
+		instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let (x1, x2) = Self::pop_operands(engine)?;
+		self.jump_if(engine, x1 != x2, instruction.token_i32())
+	}
+
+	pub fn jmp_gt(
+		&self,
+		engine: &mut ExecutionEngine,
+		instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let (x1, x2) = Self::pop_operands(engine)?;
+		self.jump_if(engine, x1 > x2, instruction.token_i8() as i32)
+	}
+
+	pub fn jmp_gt_l(
+		&self,
+		engine: &mut ExecutionEngine,
+		instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let (x1, x2) = Self::pop_operands(engine)?;
+		self.jump_if(engine, x1 > x2, instruction.token_i32())
+	}
+
+	pub fn jmp_ge(
+		&self,
+		engine: &mut ExecutionEngine,
+		instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let (x1, x2) = Self::pop_operands(engine)?;
+		self.jump_if(engine, x1 >= x2, instruction.token_i8() as i32)
+	}
+
+	pub fn jmp_ge_l(
+		&self,
+		engine: &mut ExecutionEngine,
+		instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let (x1, x2) = Self::pop_operands(engine)?;
+		self.jump_if(engine, x1 >= x2, instruction.token_i32())
+	}
+
+	pub fn jmp_lt(
+		&self,
+		engine: &mut ExecutionEngine,
+		instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let (x1, x2) = Self::pop_operands(engine)?;
+		self.jump_if(engine, x1 < x2, instruction.token_i8() as i32)
+	}
+
+	pub fn jmp_lt_l(
+		&self,
+		engine: &mut ExecutionEngine,
+		instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let (x1, x2) = Self::pop_operands(engine)?;
+		self.jump_if(engine, x1 < x2, instruction.token_i32())
+	}
+
+	pub fn jmp_le(
+		&self,
+		engine: &mut ExecutionEngine,
+		instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let (x1, x2) = Self::pop_operands(engine)?;
+		self.jump_if(engine, x1 <= x2, instruction.token_i8() as i32)
+	}
+
+	pub fn jmp_le_l(
+		&self,
+		engine: &mut ExecutionEngine,
+		instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let (x1, x2) = Self::pop_operands(engine)?;
+		self.jump_if(engine, x1 <= x2, instruction.token_i32())
+	}
+
+	pub fn call(
+		&self,
+		engine: &mut ExecutionEngine,
+		instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let position = Self::offset_position(engine, instruction.token_i8() as i32)?;
+		self.execute_call(engine, position)
+	}
+
+	pub fn call_l(
+		&self,
+		engine: &mut ExecutionEngine,
+		instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let position = Self::offset_position(engine, instruction.token_i32())?;
+		self.execute_call(engine, position)
+	}
+
+	/// Calls the instruction a popped `Pointer` into the current script points to.
+	pub fn call_a(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let position = match &*engine.pop()?.borrow() {
+			StackItem::Pointer { script, position } => {
+				if !Rc::ptr_eq(script, &engine.context()?.borrow().script()) {
+					return Err(VMError::InvalidJump(
+						"Pointers can't be shared between scripts".to_string(),
+					))
+				}
+				*position
+			},
+			item =>
+				return Err(VMError::InvalidType(format!(
+					"Expected a Pointer, found {:?}",
+					item.get_type()
+				))),
+		};
+		self.execute_call(engine, position)
+	}
+
+	pub fn call_t(
+		&self,
+		engine: &mut ExecutionEngine,
+		instruction: &Instruction,
+	) -> Result<(), VMError> {
+		engine.load_token(instruction.token_u16())
+	}
+
+	pub fn abort(
+		&self,
+		_engine: &mut ExecutionEngine,
+		instruction: &Instruction,
+	) -> Result<(), VMError> {
+		Err(VMError::Custom(format!("{:?} is executed.", instruction.opcode)))
+	}
+
+	pub fn assert(
+		&self,
+		engine: &mut ExecutionEngine,
+		instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let condition = engine.pop()?.borrow().get_boolean()?;
+		if !condition {
+			return Err(VMError::Custom(format!(
+				"{:?} is executed with false result.",
+				instruction.opcode
+			)))
+		}
+		Ok(())
+	}
+
+	pub fn throw(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let exception = engine.pop()?;
+		self.execute_throw(engine, exception)
+	}
+
+	pub fn try_(
+		&self,
+		engine: &mut ExecutionEngine,
+		instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let catch_offset = instruction.token_i8() as i32;
+		let finally_offset = instruction.token_i8_1() as i32;
+		self.execute_try(engine, catch_offset, finally_offset)
+	}
+
+	pub fn try_l(
+		&self,
+		engine: &mut ExecutionEngine,
+		instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let catch_offset = instruction.token_i32();
+		let finally_offset = instruction.token_i32_1();
+		self.execute_try(engine, catch_offset, finally_offset)
+	}
+
+	pub fn end_try(
+		&self,
+		engine: &mut ExecutionEngine,
+		instruction: &Instruction,
+	) -> Result<(), VMError> {
+		self.execute_end_try(engine, instruction.token_i8() as i32)
+	}
+
+	pub fn end_try_l(
+		&self,
+		engine: &mut ExecutionEngine,
+		instruction: &Instruction,
+	) -> Result<(), VMError> {
+		self.execute_end_try(engine, instruction.token_i32())
+	}
+
+	/// Leaves the current `finally` block, rethrowing the exception that entered it, if any, to
+	/// the next enclosing handler, or else jumping to the target of the `ENDTRY`.
+	pub fn end_finally(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let context = engine.context()?;
+		let current_try = context
+			.borrow_mut()
+			.try_stack
+			.as_mut()
+			.and_then(|try_stack| try_stack.pop())
+			.ok_or_else(|| {
+				VMError::InvalidParameter("The corresponding TRY block cannot be found.".into())
+			})?;
+		match engine.uncaught_exception.clone() {
+			None => context.borrow_mut().instruction_pointer = current_try.end_pointer() as usize,
+			Some(exception) => self.execute_throw(engine, exception)?,
+		}
+		engine.is_jumping = true;
+		Ok(())
+	}
+
+	pub fn ret(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let context_pop = engine
+			.invocation_stack
+			.pop()
+			.ok_or_else(|| VMError::Custom("The invocation stack is empty.".to_string()))?;
+		let stack_eval = match engine.invocation_stack.last() {
+			Some(context) => context.borrow().evaluation_stack(),
+			None => engine.result_stack.clone(),
+		};
+		let eval_pop = context_pop.borrow().evaluation_stack();
+		if !Rc::ptr_eq(&eval_pop, &stack_eval) {
+			let rv_count = context_pop.borrow().rv_count;
+			if rv_count >= 0 && eval_pop.borrow().count() != rv_count as usize {
+				return Err(VMError::InvalidParameter(
+					"RVCount doesn't match with EvaluationStack".to_string(),
+				))
+			}
+			eval_pop.borrow_mut().move_to(&mut stack_eval.borrow_mut(), -1)?;
+		}
+		if engine.invocation_stack.is_empty() {
+			engine.set_state(VMState::Halt);
+		}
+		engine.unload_context(context_pop);
+		engine.is_jumping = true;
+		Ok(())
+	}
+
+	pub fn syscall(
+		&self,
+		engine: &mut ExecutionEngine,
+		instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let hash = instruction.token_u32();
+		let handler = match engine.interop_service.get(hash) {
+			Some(service) => service.handler.clone(),
+			None => return Err(VMError::ItemNotFound(format!("Syscall {hash:#010x} not found."))),
+		};
+		handler(engine)
+	}
+
+	pub fn abort_msg(
+		&self,
+		engine: &mut ExecutionEngine,
+		instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let message = Self::pop_message(engine)?;
+		Err(VMError::Custom(format!("{:?} is executed. Reason: {message}", instruction.opcode)))
+	}
+
+	pub fn assert_msg(
+		&self,
+		engine: &mut ExecutionEngine,
+		instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let message = Self::pop_message(engine)?;
+		let condition = engine.pop()?.borrow().get_boolean()?;
+		if !condition {
+			return Err(VMError::Custom(format!(
+				"{:?} is executed with false result. Reason: {message}",
+				instruction.opcode
+			)))
+		}
+		Ok(())
+	}
+
+	/// Calls the function at `position` in the current script.
+	pub(crate) fn execute_call(
+		&self,
+		engine: &mut ExecutionEngine,
+		position: usize,
+	) -> Result<(), VMError> {
+		let context = engine.context()?.borrow().clone_at(position);
+		engine.load_context(Rc::new(RefCell::new(context)))
+	}
+
+	/// Jumps to `position` in the current script.
+	pub(crate) fn execute_jump(
+		&self,
+		engine: &mut ExecutionEngine,
+		position: usize,
+	) -> Result<(), VMError> {
+		let context = engine.context()?;
+		let mut context = context.borrow_mut();
+		if position >= context.script().borrow().len() {
+			return Err(VMError::InvalidJump(format!("Jump out of range for position: {position}")))
+		}
+		context.instruction_pointer = position;
+		engine.is_jumping = true;
+		Ok(())
+	}
+
+	/// Jumps `offset` bytes relative to the current instruction.
+	pub(crate) fn execute_jump_offset(
+		&self,
+		engine: &mut ExecutionEngine,
+		offset: i32,
+	) -> Result<(), VMError> {
+		let position = Self::offset_position(engine, offset)?;
+		self.execute_jump(engine, position)
+	}
+
+	/// Enters a `try` block with the specified `catch` and `finally` offsets.
+	pub(crate) fn execute_try(
+		&self,
+		engine: &mut ExecutionEngine,
+		catch_offset: i32,
+		finally_offset: i32,
+	) -> Result<(), VMError> {
+		if catch_offset == 0 && finally_offset == 0 {
+			return Err(VMError::InvalidParameter(
+				"catchOffset and finallyOffset can't be 0 in a TRY block".to_string(),
+			))
+		}
+		let catch_pointer = if catch_offset == 0 {
+			-1
+		} else {
+			Self::offset_position(engine, catch_offset)? as i32
+		};
+		let finally_pointer = if finally_offset == 0 {
+			-1
+		} else {
+			Self::offset_position(engine, finally_offset)? as i32
+		};
+		let try_context = ExceptionHandlingContext::new(catch_pointer, finally_pointer);
+		{
+			let context = engine.context()?;
+			let mut context = context.borrow_mut();
+			let try_stack = context.try_stack.get_or_insert_with(Vec::new);
+			if try_stack.len() >= engine.limits.max_try_nesting_depth {
+				return Err(VMError::TryNestingOverflow("MaxTryNestingDepth exceed.".to_string()))
+			}
+			try_stack.push(try_context);
+		}
+		engine.on_try_enter(&try_context);
+		Ok(())
+	}
+
+	/// Leaves the current `try` or `catch` block, running the `finally` block if there is one.
+	pub(crate) fn execute_end_try(
+		&self,
+		engine: &mut ExecutionEngine,
+		end_offset: i32,
+	) -> Result<(), VMError> {
+		let end_pointer = Self::offset_position(engine, end_offset)?;
+		let finally = {
+			let context = engine.context()?;
+			let mut context = context.borrow_mut();
+			let context = &mut *context;
+			let try_stack =
+				context.try_stack.as_mut().filter(|s| !s.is_empty()).ok_or_else(|| {
+					VMError::InvalidParameter(
+						"The corresponding TRY block cannot be found.".to_string(),
+					)
+				})?;
+			let current_try = try_stack.last_mut().unwrap();
+			if current_try.state() == ExceptionHandlingState::Finally {
+				return Err(VMError::InvalidParameter(
+					"The opcode ENDTRY can't be executed in a FINALLY block.".to_string(),
+				))
+			}
+			if current_try.has_finally() {
+				current_try.set_state(ExceptionHandlingState::Finally);
+				current_try.set_end_pointer(end_pointer as i32);
+				context.instruction_pointer = current_try.finally_pointer() as usize;
+				Some(*current_try)
+			} else {
+				try_stack.pop();
+				context.instruction_pointer = end_pointer;
+				None
+			}
+		};
+		if let Some(try_context) = finally {
+			engine.on_finally_enter(&try_context);
+		}
+		engine.is_jumping = true;
+		Ok(())
+	}
+
+	/// Throws `exception`, transferring control to the nearest `catch` or `finally` block and
+	/// unloading every context above it. Fails when no handler is left to take it.
+	pub(crate) fn execute_throw(
+		&self,
+		engine: &mut ExecutionEngine,
+		exception: Rc<RefCell<StackItem>>,
+	) -> Result<(), VMError> {
+		engine.uncaught_exception = Some(exception);
+
+		// Find the innermost frame with a handler able to take the exception, discarding
+		// handlers that have already finished their catch or finally block on the way.
+		let mut pop = None;
+		for (depth, context) in engine.invocation_stack.iter().rev().enumerate() {
+			let mut context = context.borrow_mut();
+			let Some(try_stack) = context.try_stack.as_mut() else { continue };
+			while let Some(try_context) = try_stack.last() {
+				if try_context.state() == ExceptionHandlingState::Finally
+					|| (try_context.state() == ExceptionHandlingState::Catch
+						&& !try_context.has_finally())
+				{
+					try_stack.pop();
+					continue
+				}
+				pop = Some(depth);
+				break
+			}
+			if pop.is_some() {
+				break
+			}
+		}
+		let Some(pop) = pop else {
+			return Err(VMError::Custom("An unhandled exception was thrown.".to_string()))
+		};
+
+		for _ in 0..pop {
+			if let Some(context) = engine.invocation_stack.pop() {
+				engine.unload_context(context);
+			}
+		}
+		let context = engine.context()?;
+		let try_context = {
+			let mut context = context.borrow_mut();
+			let context = &mut *context;
+			let try_context = context.try_stack.as_mut().and_then(|s| s.last_mut()).unwrap();
+			if try_context.state() == ExceptionHandlingState::Try && try_context.has_catch() {
+				try_context.set_state(ExceptionHandlingState::Catch);
+				context.instruction_pointer = try_context.catch_pointer() as usize;
+			} else {
+				try_context.set_state(ExceptionHandlingState::Finally);
+				context.instruction_pointer = try_context.finally_pointer() as usize;
+			}
+			*try_context
+		};
+		if try_context.state() == ExceptionHandlingState::Catch {
+			if let Some(exception) = engine.uncaught_exception.take() {
+				engine.push(exception)?;
+			}
+			engine.on_catch_enter(&try_context);
+		} else {
+			engine.on_finally_enter(&try_context);
+		}
+		engine.is_jumping = true;
+		Ok(())
+	}
+
+	fn jump_if(
+		&self,
+		engine: &mut ExecutionEngine,
+		condition: bool,
+		offset: i32,
+	) -> Result<(), VMError> {
+		if condition {
+			self.execute_jump_offset(engine, offset)?;
+		}
+		Ok(())
+	}
+
+	/// Resolves `offset` against the current instruction pointer.
+	pub(crate) fn offset_position(engine: &ExecutionEngine, offset: i32) -> Result<usize, VMError> {
+		let ip = engine.context()?.borrow().instruction_pointer as i64;
+		usize::try_from(ip + offset as i64)
+			.map_err(|_| VMError::InvalidJump(format!("Jump out of range for offset: {offset}")))
+	}
+
+	/// Pops the two integer operands of a comparison, returning them in push order.
+	fn pop_operands(engine: &mut ExecutionEngine) -> Result<(BigInt, BigInt), VMError> {
+		let x2 = engine.pop()?.borrow().get_integer()?;
+		let x1 = engine.pop()?.borrow().get_integer()?;
+		Ok((x1, x2))
+	}
+
+	fn pop_message(engine: &mut ExecutionEngine) -> Result<String, VMError> {
+		engine
+			.pop()?
+			.borrow()
+			.get_string()
+			.ok_or_else(|| VMError::InvalidType("The message is not a valid string.".to_string()))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::{
+		exception_hooks::{ExceptionHooks, RegionHook},
+		execution_engine::ExecutionEngine,
+		jump_table::test_helpers::{result, run_script},
+		method_token::MethodToken,
+		op_code::OpCode,
+		stack_item::StackItem,
+		vm::script::Script,
+		vm_error::VMError,
+		vm_state::VMState,
+	};
+	use std::{cell::RefCell, rc::Rc};
+
+	#[test]
+	fn test_push_a_call_a() {
+		let engine = run_script(vec![
+			OpCode::PushA as u8,
+			0x07,
+			0x00,
+			0x00,
+			0x00,
+			OpCode::CallA as u8,
+			OpCode::Ret as u8,
+			// The pointed-to function at offset 7.
+			OpCode::Push5 as u8,
+			OpCode::Ret as u8,
+		]);
+		assert_eq!(engine.state, VMState::Halt);
+		assert_eq!(engine.result_stack.borrow().count(), 1);
+		assert_eq!(result(&engine, 0), StackItem::from(5));
+
+		let engine = run_script(vec![OpCode::Push1 as u8, OpCode::CallA as u8]);
+		assert_eq!(engine.state, VMState::Fault);
+	}
+
+	#[test]
+	fn test_call_a_rejects_pointers_into_other_scripts() {
+		let mut engine = ExecutionEngine::new();
+		engine.load_script(Script::new(vec![OpCode::CallA as u8]), -1, 0).unwrap();
+		let other = Rc::new(RefCell::new(Script::new(vec![OpCode::CallA as u8])));
+		engine.push(StackItem::Pointer { script: other, position: 0 }.into()).unwrap();
+		assert_eq!(engine.execute(), VMState::Fault);
+	}
+
+	#[test]
+	fn test_call_t() {
+		let mut engine = ExecutionEngine::new();
+		engine.tokens.push(MethodToken {
+			hash: [0; 20],
+			method: "seven".to_string(),
+			parameters_count: 1,
+			has_return_value: true,
+			call_flags: 0,
+		});
+		engine.set_token_resolver(|engine, token| {
+			assert_eq!(token.method, "seven");
+			let argument = engine.pop()?;
+			let script = vec![OpCode::Add as u8, OpCode::Ret as u8];
+			let context = engine.load_script(Script::new(script), 1, 0)?;
+			context.borrow().evaluation_stack().borrow_mut().push(StackItem::from(7).into());
+			context.borrow().evaluation_stack().borrow_mut().push(argument);
+			Ok(())
+		});
+		let script =
+			vec![OpCode::Push1 as u8, OpCode::CallT as u8, 0x00, 0x00, OpCode::Push2 as u8];
+		engine.load_script(Script::new(script.clone()), -1, 0).unwrap();
+		assert_eq!(engine.execute(), VMState::Halt);
+		assert_eq!(result(&engine, 0), StackItem::from(2));
+		assert_eq!(result(&engine, 1), StackItem::from(8));
+
+		let mut script = script;
+		script[3] = 0x01;
+		let mut engine = ExecutionEngine::new();
+		engine.set_token_resolver(|_, _| Ok(()));
+		engine.load_script(Script::new(script), -1, 0).unwrap();
+		assert_eq!(engine.execute(), VMState::Fault);
+	}
+
+	#[test]
+	fn test_exception_hooks() {
+		let events = Rc::new(RefCell::new(Vec::new()));
+		let mut engine = ExecutionEngine::new();
+		let record = |name: &'static str| -> RegionHook {
+			let events = events.clone();
+			Rc::new(move |_, _| events.borrow_mut().push(name))
+		};
+		engine.exception_hooks = ExceptionHooks {
+			on_try_enter: Some(record("try")),
+			on_catch_enter: Some(record("catch")),
+			on_finally_enter: Some(record("finally")),
+		};
+		let script = vec![
+			OpCode::Try as u8,
+			0x05,
+			0x08,
+			OpCode::Push1 as u8,
+			OpCode::Throw as u8,
+			OpCode::Drop as u8,
+			OpCode::EndTry as u8,
+			0x04,
+			OpCode::Push3 as u8,
+			OpCode::EndFinally as u8,
+			OpCode::Push4 as u8,
+		];
+		engine.load_script(Script::new(script), -1, 0).unwrap();
+		assert_eq!(engine.execute(), VMState::Halt);
+		assert_eq!(*events.borrow(), ["try", "catch", "finally"]);
+		assert_eq!(result(&engine, 0), StackItem::from(4));
+		assert_eq!(result(&engine, 1), StackItem::from(3));
+	}
+
+	#[test]
+	fn test_syscall() {
+		let mut script = vec![OpCode::Syscall as u8];
+		script.extend(0x12345678u32.to_le_bytes());
+
+		let mut engine = ExecutionEngine::new();
+		engine.register_syscall(0x12345678, |engine| engine.push(StackItem::from(42).into()));
+		engine.load_script(Script::new(script.clone()), -1, 0).unwrap();
+		assert_eq!(engine.execute(), VMState::Halt);
+		assert_eq!(result(&engine, 0), StackItem::from(42));
+
+		assert_eq!(run_script(script).state, VMState::Fault);
+	}
+
+	#[test]
+	fn test_jmp_if_not_skips_when_false() {
+		let engine = run_script(vec![
+			OpCode::PushFalse as u8,
+			OpCode::JmpIfNot as u8,
+			0x03,
+			OpCode::Push1 as u8,
+			OpCode::Push2 as u8,
+		]);
+		assert_eq!(engine.state, VMState::Halt);
+		assert_eq!(engine.result_stack.borrow().count(), 1);
+		assert_eq!(result(&engine, 0), StackItem::from(2));
+	}
+
+	#[test]
+	fn test_jump_out_of_range_faults() {
+		let engine = run_script(vec![OpCode::Jmp as u8, 0x7F]);
+		assert_eq!(engine.state, VMState::Fault);
+	}
+
+	#[test]
+	fn test_call_and_ret() {
+		// CALL +3; RET; PUSH7; RET
+		let engine = run_script(vec![
+			OpCode::Call as u8,
+			0x03,
+			OpCode::Ret as u8,
+			OpCode::Push7 as u8,
+			OpCode::Ret as u8,
+		]);
+		assert_eq!(engine.state, VMState::Halt);
+		assert_eq!(result(&engine, 0), StackItem::from(7));
+	}
+
+	#[test]
+	fn test_try_catch() {
+		// TRY +5 (catch), 0; PUSH3; THROW; [catch] DROP; PUSH1
+		let engine = run_script(vec![
+			OpCode::Try as u8,
+			0x05,
+			0x00,
+			OpCode::Push3 as u8,
+			OpCode::Throw as u8,
+			OpCode::Drop as u8,
+			OpCode::Push1 as u8,
+		]);
+		assert_eq!(engine.state, VMState::Halt);
+		assert_eq!(result(&engine, 0), StackItem::from(1));
+	}
+
+	#[test]
+	fn test_try_finally_rethrows() {
+		let engine = run_script(vec![
+			// TRY +14 (catch), 0
+			OpCode::Try as u8,
+			0x0E,
+			0x00,
+			// TRY 0, +7 (finally); PUSH1; THROW; ENDTRY +4
+			OpCode::Try as u8,
+			0x00,
+			0x07,
+			OpCode::Push1 as u8,
+			OpCode::Throw as u8,
+			OpCode::EndTry as u8,
+			0x04,
+			// [finally] PUSH2; ENDFINALLY
+			OpCode::Push2 as u8,
+			OpCode::EndFinally as u8,
+			// ENDTRY +3
+			OpCode::EndTry as u8,
+			0x03,
+			// [catch] PUSH3
+			OpCode::Push3 as u8,
+		]);
+		assert_eq!(engine.state, VMState::Halt);
+		assert!(engine.uncaught_exception.is_none());
+		assert_eq!(engine.result_stack.borrow().count(), 3);
+		assert_eq!(result(&engine, 0), StackItem::from(3));
+		assert_eq!(result(&engine, 1), StackItem::from(1));
+		assert_eq!(result(&engine, 2), StackItem::from(2));
+
+		// Without the outer catch, the exception is still uncaught after the finally block.
+		let engine = run_script(vec![
+			OpCode::Try as u8,
+			0x00,
+			0x07,
+			OpCode::Push1 as u8,
+			OpCode::Throw as u8,
+			OpCode::EndTry as u8,
+			0x04,
+			OpCode::Push2 as u8,
+			OpCode::EndFinally as u8,
+		]);
+		assert_eq!(engine.state, VMState::Fault);
+		let exception = engine.uncaught_exception.as_ref().unwrap();
+		assert_eq!(*exception.borrow(), StackItem::from(1));
+	}
+
+	#[test]
+	fn test_try_finally_falls_through() {
+		// TRY 0, +6 (finally); PUSH1; ENDTRY +4; [finally] PUSH2; ENDFINALLY; PUSH3
+		let engine = run_script(vec![
+			OpCode::Try as u8,
+			0x00,
+			0x06,
+			OpCode::Push1 as u8,
+			OpCode::EndTry as u8,
+			0x04,
+			OpCode::Push2 as u8,
+			OpCode::EndFinally as u8,
+			OpCode::Push3 as u8,
+		]);
+		assert_eq!(engine.state, VMState::Halt);
+		for (index, value) in [3, 2, 1].into_iter().enumerate() {
+			assert_eq!(result(&engine, index as i32), StackItem::from(value));
+		}
+	}
+
+	#[test]
+	fn test_throw_caught_two_frames_up() {
+		let script = vec![
+			// TRY +7 (catch), 0; CALL +7; ENDTRY +4
+			OpCode::Try as u8,
+			0x07,
+			0x00,
+			OpCode::Call as u8,
+			0x07,
+			OpCode::EndTry as u8,
+			0x04,
+			// [catch] ENDTRY +2; RET
+			OpCode::EndTry as u8,
+			0x02,
+			OpCode::Ret as u8,
+			// INITSLOT 1, 0; NEWARRAY0; STLOC0; CALL +3; RET
+			OpCode::InitSlot as u8,
+			0x01,
+			0x00,
+			OpCode::NewArray0 as u8,
+			OpCode::StLoc0 as u8,
+			OpCode::Call as u8,
+			0x03,
+			OpCode::Ret as u8,
+			// PUSH1; THROW
+			OpCode::Push1 as u8,
+			OpCode::Throw as u8,
+		];
+		let depths = Rc::new(RefCell::new(Vec::new()));
+		let mut engine = ExecutionEngine::new();
+		let catch_depths = depths.clone();
+		engine.exception_hooks.on_catch_enter = Some(Rc::new(move |engine, _| {
+			catch_depths.borrow_mut().push(engine.invocation_stack.len())
+		}));
+		engine.load_script(Script::new(script), -1, 0).unwrap();
+		assert_eq!(engine.execute(), VMState::Halt);
+		// Both called frames were unloaded before the catch block ran, releasing the array
+		// held by the local variable of the first one.
+		assert_eq!(*depths.borrow(), [1]);
+		assert_eq!(engine.result_stack.borrow().count(), 1);
+		assert_eq!(result(&engine, 0), StackItem::from(1));
+		assert_eq!(engine.reference_counter.borrow_mut().check_zero_referred(), 1);
+	}
+
+	#[test]
+	fn test_throw_releases_the_stack_of_unwound_frames() {
+		let mut engine = ExecutionEngine::new();
+		engine.tokens.push(MethodToken {
+			hash: [0; 20],
+			method: "fail".to_string(),
+			parameters_count: 0,
+			has_return_value: false,
+			call_flags: 0,
+		});
+		// The called script has its own evaluation stack, and leaves 2 on it when it throws.
+		engine.set_token_resolver(|engine, _| {
+			let script = vec![OpCode::Push2 as u8, OpCode::Push1 as u8, OpCode::Throw as u8];
+			engine.load_script(Script::new(script), 0, 0)?;
+			Ok(())
+		});
+		let mut script = Vec::new();
+		for _ in 0..3 {
+			// TRY +8 (catch), 0; CALLT 0; ENDTRY +5; [catch] DROP; ENDTRY +2
+			script.extend([
+				OpCode::Try as u8,
+				0x08,
+				0x00,
+				OpCode::CallT as u8,
+				0x00,
+				0x00,
+				OpCode::EndTry as u8,
+				0x05,
+				OpCode::Drop as u8,
+				OpCode::EndTry as u8,
+				0x02,
+			]);
+		}
+		// Without releasing the items left behind, the third call would exceed the limit.
+		engine.limits.max_stack_size = 3;
+		engine.load_script(Script::new(script), -1, 0).unwrap();
+		assert_eq!(engine.execute(), VMState::Halt);
+		assert_eq!(engine.reference_counter.borrow().count(), 0);
+	}
+
+	#[test]
+	fn test_try_nesting_overflow() {
+		let mut engine = ExecutionEngine::new();
+		engine.limits.max_try_nesting_depth = 2;
+		// TRY +3 (catch), 0, three times
+		let script = [OpCode::Try as u8, 0x03, 0x00].repeat(3);
+		engine.load_script(Script::new(script), -1, 0).unwrap();
+		assert_eq!(engine.execute(), VMState::Fault);
+		let depth = engine.context().unwrap().borrow().try_stack.as_ref().map(Vec::len);
+		assert_eq!(depth, Some(2));
+		match engine.last_error {
+			Some(VMError::FaultAt { ip, opcode, reason }) => {
+				assert_eq!((ip, opcode), (6, OpCode::Try));
+				assert!(matches!(*reason, VMError::TryNestingOverflow(_)));
+			},
+			error => panic!("unexpected error: {error:?}"),
+		}
+	}
+
+	#[test]
+	fn test_uncaught_throw_faults() {
+		let engine = run_script(vec![OpCode::Push3 as u8, OpCode::Throw as u8]);
+		assert_eq!(engine.state, VMState::Fault);
+		assert!(engine.uncaught_exception.is_some());
+	}
+
+	#[test]
+	fn test_abort_and_assert() {
+		assert_eq!(run_script(vec![OpCode::Abort as u8]).state, VMState::Fault);
+		assert_eq!(
+			run_script(vec![OpCode::PushFalse as u8, OpCode::Assert as u8]).state,
+			VMState::Fault
+		);
+		assert_eq!(
+			run_script(vec![OpCode::PushTrue as u8, OpCode::Assert as u8]).state,
+			VMState::Halt
+		);
+	}
+}
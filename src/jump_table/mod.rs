@@ -0,0 +1,447 @@
+use crate::{
+	execution_engine::ExecutionEngine, instruction::Instruction, op_code::OpCode, vm_error::VMError,
+};
+use num_bigint::BigInt;
+use num_traits::ToPrimitive;
+
+mod bitwise;
+mod compound;
+mod control;
+mod numeric;
+mod push;
+mod slot;
+mod splice;
+mod stack;
+mod types;
+
+/// A function executing a single instruction against the engine.
+pub type InstructionHandler =
+	fn(&JumpTable, &mut ExecutionEngine, &Instruction) -> Result<(), VMError>;
+
+/// Maps each `OpCode` to the handler used to execute it.
+#[derive(Clone, Debug)]
+pub struct JumpTable {
+	/// The handlers, indexed by the byte value of their opcode.
+	table: [Option<InstructionHandler>; 256],
+}
+
+impl Default for JumpTable {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl JumpTable {
+	/// Creates a jump table with the default handler registered for each supported opcode.
+	pub fn new() -> Self {
+		let mut jump_table = Self { table: [None; 256] };
+		jump_table.initialize();
+		jump_table
+	}
+
+	fn initialize(&mut self) {
+		// Push
+		for opcode in [
+			OpCode::PushInt8,
+			OpCode::PushInt16,
+			OpCode::PushInt32,
+			OpCode::PushInt64,
+			OpCode::PushInt128,
+			OpCode::PushInt256,
+		] {
+			self.register(opcode, Self::push_int);
+		}
+		self.register(OpCode::PushTrue, Self::push_true);
+		self.register(OpCode::PushFalse, Self::push_false);
+		self.register(OpCode::PushA, Self::push_a);
+		self.register(OpCode::PushNull, Self::push_null);
+		for opcode in [OpCode::PushData1, OpCode::PushData2, OpCode::PushData4] {
+			self.register(opcode, Self::push_data);
+		}
+		for code in OpCode::PushM1 as u8..=OpCode::Push16 as u8 {
+			if let Some(opcode) = num_traits::FromPrimitive::from_u8(code) {
+				self.register(opcode, Self::push_const);
+			}
+		}
+
+		// Control
+		self.register(OpCode::Nop, Self::nop);
+		self.register(OpCode::Jmp, Self::jmp);
+		self.register(OpCode::JmpL, Self::jmp_l);
+		self.register(OpCode::JmpIf, Self::jmp_if);
+		self.register(OpCode::JmpIfL, Self::jmp_if_l);
+		self.register(OpCode::JmpIfNot, Self::jmp_if_not);
+		self.register(OpCode::JmpIfNotL, Self::jmp_if_not_l);
+		self.register(OpCode::JmpEq, Self::jmp_eq);
+		self.register(OpCode::JmpEqL, Self::jmp_eq_l);
+		self.register(OpCode::JmpNe, Self::jmp_ne);
+		self.register(OpCode::JmpNeL, Self::jmp_ne_l);
+		self.register(OpCode::JmpGt, Self::jmp_gt);
+		self.register(OpCode::JmpGtL, Self::jmp_gt_l);
+		self.register(OpCode::JmpGe, Self::jmp_ge);
+		self.register(OpCode::JmpGeL, Self::jmp_ge_l);
+		self.register(OpCode::JmpLt, Self::jmp_lt);
+		self.register(OpCode::JmpLtL, Self::jmp_lt_l);
+		self.register(OpCode::JmpLe, Self::jmp_le);
+		self.register(OpCode::JmpLeL, Self::jmp_le_l);
+		self.register(OpCode::Call, Self::call);
+		self.register(OpCode::CallL, Self::call_l);
+		self.register(OpCode::CallA, Self::call_a);
+		self.register(OpCode::CallT, Self::call_t);
+		self.register(OpCode::Abort, Self::abort);
+		self.register(OpCode::Assert, Self::assert);
+		self.register(OpCode::Throw, Self::throw);
+		self.register(OpCode::Try, Self::try_);
+		self.register(OpCode::TryL, Self::try_l);
+		self.register(OpCode::EndTry, Self::end_try);
+		self.register(OpCode::EndTryL, Self::end_try_l);
+		self.register(OpCode::EndFinally, Self::end_finally);
+		self.register(OpCode::Ret, Self::ret);
+		self.register(OpCode::Syscall, Self::syscall);
+		self.register(OpCode::AbortMsg, Self::abort_msg);
+		self.register(OpCode::AssertMsg, Self::assert_msg);
+
+		// Stack
+		self.register(OpCode::Depth, Self::depth);
+		self.register(OpCode::Drop, Self::drop);
+		self.register(OpCode::Nip, Self::nip);
+		self.register(OpCode::Xdrop, Self::xdrop);
+		self.register(OpCode::Clear, Self::clear);
+		self.register(OpCode::Dup, Self::dup);
+		self.register(OpCode::Over, Self::over);
+		self.register(OpCode::Pick, Self::pick);
+		self.register(OpCode::Tuck, Self::tuck);
+		self.register(OpCode::Swap, Self::swap);
+		self.register(OpCode::Rot, Self::rot);
+		self.register(OpCode::Roll, Self::roll);
+		self.register(OpCode::Reverse3, Self::reverse3);
+		self.register(OpCode::Reverse4, Self::reverse4);
+		self.register(OpCode::ReverseN, Self::reverse_n);
+
+		// Slot
+		self.register(OpCode::InitSSLot, Self::init_sslot);
+		self.register(OpCode::InitSlot, Self::init_slot);
+		for code in OpCode::LdSFLd0 as u8..=OpCode::StArg as u8 {
+			let Some(opcode) = num_traits::FromPrimitive::from_u8(code) else { continue };
+			let handler: InstructionHandler = match code {
+				c if c <= OpCode::LdSFLd as u8 => Self::ld_sfld,
+				c if c <= OpCode::StSFLd as u8 => Self::st_sfld,
+				c if c <= OpCode::LdLoc as u8 => Self::ld_loc,
+				c if c <= OpCode::StLoc as u8 => Self::st_loc,
+				c if c <= OpCode::LdArg as u8 => Self::ld_arg,
+				_ => Self::st_arg,
+			};
+			self.register(opcode, handler);
+		}
+
+		// Splice
+		self.register(OpCode::NewBuffer, Self::new_buffer);
+		self.register(OpCode::MemCpy, Self::memcpy);
+		self.register(OpCode::Cat, Self::cat);
+		self.register(OpCode::Substr, Self::substr);
+		self.register(OpCode::Left, Self::left);
+		self.register(OpCode::Right, Self::right);
+
+		// Bitwise logic
+		self.register(OpCode::Invert, Self::invert);
+		self.register(OpCode::And, Self::and);
+		self.register(OpCode::Or, Self::or);
+		self.register(OpCode::Xor, Self::xor);
+		self.register(OpCode::Equal, Self::equal);
+		self.register(OpCode::NotEqual, Self::not_equal);
+
+		// Numeric
+		self.register(OpCode::Sign, Self::sign);
+		self.register(OpCode::Abs, Self::abs);
+		self.register(OpCode::Negate, Self::negate);
+		self.register(OpCode::Inc, Self::inc);
+		self.register(OpCode::Dec, Self::dec);
+		self.register(OpCode::Add, Self::add);
+		self.register(OpCode::Sub, Self::sub);
+		self.register(OpCode::Mul, Self::mul);
+		self.register(OpCode::Div, Self::div);
+		self.register(OpCode::Mod, Self::mod_op);
+		self.register(OpCode::Pow, Self::pow);
+		self.register(OpCode::Sqrt, Self::sqrt);
+		self.register(OpCode::ModMul, Self::mod_mul);
+		self.register(OpCode::ModPow, Self::mod_pow);
+		self.register(OpCode::Shl, Self::shl);
+		self.register(OpCode::Shr, Self::shr);
+		self.register(OpCode::Not, Self::not);
+		self.register(OpCode::BoolAnd, Self::bool_and);
+		self.register(OpCode::BoolOr, Self::bool_or);
+		self.register(OpCode::Nz, Self::nz);
+		self.register(OpCode::NumEqual, Self::num_equal);
+		self.register(OpCode::NumNotEqual, Self::num_not_equal);
+		self.register(OpCode::Lt, Self::lt);
+		self.register(OpCode::Le, Self::le);
+		self.register(OpCode::Gt, Self::gt);
+		self.register(OpCode::Ge, Self::ge);
+		self.register(OpCode::Min, Self::min);
+		self.register(OpCode::Max, Self::max);
+		self.register(OpCode::Within, Self::within);
+
+		// Compound-type
+		self.register(OpCode::PackMap, Self::pack_map);
+		self.register(OpCode::PackStruct, Self::pack_struct);
+		self.register(OpCode::Pack, Self::pack);
+		self.register(OpCode::Unpack, Self::unpack);
+		self.register(OpCode::NewArray0, Self::new_array0);
+		self.register(OpCode::NewArray, Self::new_array);
+		self.register(OpCode::NewArrayT, Self::new_array_t);
+		self.register(OpCode::NewStruct0, Self::new_struct0);
+		self.register(OpCode::NewStruct, Self::new_struct);
+		self.register(OpCode::NewMap, Self::new_map);
+		self.register(OpCode::Size, Self::size);
+		self.register(OpCode::HasKey, Self::has_key);
+		self.register(OpCode::Keys, Self::keys);
+		self.register(OpCode::Values, Self::values);
+		self.register(OpCode::PickItem, Self::pick_item);
+		self.register(OpCode::Append, Self::append);
+		self.register(OpCode::SetItem, Self::set_item);
+		self.register(OpCode::ReverseItems, Self::reverse_items);
+		self.register(OpCode::Remove, Self::remove);
+		self.register(OpCode::ClearItems, Self::clear_items);
+		self.register(OpCode::PopItem, Self::pop_item);
+
+		// Types
+		self.register(OpCode::IsNull, Self::is_null);
+		self.register(OpCode::IsType, Self::is_type);
+		self.register(OpCode::Convert, Self::convert);
+	}
+
+	/// Registers `handler` for `opcode`, replacing any existing handler.
+	pub fn register(&mut self, opcode: OpCode, handler: InstructionHandler) {
+		self.table[opcode as usize] = Some(handler);
+	}
+
+	/// Returns the handler registered for `opcode`, if any.
+	pub fn get(&self, opcode: OpCode) -> Option<InstructionHandler> {
+		self.table[opcode as usize]
+	}
+
+	/// Executes `instruction` with the handler registered for its opcode.
+	pub fn execute(
+		&self,
+		engine: &mut ExecutionEngine,
+		instruction: &Instruction,
+	) -> Result<(), VMError> {
+		match self.get(instruction.opcode) {
+			Some(handler) => handler(self, engine, instruction),
+			None =>
+				Err(VMError::InvalidOpcode(format!("Opcode {} is undefined.", instruction.opcode))),
+		}
+	}
+}
+
+/// Converts an integer popped from the stack to `i32`, failing when it is out of range.
+pub(crate) fn to_i32(value: &BigInt) -> Result<i32, VMError> {
+	value
+		.to_i32()
+		.ok_or_else(|| VMError::InvalidParameter(format!("The value {value} is out of range.")))
+}
+
+/// Converts an integer popped from the stack to `usize`, failing when it is negative or out
+/// of range.
+pub(crate) fn to_usize(value: &BigInt) -> Result<usize, VMError> {
+	value
+		.to_usize()
+		.ok_or_else(|| VMError::InvalidParameter(format!("The value {value} is out of range.")))
+}
+
+#[cfg(test)]
+pub(crate) mod test_helpers {
+	use crate::{
+		execution_engine::ExecutionEngine, stack_item::StackItem, vm::script::Script,
+		vm_state::VMState,
+	};
+	use std::{cell::RefCell, rc::Rc};
+
+	/// Runs `script` to completion on a fresh engine.
+	pub(crate) fn run_script(script: Vec<u8>) -> ExecutionEngine {
+		let mut engine = ExecutionEngine::new();
+		engine.load_script(Script::new(script), -1, 0).unwrap();
+		engine.execute();
+		engine
+	}
+
+	/// Returns a copy of the item at `index` from the top of the result stack.
+	pub(crate) fn result(engine: &ExecutionEngine, index: i32) -> StackItem {
+		engine.result_stack.borrow().peek(index).unwrap().borrow().clone()
+	}
+
+	/// Runs `script` on a fresh engine after pushing `input` onto its evaluation stack, and
+	/// returns the final state and the result stack from the bottom up.
+	fn run_with_input(
+		script: &[u8],
+		input: &[StackItem],
+	) -> (VMState, Vec<Rc<RefCell<StackItem>>>) {
+		let mut engine = ExecutionEngine::new();
+		engine.load_script(Script::new(script.to_vec()), -1, 0).unwrap();
+		for item in input {
+			engine.push(item.clone().into()).unwrap();
+		}
+		let state = engine.execute();
+		let results = engine.result_stack.borrow().iter().cloned().collect();
+		(state, results)
+	}
+
+	/// Indicates whether `a` and `b` hold the same value. Compound items are compared element
+	/// by element, as the items of two engines never share references, and a pair met again
+	/// while it is being compared is taken as equal, so that circular references terminate.
+	fn same_value(
+		a: &Rc<RefCell<StackItem>>,
+		b: &Rc<RefCell<StackItem>>,
+		comparing: &mut Vec<(*const RefCell<StackItem>, *const RefCell<StackItem>)>,
+	) -> bool {
+		let pair = (Rc::as_ptr(a), Rc::as_ptr(b));
+		if comparing.contains(&pair) {
+			return true
+		}
+		comparing.push(pair);
+		let same = match (&*a.borrow(), &*b.borrow()) {
+			(StackItem::Array(x), StackItem::Array(y))
+			| (StackItem::Struct(x), StackItem::Struct(y)) =>
+				x.len() == y.len() && x.iter().zip(y).all(|(x, y)| same_value(x, y, comparing)),
+			(StackItem::Map(x), StackItem::Map(y)) =>
+				x.len() == y.len()
+					&& x.iter()
+						.all(|(key, x)| y.get(key).is_some_and(|y| same_value(x, y, comparing))),
+			(
+				StackItem::Pointer { script: x, position: i },
+				StackItem::Pointer { script: y, position: j },
+			) => i == j && x.borrow().value() == y.borrow().value(),
+			(x, y) => x == y,
+		};
+		comparing.pop();
+		same
+	}
+
+	/// Asserts that `a` and `b` end in the same state with result stacks holding the same
+	/// values for every input stack in `inputs`, where each input is listed from the bottom up.
+	pub(crate) fn assert_scripts_equivalent(a: &[u8], b: &[u8], inputs: &[Vec<StackItem>]) {
+		for input in inputs {
+			let (state_a, results_a) = run_with_input(a, input);
+			let (state_b, results_b) = run_with_input(b, input);
+			let same = state_a == state_b
+				&& results_a.len() == results_b.len()
+				&& results_a.iter().zip(&results_b).all(|(x, y)| same_value(x, y, &mut Vec::new()));
+			assert!(same, "the scripts diverge on input {input:?}");
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		stack_item::StackItem, stack_item_type::StackItemType, vm::script::Script,
+		vm_state::VMState,
+	};
+	use num_traits::FromPrimitive;
+	use std::rc::Rc;
+
+	/// The opcodes the default jump table doesn't handle yet. Wiring one up means removing it
+	/// from this list; defining a new opcode without a handler means adding it here.
+	const UNHANDLED: [OpCode; 0] = [];
+
+	#[test]
+	fn test_assert_scripts_equivalent() {
+		let inputs = [vec![], vec![StackItem::from(5)], vec![StackItem::Null, StackItem::from(-1)]];
+		test_helpers::assert_scripts_equivalent(
+			&[OpCode::Push1 as u8, OpCode::Push1 as u8, OpCode::Add as u8],
+			&[OpCode::Push2 as u8],
+			&inputs,
+		);
+		// Both fault without an input to add to.
+		test_helpers::assert_scripts_equivalent(
+			&[OpCode::Push1 as u8, OpCode::Add as u8],
+			&[OpCode::Add as u8],
+			&[vec![]],
+		);
+		// Compound results of the two engines are compared by value.
+		let pack = [OpCode::Push1 as u8, OpCode::Push1 as u8, OpCode::Pack as u8];
+		test_helpers::assert_scripts_equivalent(&pack, &pack, &[vec![]]);
+		test_helpers::assert_scripts_equivalent(
+			&pack,
+			&[
+				OpCode::NewArray0 as u8,
+				OpCode::Dup as u8,
+				OpCode::Push1 as u8,
+				OpCode::Append as u8,
+			],
+			&[vec![]],
+		);
+	}
+
+	#[test]
+	#[should_panic(expected = "the scripts diverge")]
+	fn test_assert_scripts_equivalent_compares_elements() {
+		test_helpers::assert_scripts_equivalent(
+			&[OpCode::Push1 as u8, OpCode::Push1 as u8, OpCode::Pack as u8],
+			&[OpCode::Push2 as u8, OpCode::Push1 as u8, OpCode::Pack as u8],
+			&[vec![]],
+		);
+	}
+
+	#[test]
+	#[should_panic(expected = "the scripts diverge")]
+	fn test_assert_scripts_equivalent_detects_divergence() {
+		test_helpers::assert_scripts_equivalent(
+			&[OpCode::Push1 as u8],
+			&[OpCode::Push2 as u8],
+			&[vec![]],
+		);
+	}
+
+	#[test]
+	fn test_handler_errors_fault() {
+		// A failing instruction from each group of handlers, after valid ones where it needs
+		// operands.
+		let scripts: [&[u8]; 9] = [
+			&[OpCode::PushData1 as u8, 0x02, 0x00],
+			&[OpCode::Jmp as u8, 0x7f],
+			&[OpCode::Push1 as u8, OpCode::Push0 as u8, OpCode::Div as u8],
+			&[
+				OpCode::Push1 as u8,
+				OpCode::Push0 as u8,
+				OpCode::Shl as u8,
+				OpCode::Invert as u8,
+				OpCode::Xdrop as u8,
+			],
+			&[OpCode::LdLoc0 as u8],
+			&[OpCode::NewMap as u8, OpCode::Push0 as u8, OpCode::PickItem as u8],
+			&[OpCode::Push1 as u8, OpCode::Push2 as u8, OpCode::Substr as u8],
+			&[OpCode::NewMap as u8, OpCode::And as u8],
+			&[OpCode::NewMap as u8, OpCode::Convert as u8, StackItemType::Integer as u8],
+		];
+		for script in scripts {
+			let engine = test_helpers::run_script(script.to_vec());
+			assert_eq!(engine.state, VMState::Fault, "{script:?}");
+			assert!(engine.last_error.is_some(), "{script:?}");
+		}
+
+		// An opcode without a handler faults instead of being skipped.
+		let mut jump_table = JumpTable::new();
+		jump_table.table[OpCode::Nop as usize] = None;
+		let mut engine = ExecutionEngine::new();
+		engine.jump_table = Rc::new(jump_table);
+		let script = Script::new(vec![OpCode::Nop as u8, OpCode::Push1 as u8]);
+		engine.load_script(script, -1, 0).unwrap();
+		assert_eq!(engine.execute(), VMState::Fault);
+		assert!(matches!(
+			engine.last_error,
+			Some(VMError::FaultAt { ip: 0, opcode: OpCode::Nop, .. })
+		));
+	}
+
+	#[test]
+	fn test_opcode_coverage() {
+		let jump_table = JumpTable::default();
+		let unhandled: Vec<OpCode> = (0..=u8::MAX)
+			.filter_map(OpCode::from_u8)
+			.filter(|&opcode| jump_table.get(opcode).is_none())
+			.collect();
+		assert_eq!(unhandled, UNHANDLED, "the unhandled opcodes changed");
+	}
+}
@@ -0,0 +1,709 @@
+use crate::{
+	execution_engine::ExecutionEngine,
+	instruction::Instruction,
+	jump_table::{to_i32, JumpTable},
+	stack_item::StackItem,
+	utility::mod_inverse,
+	vm_error::VMError,
+};
+use num_bigint::{BigInt, Sign};
+use num_traits::{One, Signed, Zero};
+
+impl JumpTable {
+	pub fn sign(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let x = engine.pop_int()?;
+		engine.push(StackItem::Integer(x.signum()).into())
+	}
+
+	pub fn abs(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let x = engine.pop_int()?;
+		Self::push_integer(engine, x.abs())
+	}
+
+	pub fn negate(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let x = engine.pop_int()?;
+		Self::push_integer(engine, -x)
+	}
+
+	pub fn inc(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let x = engine.pop_int()?;
+		Self::push_integer(engine, x + 1)
+	}
+
+	pub fn dec(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let x = engine.pop_int()?;
+		Self::push_integer(engine, x - 1)
+	}
+
+	pub fn add(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let x2 = engine.pop_int()?;
+		let x1 = engine.pop_int()?;
+		Self::push_integer(engine, x1 + x2)
+	}
+
+	pub fn sub(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let x2 = engine.pop_int()?;
+		let x1 = engine.pop_int()?;
+		Self::push_integer(engine, x1 - x2)
+	}
+
+	pub fn mul(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let x2 = engine.pop_int()?;
+		let x1 = engine.pop_int()?;
+		Self::push_integer(engine, x1 * x2)
+	}
+
+	pub fn div(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let x2 = engine.pop_int()?;
+		let x1 = engine.pop_int()?;
+		if x2.is_zero() {
+			return Err(VMError::DivisionByZero(format!("{x1} / 0")))
+		}
+		let (quotient, _) = Self::truncated_div_rem(&x1, &x2);
+		engine.push(StackItem::Integer(quotient).into())
+	}
+
+	pub fn mod_op(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let x2 = engine.pop_int()?;
+		let x1 = engine.pop_int()?;
+		if x2.is_zero() {
+			return Err(VMError::DivisionByZero(format!("{x1} % 0")))
+		}
+		let (_, remainder) = Self::truncated_div_rem(&x1, &x2);
+		engine.push(StackItem::Integer(remainder).into())
+	}
+
+	/// Raises a value to an exponent between 0 and `MaxShift`; the result is subject to the
+	/// integer size limit.
+	pub fn pow(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let exponent = to_i32(&engine.pop_int()?)?;
+		engine.limits.assert_shift(exponent)?;
+		let value = engine.pop_int()?;
+		Self::push_integer(engine, value.pow(exponent as u32))
+	}
+
+	pub fn sqrt(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let x = engine.pop_int()?;
+		if x.is_negative() {
+			return Err(VMError::InvalidParameter("value can not be negative".to_string()))
+		}
+		engine.push(StackItem::Integer(x.sqrt()).into())
+	}
+
+	/// Multiplies two integers modulo a non-zero modulus. Unlike `MOD`, the result always lies
+	/// in `[0, |modulus|)`.
+	pub fn mod_mul(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let modulus = engine.pop_int()?;
+		let x2 = engine.pop_int()?;
+		let x1 = engine.pop_int()?;
+		if modulus.is_zero() {
+			return Err(VMError::DivisionByZero(format!("{x1} * {x2} % 0")))
+		}
+		let modulus = modulus.abs();
+		let remainder = x1 * x2 % &modulus;
+		let result = if remainder.is_negative() { remainder + modulus } else { remainder };
+		engine.push(StackItem::Integer(result).into())
+	}
+
+	/// Raises a value to a non-negative exponent modulo a non-zero modulus. An exponent of -1
+	/// computes the modular inverse instead, which requires a positive value and a modulus of
+	/// at least 2.
+	pub fn mod_pow(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let modulus = engine.pop_int()?;
+		let exponent = engine.pop_int()?;
+		let value = engine.pop_int()?;
+		if modulus.is_zero() {
+			return Err(VMError::DivisionByZero(format!("{value} ^ {exponent} % 0")))
+		}
+		if exponent == -BigInt::one() {
+			let inverse = mod_inverse(&value, &modulus).map_err(VMError::InvalidParameter)?;
+			return engine.push(StackItem::Integer(inverse).into())
+		}
+		if exponent.is_negative() {
+			return Err(VMError::InvalidParameter(format!(
+				"The exponent {exponent} is not supported."
+			)))
+		}
+		// Like the remainder operator, the result takes the sign of `value ^ exponent`.
+		let magnitude =
+			BigInt::from(value.magnitude().modpow(exponent.magnitude(), modulus.magnitude()));
+		let odd = (&exponent % 2u32).is_one();
+		let result = if value.is_negative() && odd { -magnitude } else { magnitude };
+		engine.push(StackItem::Integer(result).into())
+	}
+
+	pub fn shl(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let shift = to_i32(&engine.pop_int()?)?;
+		engine.limits.assert_shift(shift)?;
+		if shift == 0 {
+			return Ok(())
+		}
+		let x = engine.pop_int()?;
+		Self::push_integer(engine, x << shift as usize)
+	}
+
+	pub fn shr(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let shift = to_i32(&engine.pop_int()?)?;
+		engine.limits.assert_shift(shift)?;
+		if shift == 0 {
+			return Ok(())
+		}
+		let x = engine.pop_int()?;
+		engine.push(StackItem::Integer(x >> shift as usize).into())
+	}
+
+	pub fn not(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let x = engine.pop_bool()?;
+		engine.push(StackItem::Boolean(!x).into())
+	}
+
+	pub fn bool_and(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let x2 = engine.pop_bool()?;
+		let x1 = engine.pop_bool()?;
+		engine.push(StackItem::Boolean(x1 && x2).into())
+	}
+
+	pub fn bool_or(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let x2 = engine.pop_bool()?;
+		let x1 = engine.pop_bool()?;
+		engine.push(StackItem::Boolean(x1 || x2).into())
+	}
+
+	pub fn nz(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let x = engine.pop_int()?;
+		engine.push(StackItem::Boolean(!x.is_zero()).into())
+	}
+
+	pub fn num_equal(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let x2 = engine.pop_int()?;
+		let x1 = engine.pop_int()?;
+		engine.push(StackItem::Boolean(x1 == x2).into())
+	}
+
+	pub fn num_not_equal(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let x2 = engine.pop_int()?;
+		let x1 = engine.pop_int()?;
+		engine.push(StackItem::Boolean(x1 != x2).into())
+	}
+
+	pub fn lt(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let result = Self::pop_comparison(engine)?.map(|(x1, x2)| x1 < x2);
+		engine.push(StackItem::Boolean(result.unwrap_or(false)).into())
+	}
+
+	pub fn le(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let result = Self::pop_comparison(engine)?.map(|(x1, x2)| x1 <= x2);
+		engine.push(StackItem::Boolean(result.unwrap_or(false)).into())
+	}
+
+	pub fn gt(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let result = Self::pop_comparison(engine)?.map(|(x1, x2)| x1 > x2);
+		engine.push(StackItem::Boolean(result.unwrap_or(false)).into())
+	}
+
+	pub fn ge(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let result = Self::pop_comparison(engine)?.map(|(x1, x2)| x1 >= x2);
+		engine.push(StackItem::Boolean(result.unwrap_or(false)).into())
+	}
+
+	pub fn min(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let x2 = engine.pop_int()?;
+		let x1 = engine.pop_int()?;
+		engine.push(StackItem::Integer(x1.min(x2)).into())
+	}
+
+	pub fn max(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let x2 = engine.pop_int()?;
+		let x1 = engine.pop_int()?;
+		engine.push(StackItem::Integer(x1.max(x2)).into())
+	}
+
+	/// Pushes whether `x` lies in the half-open interval `[a, b)`, popping `b`, `a` and `x`.
+	pub fn within(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let b = engine.pop_int()?;
+		let a = engine.pop_int()?;
+		let x = engine.pop_int()?;
+		engine.push(StackItem::Boolean(a <= x && x < b).into())
+	}
+
+	/// Pushes the result of an operation that can grow a value, failing when it exceeds the
+	/// integer size limit.
+	fn push_integer(engine: &mut ExecutionEngine, value: BigInt) -> Result<(), VMError> {
+		let item = StackItem::Integer(value);
+		engine.track_item_size(&item)?;
+		engine.push(item.into())
+	}
+
+	/// Divides `x1` by a non-zero `x2` as C# does: the quotient is truncated toward zero and
+	/// the remainder takes the sign of the dividend.
+	fn truncated_div_rem(x1: &BigInt, x2: &BigInt) -> (BigInt, BigInt) {
+		let magnitude = x1.magnitude() / x2.magnitude();
+		let sign = if x1.sign() == x2.sign() { Sign::Plus } else { Sign::Minus };
+		let quotient = BigInt::from_biguint(sign, magnitude);
+		let remainder = x1 - &quotient * x2;
+		(quotient, remainder)
+	}
+
+	/// Pops the operands of `OpCode::Lt` and friends, returning `None` when either of them
+	/// is `StackItem::Null`.
+	fn pop_comparison(engine: &mut ExecutionEngine) -> Result<Option<(BigInt, BigInt)>, VMError> {
+		let x2 = engine.pop()?;
+		let x1 = engine.pop()?;
+		if x1.borrow().is_null() || x2.borrow().is_null() {
+			return Ok(None)
+		}
+		let x2 = x2.borrow().get_integer()?;
+		let x1 = x1.borrow().get_integer()?;
+		Ok(Some((x1, x2)))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::{
+		jump_table::test_helpers::{result, run_script},
+		op_code::OpCode,
+		stack_item::StackItem,
+		vm_state::VMState,
+	};
+	use num_bigint::BigInt;
+	use num_traits::{One, Zero};
+
+	#[test]
+	fn test_not_produces_boolean() {
+		let engine = run_script(vec![
+			OpCode::Push0 as u8,
+			OpCode::Not as u8,
+			OpCode::Push5 as u8,
+			OpCode::Not as u8,
+			OpCode::PushData1 as u8,
+			0x00,
+			OpCode::Not as u8,
+		]);
+		assert_eq!(engine.state, VMState::Halt);
+		assert_eq!(result(&engine, 0), StackItem::Boolean(true));
+		assert_eq!(result(&engine, 1), StackItem::Boolean(false));
+		assert_eq!(result(&engine, 2), StackItem::Boolean(true));
+	}
+
+	#[test]
+	fn test_sign_and_abs() {
+		let mut script = Vec::new();
+		for value in [-5i8, 0, 7] {
+			script.extend([OpCode::PushInt8 as u8, value as u8, OpCode::Sign as u8]);
+		}
+		script.extend([OpCode::PushInt8 as u8, -5i8 as u8, OpCode::Abs as u8]);
+		let engine = run_script(script);
+		assert_eq!(engine.state, VMState::Halt);
+		assert_eq!(result(&engine, 0), StackItem::from(5));
+		assert_eq!(result(&engine, 1), StackItem::from(1));
+		assert_eq!(result(&engine, 2), StackItem::from(0));
+		assert_eq!(result(&engine, 3), StackItem::from(-1));
+
+		// The absolute value of the smallest 256-bit integer doesn't fit in 256 bits.
+		let mut script = vec![OpCode::PushInt256 as u8];
+		script.extend([0; 31]);
+		script.extend([0x80, OpCode::Abs as u8]);
+		assert_eq!(run_script(script).state, VMState::Fault);
+	}
+
+	#[test]
+	fn test_arithmetic() {
+		let engine = run_script(vec![
+			OpCode::Push7 as u8,
+			OpCode::Push3 as u8,
+			OpCode::Sub as u8,
+			OpCode::Push6 as u8,
+			OpCode::Push4 as u8,
+			OpCode::Mul as u8,
+			OpCode::Push2 as u8,
+			OpCode::Push10 as u8,
+			OpCode::Pow as u8,
+		]);
+		assert_eq!(engine.state, VMState::Halt);
+		assert_eq!(result(&engine, 0), StackItem::from(1024));
+		assert_eq!(result(&engine, 1), StackItem::from(24));
+		assert_eq!(result(&engine, 2), StackItem::from(4));
+	}
+
+	#[test]
+	fn test_div_mod_truncate_toward_zero() {
+		// (dividend, divisor, quotient, remainder)
+		let cases = [(7, 2, 3, 1), (-7, 2, -3, -1), (7, -2, -3, 1), (-7, -2, 3, -1)];
+		for (x1, x2, quotient, remainder) in cases {
+			let mut script = Vec::new();
+			for opcode in [OpCode::Div, OpCode::Mod] {
+				script.extend([OpCode::PushInt8 as u8, x1 as u8, OpCode::PushInt8 as u8, x2 as u8]);
+				script.push(opcode as u8);
+			}
+			let engine = run_script(script);
+			assert_eq!(engine.state, VMState::Halt);
+			assert_eq!(result(&engine, 0), StackItem::from(remainder), "{x1} % {x2}");
+			assert_eq!(result(&engine, 1), StackItem::from(quotient), "{x1} / {x2}");
+		}
+	}
+
+	#[test]
+	fn test_integer_limit_after_arithmetic() {
+		let push = |value: BigInt| {
+			let mut bytes = value.to_signed_bytes_le();
+			let sign = if value < BigInt::zero() { 0xFF } else { 0x00 };
+			bytes.resize(32, sign);
+			[vec![OpCode::PushInt256 as u8], bytes].concat()
+		};
+		let half: BigInt = BigInt::one() << 254;
+
+		let engine =
+			run_script([push(half.clone()), vec![OpCode::Dup as u8, OpCode::Add as u8]].concat());
+		assert_eq!(engine.state, VMState::Fault);
+
+		let engine = run_script(
+			[push(&half - 1), vec![OpCode::Dup as u8, OpCode::Add as u8, OpCode::Inc as u8]]
+				.concat(),
+		);
+		assert_eq!(engine.state, VMState::Halt);
+		assert_eq!(result(&engine, 0), StackItem::Integer((BigInt::one() << 255) - 1));
+
+		let engine = run_script(
+			[push(-half.clone()), vec![OpCode::Push2 as u8, OpCode::Mul as u8]].concat(),
+		);
+		assert_eq!(engine.state, VMState::Halt);
+		let engine = run_script(
+			[push(-half), vec![OpCode::Push2 as u8, OpCode::Mul as u8, OpCode::Dec as u8]].concat(),
+		);
+		assert_eq!(engine.state, VMState::Fault);
+
+		let engine = run_script(vec![
+			OpCode::Push1 as u8,
+			OpCode::PushInt16 as u8,
+			0xFF,
+			0x00,
+			OpCode::Shl as u8,
+		]);
+		assert_eq!(engine.state, VMState::Fault);
+		let engine = run_script(vec![
+			OpCode::Push2 as u8,
+			OpCode::PushInt16 as u8,
+			0xFF,
+			0x00,
+			OpCode::Pow as u8,
+		]);
+		assert_eq!(engine.state, VMState::Fault);
+	}
+
+	#[test]
+	fn test_pow_exponent_bounds() {
+		let pow = |value: u8, exponent: Vec<u8>| {
+			run_script([vec![value], exponent, vec![OpCode::Pow as u8]].concat())
+		};
+		let engine = pow(OpCode::Push2 as u8, vec![OpCode::Push10 as u8]);
+		assert_eq!(result(&engine, 0), StackItem::from(1024));
+		let engine = pow(OpCode::PushM1 as u8, vec![OpCode::Push0 as u8]);
+		assert_eq!(result(&engine, 0), StackItem::from(1));
+		assert_eq!(pow(OpCode::Push2 as u8, vec![OpCode::PushM1 as u8]).state, VMState::Fault);
+
+		// 1 ^ 256 is allowed, but 257 exceeds `MaxShift` and 2 ^ 256 the integer size limit.
+		let exponent = |exponent: i16| {
+			[vec![OpCode::PushInt16 as u8], exponent.to_le_bytes().to_vec()].concat()
+		};
+		assert_eq!(result(&pow(OpCode::Push1 as u8, exponent(256)), 0), StackItem::from(1));
+		assert_eq!(pow(OpCode::Push1 as u8, exponent(257)).state, VMState::Fault);
+		assert_eq!(pow(OpCode::Push2 as u8, exponent(256)).state, VMState::Fault);
+		let mut huge = vec![OpCode::PushInt64 as u8];
+		huge.extend(i64::MAX.to_le_bytes());
+		assert_eq!(pow(OpCode::Push1 as u8, huge).state, VMState::Fault);
+	}
+
+	#[test]
+	fn test_mod_mul() {
+		let mod_mul = |x1: i8, x2: i8, modulus: i8| {
+			let mut script = Vec::new();
+			for value in [x1, x2, modulus] {
+				script.extend([OpCode::PushInt8 as u8, value as u8]);
+			}
+			script.push(OpCode::ModMul as u8);
+			run_script(script)
+		};
+		assert_eq!(result(&mod_mul(7, 5, 6), 0), StackItem::from(5));
+		// -35 lies between -36 and -30, so the result is 1 whatever the sign of the modulus.
+		assert_eq!(result(&mod_mul(-7, 5, 6), 0), StackItem::from(1));
+		assert_eq!(result(&mod_mul(-7, 5, -6), 0), StackItem::from(1));
+		assert_eq!(result(&mod_mul(-7, -5, 6), 0), StackItem::from(5));
+		assert_eq!(result(&mod_mul(-6, 5, 6), 0), StackItem::from(0));
+		assert_eq!(mod_mul(7, 5, 0).state, VMState::Fault);
+	}
+
+	#[test]
+	fn test_mod_pow() {
+		let mod_pow = |value: i8, exponent: i8, modulus: i8| {
+			let mut script = Vec::new();
+			for value in [value, exponent, modulus] {
+				script.extend([OpCode::PushInt8 as u8, value as u8]);
+			}
+			script.push(OpCode::ModPow as u8);
+			run_script(script)
+		};
+		assert_eq!(result(&mod_pow(3, 4, 7), 0), StackItem::from(4));
+		assert_eq!(result(&mod_pow(-3, 3, 7), 0), StackItem::from(-6));
+		// An exponent of -1 computes the modular inverse: 19 * 52 = 7 * 141 + 1.
+		let mut script = vec![OpCode::PushInt8 as u8, 19, OpCode::PushM1 as u8];
+		script.extend([OpCode::PushInt16 as u8, 141, 0, OpCode::ModPow as u8]);
+		assert_eq!(result(&run_script(script), 0), StackItem::from(52));
+		assert_eq!(result(&mod_pow(3, -1, 7), 0), StackItem::from(5));
+		assert_eq!(mod_pow(6, -1, 9).state, VMState::Fault);
+		assert_eq!(mod_pow(-3, -1, 7).state, VMState::Fault);
+		assert_eq!(mod_pow(3, -2, 7).state, VMState::Fault);
+		assert_eq!(mod_pow(3, 4, 0).state, VMState::Fault);
+	}
+
+	#[test]
+	fn test_div_by_zero_faults() {
+		let engine = run_script(vec![OpCode::Push1 as u8, OpCode::Push0 as u8, OpCode::Div as u8]);
+		assert_eq!(engine.state, VMState::Fault);
+		let engine = run_script(vec![OpCode::Push1 as u8, OpCode::Push0 as u8, OpCode::Mod as u8]);
+		assert_eq!(engine.state, VMState::Fault);
+	}
+
+	#[test]
+	fn test_shift_bounds() {
+		for opcode in [OpCode::Shl, OpCode::Shr] {
+			let engine = run_script(vec![OpCode::Push3 as u8, OpCode::Push0 as u8, opcode as u8]);
+			assert_eq!(engine.state, VMState::Halt);
+			assert_eq!(engine.result_stack.borrow().count(), 1);
+			assert_eq!(result(&engine, 0), StackItem::from(3));
+
+			let engine = run_script(vec![OpCode::Push3 as u8, OpCode::PushM1 as u8, opcode as u8]);
+			assert_eq!(engine.state, VMState::Fault);
+
+			// 257 is just above the default MaxShift.
+			let script = [OpCode::Push3 as u8, OpCode::PushInt16 as u8, 0x01, 0x01, opcode as u8];
+			assert_eq!(run_script(script.to_vec()).state, VMState::Fault);
+
+			let mut script = vec![OpCode::Push3 as u8, OpCode::PushInt64 as u8];
+			script.extend(i64::MAX.to_le_bytes());
+			script.push(opcode as u8);
+			assert_eq!(run_script(script).state, VMState::Fault);
+		}
+		let engine = run_script(vec![OpCode::Push3 as u8, OpCode::Push2 as u8, OpCode::Shl as u8]);
+		assert_eq!(result(&engine, 0), StackItem::from(12));
+	}
+
+	#[test]
+	fn test_comparison_with_null_is_false() {
+		for opcode in [OpCode::Lt, OpCode::Le, OpCode::Gt, OpCode::Ge] {
+			for (x1, x2) in [(OpCode::PushNull, OpCode::Push1), (OpCode::Push1, OpCode::PushNull)] {
+				let engine = run_script(vec![x1 as u8, x2 as u8, opcode as u8]);
+				assert_eq!(engine.state, VMState::Halt);
+				assert_eq!(result(&engine, 0), StackItem::Boolean(false), "{opcode:?}");
+			}
+		}
+	}
+
+	#[test]
+	fn test_comparisons() {
+		let compare = |x1: OpCode, x2: OpCode, opcode: OpCode| {
+			let engine = run_script(vec![x1 as u8, x2 as u8, opcode as u8]);
+			assert_eq!(engine.state, VMState::Halt);
+			result(&engine, 0)
+		};
+		// (LT, LE, GT, GE) of 1 and 2, 2 and 2, and 2 and 1
+		let expected = [
+			(OpCode::Push1, OpCode::Push2, [true, true, false, false]),
+			(OpCode::Push2, OpCode::Push2, [false, true, false, true]),
+			(OpCode::Push2, OpCode::Push1, [false, false, true, true]),
+		];
+		for (x1, x2, results) in expected {
+			for (opcode, expected) in
+				[OpCode::Lt, OpCode::Le, OpCode::Gt, OpCode::Ge].into_iter().zip(results)
+			{
+				assert_eq!(
+					compare(x1, x2, opcode),
+					StackItem::Boolean(expected),
+					"{x1:?} {opcode:?} {x2:?}"
+				);
+			}
+		}
+	}
+
+	#[test]
+	fn test_within_bounds() {
+		let within = |x: OpCode| {
+			let engine = run_script(vec![
+				x as u8,
+				OpCode::Push2 as u8,
+				OpCode::Push4 as u8,
+				OpCode::Within as u8,
+			]);
+			assert_eq!(engine.state, VMState::Halt);
+			result(&engine, 0)
+		};
+		assert_eq!(within(OpCode::Push1), StackItem::Boolean(false));
+		assert_eq!(within(OpCode::Push2), StackItem::Boolean(true));
+		assert_eq!(within(OpCode::Push3), StackItem::Boolean(true));
+		assert_eq!(within(OpCode::Push4), StackItem::Boolean(false));
+	}
+
+	#[test]
+	fn test_min_max_within_non_integer_faults() {
+		for opcode in [OpCode::Min, OpCode::Max] {
+			let engine =
+				run_script(vec![OpCode::PushNull as u8, OpCode::Push1 as u8, opcode as u8]);
+			assert_eq!(engine.state, VMState::Fault, "{opcode:?}");
+			let engine = run_script(vec![OpCode::Push1 as u8, OpCode::NewMap as u8, opcode as u8]);
+			assert_eq!(engine.state, VMState::Fault, "{opcode:?}");
+		}
+		let engine = run_script(vec![
+			OpCode::Push1 as u8,
+			OpCode::PushNull as u8,
+			OpCode::Push4 as u8,
+			OpCode::Within as u8,
+		]);
+		assert_eq!(engine.state, VMState::Fault);
+	}
+
+	#[test]
+	fn test_bool_and_bool_or() {
+		let logic = |x1: &[u8], x2: &[u8], opcode: OpCode| {
+			let engine = run_script([x1, x2, &[opcode as u8]].concat());
+			assert_eq!(engine.state, VMState::Halt);
+			result(&engine, 0)
+		};
+		let zero = [OpCode::Push0 as u8];
+		let five = [OpCode::Push5 as u8];
+		assert_eq!(logic(&zero, &five, OpCode::BoolAnd), StackItem::Boolean(false));
+		assert_eq!(logic(&five, &five, OpCode::BoolAnd), StackItem::Boolean(true));
+		assert_eq!(logic(&zero, &five, OpCode::BoolOr), StackItem::Boolean(true));
+		assert_eq!(logic(&zero, &zero, OpCode::BoolOr), StackItem::Boolean(false));
+
+		// A ByteString is true when any of its bytes is non-zero.
+		let non_zero = [OpCode::PushData1 as u8, 0x02, 0x00, 0x01];
+		let zeros = [OpCode::PushData1 as u8, 0x02, 0x00, 0x00];
+		let empty = [OpCode::PushData1 as u8, 0x00];
+		assert_eq!(logic(&non_zero, &five, OpCode::BoolAnd), StackItem::Boolean(true));
+		assert_eq!(logic(&zeros, &five, OpCode::BoolAnd), StackItem::Boolean(false));
+		assert_eq!(logic(&empty, &zero, OpCode::BoolOr), StackItem::Boolean(false));
+
+		// Null is false and compound items are true, though neither converts to an integer.
+		let null = [OpCode::PushNull as u8];
+		let array = [OpCode::NewArray0 as u8];
+		assert_eq!(logic(&null, &five, OpCode::BoolOr), StackItem::Boolean(true));
+		assert_eq!(logic(&array, &null, OpCode::BoolAnd), StackItem::Boolean(false));
+		assert_eq!(logic(&array, &five, OpCode::BoolAnd), StackItem::Boolean(true));
+	}
+}
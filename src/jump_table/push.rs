@@ -0,0 +1,141 @@
+use crate::{
+	execution_engine::ExecutionEngine, instruction::Instruction, jump_table::JumpTable,
+	op_code::OpCode, stack_item::StackItem, vm_error::VMError,
+};
+use num_bigint::BigInt;
+
+impl JumpTable {
+	/// Pushes the integer encoded in the operand of `OpCode::PushInt8` to
+	/// `OpCode::PushInt256`.
+	pub fn push_int(
+		&self,
+		engine: &mut ExecutionEngine,
+		instruction: &Instruction,
+	) -> Result<(), VMError> {
+		engine.push(StackItem::Integer(BigInt::from_signed_bytes_le(&instruction.operand)).into())
+	}
+
+	pub fn push_true(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		engine.push(StackItem::Boolean(true).into())
+	}
+
+	pub fn push_false(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		engine.push(StackItem::Boolean(false).into())
+	}
+
+	/// Pushes a `Pointer` to the current instruction offset by the operand.
+	pub fn push_a(
+		&self,
+		engine: &mut ExecutionEngine,
+		instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let position = Self::offset_position(engine, instruction.token_i32())?;
+		let script = engine.context()?.borrow().script();
+		if position > script.borrow().len() {
+			return Err(VMError::InvalidJump(format!("Bad pointer address: {position}")))
+		}
+		engine.push(StackItem::Pointer { script, position }.into())
+	}
+
+	pub fn push_null(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		engine.push(StackItem::Null.into())
+	}
+
+	/// Pushes the operand of `OpCode::PushData1`, `OpCode::PushData2` or `OpCode::PushData4`
+	/// as a `ByteString`.
+	pub fn push_data(
+		&self,
+		engine: &mut ExecutionEngine,
+		instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let item = StackItem::ByteString(instruction.operand.clone());
+		engine.track_item_size(&item)?;
+		engine.push(item.into())
+	}
+
+	/// Pushes the constant encoded in the opcode of `OpCode::PushM1` to `OpCode::Push16`.
+	pub fn push_const(
+		&self,
+		engine: &mut ExecutionEngine,
+		instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let value = instruction.opcode as i32 - OpCode::Push0 as i32;
+		engine.push(StackItem::from(value).into())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::{
+		jump_table::test_helpers::{result, run_script},
+		op_code::OpCode,
+		stack_item::StackItem,
+		stack_item_type::StackItemType,
+		vm_state::VMState,
+	};
+
+	#[test]
+	fn test_push_a() {
+		let engine = run_script(vec![OpCode::PushA as u8, 0x05, 0x00, 0x00, 0x00]);
+		assert_eq!(engine.state, VMState::Halt);
+		let StackItem::Pointer { script, position } = result(&engine, 0) else {
+			panic!("expected a pointer")
+		};
+		assert_eq!(position, 5);
+		assert_eq!(script.borrow().len(), 5);
+
+		// Past the end of the script, and before its start.
+		let engine = run_script(vec![OpCode::PushA as u8, 0x06, 0x00, 0x00, 0x00]);
+		assert_eq!(engine.state, VMState::Fault);
+		let engine = run_script(vec![OpCode::PushA as u8, 0xff, 0xff, 0xff, 0xff]);
+		assert_eq!(engine.state, VMState::Fault);
+	}
+
+	#[test]
+	fn test_push_int() {
+		let engine = run_script(vec![OpCode::PushInt16 as u8, 0x00, 0x80]);
+		assert_eq!(engine.state, VMState::Halt);
+		assert_eq!(result(&engine, 0), StackItem::from(-32768));
+	}
+
+	#[test]
+	fn test_push_const() {
+		let engine = run_script(vec![OpCode::PushM1 as u8, OpCode::Push16 as u8]);
+		assert_eq!(result(&engine, 0), StackItem::from(16));
+		assert_eq!(result(&engine, 1), StackItem::from(-1));
+	}
+
+	#[test]
+	fn test_push_const_matches_push_int() {
+		let engine =
+			run_script(vec![OpCode::Push5 as u8, OpCode::PushInt8 as u8, 5, OpCode::Equal as u8]);
+		assert_eq!(engine.state, VMState::Halt);
+		assert_eq!(result(&engine, 0), StackItem::Boolean(true));
+
+		for value in -1i8..=16 {
+			let opcode = (OpCode::Push0 as i8 + value) as u8;
+			let engine = run_script(vec![opcode, OpCode::PushInt8 as u8, value as u8]);
+			assert_eq!(result(&engine, 0), StackItem::from(value as i32));
+			assert_eq!(result(&engine, 0), result(&engine, 1), "{value}");
+			assert_eq!(result(&engine, 1).get_type(), StackItemType::Integer);
+		}
+	}
+
+	#[test]
+	fn test_push_data() {
+		let engine = run_script(vec![OpCode::PushData1 as u8, 0x02, 0xAB, 0xCD]);
+		assert_eq!(result(&engine, 0), StackItem::ByteString(vec![0xAB, 0xCD]));
+	}
+}
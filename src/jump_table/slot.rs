@@ -0,0 +1,269 @@
+use crate::{
+	execution_engine::ExecutionEngine, instruction::Instruction, jump_table::JumpTable,
+	op_code::OpCode, slot::Slot, vm_error::VMError,
+};
+use std::{cell::RefCell, rc::Rc};
+
+impl JumpTable {
+	pub fn init_sslot(
+		&self,
+		engine: &mut ExecutionEngine,
+		instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let context = engine.context()?;
+		let context = context.borrow();
+		if context.static_fields().is_some() {
+			return Err(VMError::InvalidParameter("INITSSLOT cannot be executed twice.".to_string()))
+		}
+		if instruction.token_u8() == 0 {
+			return Err(VMError::InvalidParameter(format!(
+				"The operand {} is invalid for OpCode::{:?}.",
+				instruction.token_u8(),
+				instruction.opcode
+			)))
+		}
+		let slot =
+			Slot::new_with_count(instruction.token_u8() as usize, engine.reference_counter.clone());
+		context.set_static_fields(Some(Rc::new(RefCell::new(slot))));
+		Ok(())
+	}
+
+	pub fn init_slot(
+		&self,
+		engine: &mut ExecutionEngine,
+		instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let context = engine.context()?;
+		if context.borrow().local_variables.is_some() || context.borrow().arguments.is_some() {
+			return Err(VMError::InvalidParameter("INITSLOT cannot be executed twice.".to_string()))
+		}
+		if instruction.token_u16() == 0 {
+			return Err(VMError::InvalidParameter(format!(
+				"The operand {} is invalid for OpCode::{:?}.",
+				instruction.token_u16(),
+				instruction.opcode
+			)))
+		}
+		if instruction.token_u8() > 0 {
+			let slot = Slot::new_with_count(
+				instruction.token_u8() as usize,
+				engine.reference_counter.clone(),
+			);
+			context.borrow_mut().local_variables = Some(Rc::new(RefCell::new(slot)));
+		}
+		if instruction.token_u8_1() > 0 {
+			let mut items = Vec::with_capacity(instruction.token_u8_1() as usize);
+			for _ in 0..instruction.token_u8_1() {
+				items.push(engine.pop()?);
+			}
+			let slot = Slot::new(items, engine.reference_counter.clone());
+			context.borrow_mut().arguments = Some(Rc::new(RefCell::new(slot)));
+		}
+		Ok(())
+	}
+
+	pub fn ld_sfld(
+		&self,
+		engine: &mut ExecutionEngine,
+		instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let slot = engine.context()?.borrow().static_fields();
+		let index = Self::slot_index(instruction, OpCode::LdSFLd0, OpCode::LdSFLd);
+		Self::execute_load_from_slot(engine, slot, index)
+	}
+
+	pub fn st_sfld(
+		&self,
+		engine: &mut ExecutionEngine,
+		instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let slot = engine.context()?.borrow().static_fields();
+		let index = Self::slot_index(instruction, OpCode::StSFLd0, OpCode::StSFLd);
+		Self::execute_store_to_slot(engine, slot, index)
+	}
+
+	pub fn ld_loc(
+		&self,
+		engine: &mut ExecutionEngine,
+		instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let slot = engine.context()?.borrow().local_variables.clone();
+		let index = Self::slot_index(instruction, OpCode::LdLoc0, OpCode::LdLoc);
+		Self::execute_load_from_slot(engine, slot, index)
+	}
+
+	pub fn st_loc(
+		&self,
+		engine: &mut ExecutionEngine,
+		instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let slot = engine.context()?.borrow().local_variables.clone();
+		let index = Self::slot_index(instruction, OpCode::StLoc0, OpCode::StLoc);
+		Self::execute_store_to_slot(engine, slot, index)
+	}
+
+	pub fn ld_arg(
+		&self,
+		engine: &mut ExecutionEngine,
+		instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let slot = engine.context()?.borrow().arguments.clone();
+		let index = Self::slot_index(instruction, OpCode::LdArg0, OpCode::LdArg);
+		Self::execute_load_from_slot(engine, slot, index)
+	}
+
+	pub fn st_arg(
+		&self,
+		engine: &mut ExecutionEngine,
+		instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let slot = engine.context()?.borrow().arguments.clone();
+		let index = Self::slot_index(instruction, OpCode::StArg0, OpCode::StArg);
+		Self::execute_store_to_slot(engine, slot, index)
+	}
+
+	/// Resolves the slot index of an instruction, which is either encoded in the opcode
+	/// (`LDLOC0` to `LDLOC6` and friends) or given by the operand of the generic form.
+	fn slot_index(instruction: &Instruction, first: OpCode, generic: OpCode) -> usize {
+		if instruction.opcode == generic {
+			instruction.token_u8() as usize
+		} else {
+			instruction.opcode as usize - first as usize
+		}
+	}
+
+	fn execute_load_from_slot(
+		engine: &mut ExecutionEngine,
+		slot: Option<Rc<RefCell<Slot>>>,
+		index: usize,
+	) -> Result<(), VMError> {
+		let slot = slot.ok_or_else(|| {
+			VMError::InvalidParameter("Slot has not been initialized.".to_string())
+		})?;
+		let item = slot.borrow().get(index).ok_or_else(|| {
+			VMError::InvalidParameter(format!("Index out of range when loading from slot: {index}"))
+		})?;
+		engine.push(item)
+	}
+
+	fn execute_store_to_slot(
+		engine: &mut ExecutionEngine,
+		slot: Option<Rc<RefCell<Slot>>>,
+		index: usize,
+	) -> Result<(), VMError> {
+		let slot = slot.ok_or_else(|| {
+			VMError::InvalidParameter("Slot has not been initialized.".to_string())
+		})?;
+		if index >= slot.borrow().count() {
+			return Err(VMError::InvalidParameter(format!(
+				"Index out of range when storing to slot: {index}"
+			)))
+		}
+		let item = engine.pop()?;
+		slot.borrow_mut().set(index, item)?;
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::{
+		execution_engine::ExecutionEngine,
+		jump_table::test_helpers::{result, run_script},
+		op_code::OpCode,
+		stack_item::StackItem,
+		vm::script::Script,
+		vm_state::VMState,
+	};
+
+	#[test]
+	fn test_local_variables() {
+		let engine = run_script(vec![
+			OpCode::InitSlot as u8,
+			0x01,
+			0x00,
+			OpCode::Push5 as u8,
+			OpCode::StLoc0 as u8,
+			OpCode::LdLoc as u8,
+			0x00,
+		]);
+		assert_eq!(engine.state, VMState::Halt);
+		assert_eq!(result(&engine, 0), StackItem::from(5));
+	}
+
+	#[test]
+	fn test_arguments_and_static_fields() {
+		let engine = run_script(vec![
+			OpCode::Push1 as u8,
+			OpCode::Push2 as u8,
+			OpCode::InitSSLot as u8,
+			0x01,
+			OpCode::InitSlot as u8,
+			0x00,
+			0x02,
+			OpCode::LdArg1 as u8,
+			OpCode::StSFLd0 as u8,
+			OpCode::LdSFLd0 as u8,
+			OpCode::LdArg0 as u8,
+		]);
+		assert_eq!(engine.state, VMState::Halt);
+		assert_eq!(result(&engine, 0), StackItem::from(2));
+		assert_eq!(result(&engine, 1), StackItem::from(1));
+	}
+
+	#[test]
+	fn test_overwrite_releases_old_item() {
+		let script = vec![
+			OpCode::InitSlot as u8,
+			0x01,
+			0x00,
+			OpCode::Push1 as u8,
+			OpCode::Push1 as u8,
+			OpCode::Pack as u8,
+			OpCode::StLoc0 as u8,
+			OpCode::NewArray0 as u8,
+			OpCode::StLoc0 as u8,
+		];
+		let mut engine = ExecutionEngine::new();
+		engine.load_script(Script::new(script), -1, 0).unwrap();
+		for expected in [1, 2, 3, 3, 2, 3, 2] {
+			engine.step().unwrap();
+			assert_eq!(engine.reference_counter.borrow().count(), expected);
+		}
+		// Only the element of the overwritten array is left, and nothing reaches the array.
+		assert_eq!(engine.reference_counter.borrow_mut().check_zero_referred(), 1);
+	}
+
+	#[test]
+	fn test_arguments_keep_their_references() {
+		let script =
+			vec![OpCode::Push1 as u8, OpCode::NewArray0 as u8, OpCode::InitSlot as u8, 0x00, 0x02];
+		let mut engine = ExecutionEngine::new();
+		engine.load_script(Script::new(script), -1, 0).unwrap();
+		for _ in 0..3 {
+			engine.step().unwrap();
+		}
+		assert_eq!(engine.evaluation_stack().unwrap().borrow().count(), 0);
+		assert_eq!(engine.reference_counter.borrow_mut().check_zero_referred(), 2);
+	}
+
+	#[test]
+	fn test_slot_index_out_of_range_faults() {
+		for (opcode, index) in [(OpCode::LdLoc, 1), (OpCode::StLoc, 1), (OpCode::LdArg, 0)] {
+			let engine = run_script(vec![
+				OpCode::InitSlot as u8,
+				0x01,
+				0x00,
+				OpCode::Push1 as u8,
+				opcode as u8,
+				index,
+			]);
+			assert_eq!(engine.state, VMState::Fault, "{opcode:?}");
+		}
+	}
+
+	#[test]
+	fn test_uninitialized_slot_faults() {
+		assert_eq!(run_script(vec![OpCode::LdLoc0 as u8]).state, VMState::Fault);
+	}
+}
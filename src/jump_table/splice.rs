@@ -0,0 +1,352 @@
+use crate::{
+	execution_engine::ExecutionEngine,
+	instruction::Instruction,
+	jump_table::{to_usize, JumpTable},
+	stack_item::StackItem,
+	vm_error::VMError,
+};
+
+impl JumpTable {
+	pub fn new_buffer(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let length = to_usize(&engine.pop()?.borrow().get_integer()?)?;
+		// Checked before allocating, as the length is arbitrary.
+		engine.limits.assert_max_item_size(length)?;
+		let buffer = engine.rent_buffer(length);
+		engine.push(StackItem::Buffer(buffer).into())
+	}
+
+	pub fn memcpy(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let count = to_usize(&engine.pop()?.borrow().get_integer()?)?;
+		let si = to_usize(&engine.pop()?.borrow().get_integer()?)?;
+		let src = engine.pop()?.borrow().get_span()?;
+		let src_end = Self::check_range(si, count, src.len())?;
+		let di = to_usize(&engine.pop()?.borrow().get_integer()?)?;
+		let dst = engine.pop()?;
+		let mut dst = dst.borrow_mut();
+		let StackItem::Buffer(buffer) = &mut *dst else {
+			return Err(VMError::InvalidType(format!(
+				"Expected a Buffer, found {:?}",
+				dst.get_type()
+			)))
+		};
+		let dst_end = Self::check_range(di, count, buffer.len())?;
+		buffer[di..dst_end].copy_from_slice(&src[si..src_end]);
+		Ok(())
+	}
+
+	pub fn cat(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let item2 = engine.pop()?;
+		let item1 = engine.pop()?;
+		let x2 = item2.borrow().get_span()?;
+		let x1 = item1.borrow().get_span()?;
+		engine.release_item(item1);
+		engine.release_item(item2);
+		let mut buffer = engine.rent_buffer(x1.len() + x2.len());
+		buffer[..x1.len()].copy_from_slice(&x1);
+		buffer[x1.len()..].copy_from_slice(&x2);
+		let result = StackItem::Buffer(buffer);
+		engine.track_item_size(&result)?;
+		engine.push(result.into())
+	}
+
+	pub fn substr(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let count = to_usize(&engine.pop()?.borrow().get_integer()?)?;
+		let index = to_usize(&engine.pop()?.borrow().get_integer()?)?;
+		let item = engine.pop()?;
+		let x = item.borrow().get_span()?;
+		let end = Self::check_range(index, count, x.len())?;
+		engine.release_item(item);
+		Self::push_slice(engine, &x[index..end])
+	}
+
+	pub fn left(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let count = to_usize(&engine.pop()?.borrow().get_integer()?)?;
+		let item = engine.pop()?;
+		let x = item.borrow().get_span()?;
+		if count > x.len() {
+			return Err(VMError::InvalidParameter(format!("The value {count} is out of range.")))
+		}
+		engine.release_item(item);
+		Self::push_slice(engine, &x[..count])
+	}
+
+	pub fn right(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let count = to_usize(&engine.pop()?.borrow().get_integer()?)?;
+		let item = engine.pop()?;
+		let x = item.borrow().get_span()?;
+		if count > x.len() {
+			return Err(VMError::InvalidParameter(format!("The value {count} is out of range.")))
+		}
+		engine.release_item(item);
+		Self::push_slice(engine, &x[x.len() - count..])
+	}
+
+	/// Checks that `count` bytes starting at `index` fit in `length` bytes, returning the end of
+	/// the range.
+	fn check_range(index: usize, count: usize, length: usize) -> Result<usize, VMError> {
+		index
+			.checked_add(count)
+			.filter(|&end| end <= length)
+			.ok_or_else(|| VMError::InvalidParameter(format!("The value {count} is out of range.")))
+	}
+
+	/// Pushes a new `Buffer` holding a copy of `data`.
+	fn push_slice(engine: &mut ExecutionEngine, data: &[u8]) -> Result<(), VMError> {
+		let mut buffer = engine.rent_buffer(data.len());
+		buffer.copy_from_slice(data);
+		let result = StackItem::Buffer(buffer);
+		engine.track_item_size(&result)?;
+		engine.push(result.into())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::{
+		execution_engine::ExecutionEngine,
+		jump_table::test_helpers::{result, run_script},
+		op_code::OpCode,
+		stack_item::StackItem,
+		stack_item_type::StackItemType,
+		vm::script::Script,
+		vm_state::VMState,
+	};
+
+	#[test]
+	fn test_cat() {
+		let engine = run_script(vec![
+			OpCode::PushData1 as u8,
+			0x01,
+			0x01,
+			OpCode::PushData1 as u8,
+			0x02,
+			0x02,
+			0x03,
+			OpCode::Cat as u8,
+		]);
+		assert_eq!(engine.state, VMState::Halt);
+		assert_eq!(result(&engine, 0), StackItem::Buffer(vec![0x01, 0x02, 0x03]));
+	}
+
+	#[test]
+	fn test_cat_buffer_and_byte_string() {
+		// A Buffer followed by a ByteString, and the other way around.
+		let buffer = [
+			OpCode::PushData1 as u8,
+			0x01,
+			0x01,
+			OpCode::Convert as u8,
+			StackItemType::Buffer as u8,
+		];
+		let byte_string = [OpCode::PushData1 as u8, 0x02, 0x02, 0x03];
+		let engine = run_script([&buffer[..], &byte_string, &[OpCode::Cat as u8]].concat());
+		assert_eq!(engine.state, VMState::Halt);
+		assert_eq!(result(&engine, 0), StackItem::Buffer(vec![0x01, 0x02, 0x03]));
+
+		let engine = run_script([&byte_string[..], &buffer, &[OpCode::Cat as u8]].concat());
+		assert_eq!(engine.state, VMState::Halt);
+		assert_eq!(result(&engine, 0), StackItem::Buffer(vec![0x02, 0x03, 0x01]));
+	}
+
+	#[test]
+	fn test_new_buffer_is_zeroed() {
+		let mut script = vec![OpCode::Push4 as u8, OpCode::NewBuffer as u8];
+		for index in [OpCode::Push0, OpCode::Push1, OpCode::Push2, OpCode::Push3] {
+			script.extend([
+				OpCode::Dup as u8,
+				index as u8,
+				OpCode::PickItem as u8,
+				OpCode::Swap as u8,
+			]);
+		}
+		script.push(OpCode::Size as u8);
+		let engine = run_script(script);
+		assert_eq!(engine.state, VMState::Halt);
+		assert_eq!(result(&engine, 0), StackItem::from(4));
+		for index in 1..=4 {
+			assert_eq!(result(&engine, index), StackItem::from(0));
+		}
+	}
+
+	/// Runs `script` with `MaxItemSize` set to 4 bytes.
+	fn run_with_max_item_size_4(script: Vec<u8>) -> VMState {
+		let mut engine = ExecutionEngine::new();
+		engine.limits.max_item_size = 4;
+		engine.load_script(Script::new(script), -1, 0).unwrap();
+		engine.execute()
+	}
+
+	#[test]
+	fn test_max_item_size_bounds() {
+		let new_buffer = |length: OpCode| vec![length as u8, OpCode::NewBuffer as u8];
+		assert_eq!(run_with_max_item_size_4(new_buffer(OpCode::Push4)), VMState::Halt);
+		assert_eq!(run_with_max_item_size_4(new_buffer(OpCode::Push5)), VMState::Fault);
+
+		let cat = |length: u8| {
+			let mut script = vec![OpCode::PushData1 as u8, 2, 0, 0, OpCode::PushData1 as u8];
+			script.push(length);
+			script.extend(vec![0; length as usize]);
+			script.push(OpCode::Cat as u8);
+			script
+		};
+		assert_eq!(run_with_max_item_size_4(cat(2)), VMState::Halt);
+		assert_eq!(run_with_max_item_size_4(cat(3)), VMState::Fault);
+
+		let push_data = |length: u8| {
+			let mut script = vec![OpCode::PushData1 as u8, length];
+			script.extend(vec![0; length as usize]);
+			script
+		};
+		assert_eq!(run_with_max_item_size_4(push_data(4)), VMState::Halt);
+		assert_eq!(run_with_max_item_size_4(push_data(5)), VMState::Fault);
+	}
+
+	#[test]
+	fn test_cat_max_item_size() {
+		// Two 600 KB byte strings
+		let mut script = vec![OpCode::PushInt32 as u8];
+		script.extend((600 * 1024i32).to_le_bytes());
+		script.extend([
+			OpCode::NewBuffer as u8,
+			OpCode::Convert as u8,
+			StackItemType::ByteString as u8,
+			OpCode::Dup as u8,
+			OpCode::Cat as u8,
+			OpCode::Size as u8,
+		]);
+		let run = |max_item_size: usize| {
+			let mut engine = ExecutionEngine::new();
+			engine.limits.max_item_size = max_item_size;
+			engine.load_script(Script::new(script.clone()), -1, 0).unwrap();
+			engine.execute();
+			engine
+		};
+		assert_eq!(run(1024 * 1024).state, VMState::Fault);
+		let engine = run(2 * 1024 * 1024);
+		assert_eq!(engine.state, VMState::Halt);
+		assert_eq!(result(&engine, 0), StackItem::from(1200 * 1024));
+	}
+
+	/// Builds `dst DUP di src si count MEMCPY`, leaving the destination on the stack.
+	fn memcpy_script(dst: &[u8], di: &[u8], src: &[u8], si: &[u8], count: &[u8]) -> Vec<u8> {
+		let mut script = dst.to_vec();
+		script.push(OpCode::Dup as u8);
+		for operand in [di, src, si, count] {
+			script.extend(operand);
+		}
+		script.push(OpCode::MemCpy as u8);
+		script
+	}
+
+	const DST: [u8; 2] = [OpCode::Push4 as u8, OpCode::NewBuffer as u8];
+	const SRC: [u8; 5] = [OpCode::PushData1 as u8, 0x03, 0x0A, 0x0B, 0x0C];
+
+	#[test]
+	fn test_memcpy() {
+		let push = |n: u8| [OpCode::Push0 as u8 + n];
+		let engine = run_script(memcpy_script(&DST, &push(1), &SRC, &push(1), &push(2)));
+		assert_eq!(engine.state, VMState::Halt);
+		assert_eq!(result(&engine, 0), StackItem::Buffer(vec![0x00, 0x0B, 0x0C, 0x00]));
+	}
+
+	#[test]
+	fn test_memcpy_overlapping() {
+		let dst = [
+			OpCode::PushData1 as u8,
+			0x04,
+			0x01,
+			0x02,
+			0x03,
+			0x04,
+			OpCode::Convert as u8,
+			StackItemType::Buffer as u8,
+		];
+		// OVER picks the destination buffer again as the source.
+		let engine = run_script(memcpy_script(
+			&dst,
+			&[OpCode::Push1 as u8],
+			&[OpCode::Over as u8],
+			&[OpCode::Push0 as u8],
+			&[OpCode::Push3 as u8],
+		));
+		assert_eq!(engine.state, VMState::Halt);
+		assert_eq!(result(&engine, 0), StackItem::Buffer(vec![0x01, 0x01, 0x02, 0x03]));
+	}
+
+	#[test]
+	fn test_memcpy_out_of_range_faults() {
+		let push = |n: u8| vec![OpCode::Push0 as u8 + n];
+		let mut huge = vec![OpCode::PushInt128 as u8];
+		huge.extend(u64::MAX.to_le_bytes());
+		huge.extend([0; 8]);
+		let cases = [
+			// The source range ends past the source.
+			(DST.to_vec(), push(0), SRC.to_vec(), push(2), push(2)),
+			// The destination range ends past the destination.
+			(DST.to_vec(), push(3), SRC.to_vec(), push(0), push(2)),
+			// Negative indexes.
+			(DST.to_vec(), vec![OpCode::PushM1 as u8], SRC.to_vec(), push(0), push(1)),
+			(DST.to_vec(), push(0), SRC.to_vec(), vec![OpCode::PushM1 as u8], push(1)),
+			// The end of the range overflows.
+			(DST.to_vec(), push(0), SRC.to_vec(), push(1), huge),
+			// The destination is not a Buffer.
+			(SRC.to_vec(), push(0), SRC.to_vec(), push(0), push(1)),
+		];
+		for (dst, di, src, si, count) in cases {
+			let engine = run_script(memcpy_script(&dst, &di, &src, &si, &count));
+			assert_eq!(engine.state, VMState::Fault);
+		}
+	}
+
+	#[test]
+	fn test_substr_left_right() {
+		let data = [OpCode::PushData1 as u8, 0x04, 0x0A, 0x0B, 0x0C, 0x0D];
+		let mut script = data.to_vec();
+		script.extend([OpCode::Push1 as u8, OpCode::Push2 as u8, OpCode::Substr as u8]);
+		script.extend(data);
+		script.extend([OpCode::Push1 as u8, OpCode::Left as u8]);
+		script.extend(data);
+		script.extend([OpCode::Push1 as u8, OpCode::Right as u8]);
+		let engine = run_script(script);
+		assert_eq!(engine.state, VMState::Halt);
+		assert_eq!(result(&engine, 0), StackItem::Buffer(vec![0x0D]));
+		assert_eq!(result(&engine, 1), StackItem::Buffer(vec![0x0A]));
+		assert_eq!(result(&engine, 2), StackItem::Buffer(vec![0x0B, 0x0C]));
+	}
+
+	#[test]
+	fn test_left_out_of_range_faults() {
+		let engine = run_script(vec![
+			OpCode::PushData1 as u8,
+			0x01,
+			0x0A,
+			OpCode::Push2 as u8,
+			OpCode::Left as u8,
+		]);
+		assert_eq!(engine.state, VMState::Fault);
+	}
+}
@@ -0,0 +1,333 @@
+use crate::{
+	execution_engine::ExecutionEngine,
+	instruction::Instruction,
+	jump_table::{to_i32, JumpTable},
+	stack_item::StackItem,
+	vm_error::VMError,
+};
+
+impl JumpTable {
+	pub fn depth(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let depth = engine.evaluation_stack()?.borrow().count();
+		engine.push(StackItem::from(depth).into())
+	}
+
+	pub fn drop(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let item = engine.pop()?;
+		engine.release_item(item);
+		Ok(())
+	}
+
+	pub fn nip(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		engine.evaluation_stack()?.borrow_mut().remove(1)?;
+		Ok(())
+	}
+
+	pub fn xdrop(
+		&self,
+		engine: &mut ExecutionEngine,
+		instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let n = Self::pop_index(engine, instruction)?;
+		engine.evaluation_stack()?.borrow_mut().remove(n)?;
+		Ok(())
+	}
+
+	pub fn clear(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		engine.evaluation_stack()?.borrow_mut().clear();
+		Ok(())
+	}
+
+	pub fn dup(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let item = engine.peek(0)?;
+		engine.push(item)
+	}
+
+	pub fn over(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let item = engine.peek(1)?;
+		engine.push(item)
+	}
+
+	pub fn pick(
+		&self,
+		engine: &mut ExecutionEngine,
+		instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let n = Self::pop_index(engine, instruction)?;
+		let item = engine.evaluation_stack()?.borrow().peek(n)?;
+		engine.push(item)
+	}
+
+	pub fn tuck(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let item = engine.peek(0)?;
+		engine.insert(2, item)
+	}
+
+	pub fn swap(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let item = engine.evaluation_stack()?.borrow_mut().remove(1)?;
+		engine.push(item)
+	}
+
+	pub fn rot(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let item = engine.evaluation_stack()?.borrow_mut().remove(2)?;
+		engine.push(item)
+	}
+
+	pub fn roll(
+		&self,
+		engine: &mut ExecutionEngine,
+		instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let n = Self::pop_index(engine, instruction)?;
+		if n == 0 {
+			return Ok(())
+		}
+		let item = engine.evaluation_stack()?.borrow_mut().remove(n)?;
+		engine.push(item)
+	}
+
+	pub fn reverse3(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		engine.evaluation_stack()?.borrow_mut().reverse(3)
+	}
+
+	pub fn reverse4(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		engine.evaluation_stack()?.borrow_mut().reverse(4)
+	}
+
+	pub fn reverse_n(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let n = to_i32(&engine.pop()?.borrow().get_integer()?)?;
+		engine.evaluation_stack()?.borrow_mut().reverse(n)
+	}
+
+	/// Pops the non-negative stack index used by `OpCode::Xdrop`, `OpCode::Pick` and
+	/// `OpCode::Roll`.
+	fn pop_index(engine: &mut ExecutionEngine, instruction: &Instruction) -> Result<i32, VMError> {
+		let n = to_i32(&engine.pop()?.borrow().get_integer()?)?;
+		if n < 0 {
+			return Err(VMError::InvalidParameter(format!(
+				"The negative value {n} is invalid for OpCode::{:?}.",
+				instruction.opcode
+			)))
+		}
+		Ok(n)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::{
+		execution_engine::ExecutionEngine,
+		jump_table::test_helpers::{result, run_script},
+		op_code::OpCode,
+		stack_item::StackItem,
+		vm::script::Script,
+		vm_state::VMState,
+	};
+
+	#[test]
+	fn test_swap_and_rot() {
+		let engine = run_script(vec![
+			OpCode::Push1 as u8,
+			OpCode::Push2 as u8,
+			OpCode::Push3 as u8,
+			OpCode::Rot as u8,
+			OpCode::Swap as u8,
+		]);
+		assert_eq!(engine.state, VMState::Halt);
+		// 1 2 3 -> ROT -> 2 3 1 -> SWAP -> 2 1 3
+		assert_eq!(result(&engine, 0), StackItem::from(3));
+		assert_eq!(result(&engine, 1), StackItem::from(1));
+		assert_eq!(result(&engine, 2), StackItem::from(2));
+	}
+
+	#[test]
+	fn test_depth_and_tuck() {
+		let engine = run_script(vec![
+			OpCode::Push1 as u8,
+			OpCode::Push2 as u8,
+			OpCode::Tuck as u8,
+			OpCode::Depth as u8,
+		]);
+		assert_eq!(result(&engine, 0), StackItem::from(3));
+		assert_eq!(result(&engine, 1), StackItem::from(2));
+		assert_eq!(result(&engine, 2), StackItem::from(1));
+		assert_eq!(result(&engine, 3), StackItem::from(2));
+	}
+
+	#[test]
+	fn test_over_pick_bounds() {
+		assert_eq!(run_script(vec![OpCode::Push1 as u8, OpCode::Over as u8]).state, VMState::Fault);
+
+		// PICK pops n first, so with two items left n == 2 is the stack depth.
+		let engine = run_script(vec![
+			OpCode::Push1 as u8,
+			OpCode::Push2 as u8,
+			OpCode::Push2 as u8,
+			OpCode::Pick as u8,
+		]);
+		assert_eq!(engine.state, VMState::Fault);
+
+		let engine = run_script(vec![
+			OpCode::Push1 as u8,
+			OpCode::Push2 as u8,
+			OpCode::Push1 as u8,
+			OpCode::Pick as u8,
+		]);
+		assert_eq!(engine.state, VMState::Halt);
+		assert_eq!(result(&engine, 0), StackItem::from(1));
+		assert_eq!(result(&engine, 1), StackItem::from(2));
+	}
+
+	fn four_items_then(n: u8, opcode: OpCode) -> Vec<u8> {
+		vec![
+			OpCode::Push1 as u8,
+			OpCode::Push2 as u8,
+			OpCode::Push3 as u8,
+			OpCode::Push4 as u8,
+			OpCode::Push0 as u8 + n,
+			opcode as u8,
+		]
+	}
+
+	#[test]
+	fn test_pick_on_four_items() {
+		let engine = run_script(four_items_then(3, OpCode::Pick));
+		assert_eq!(engine.state, VMState::Halt);
+		assert_eq!(result(&engine, 0), StackItem::from(1));
+		assert_eq!(engine.result_stack.borrow().count(), 5);
+
+		assert_eq!(run_script(four_items_then(4, OpCode::Pick)).state, VMState::Fault);
+	}
+
+	#[test]
+	fn test_roll_on_four_items() {
+		let engine = run_script(four_items_then(0, OpCode::Roll));
+		assert_eq!(engine.state, VMState::Halt);
+		for (index, value) in [4, 3, 2, 1].into_iter().enumerate() {
+			assert_eq!(result(&engine, index as i32), StackItem::from(value));
+		}
+
+		let engine = run_script(four_items_then(3, OpCode::Roll));
+		assert_eq!(engine.state, VMState::Halt);
+		for (index, value) in [1, 4, 3, 2].into_iter().enumerate() {
+			assert_eq!(result(&engine, index as i32), StackItem::from(value));
+		}
+
+		assert_eq!(run_script(four_items_then(4, OpCode::Roll)).state, VMState::Fault);
+	}
+
+	#[test]
+	fn test_clear() {
+		let mut script = four_items_then(0, OpCode::Clear);
+		script.push(OpCode::Depth as u8);
+		let mut engine = ExecutionEngine::new();
+		engine.load_script(Script::new(script), -1, 0).unwrap();
+		for _ in 0..5 {
+			engine.step().unwrap();
+		}
+		assert_eq!(engine.reference_counter.borrow().count(), 5);
+		engine.step().unwrap();
+		assert_eq!(engine.reference_counter.borrow().count(), 0);
+		assert_eq!(engine.execute(), VMState::Halt);
+		assert_eq!(engine.result_stack.borrow().count(), 1);
+		assert_eq!(result(&engine, 0), StackItem::from(0));
+	}
+
+	#[test]
+	fn test_xdrop_on_four_items() {
+		let engine = run_script(four_items_then(2, OpCode::Xdrop));
+		assert_eq!(engine.state, VMState::Halt);
+		assert_eq!(engine.result_stack.borrow().count(), 3);
+		for (index, value) in [4, 3, 1].into_iter().enumerate() {
+			assert_eq!(result(&engine, index as i32), StackItem::from(value));
+		}
+
+		assert_eq!(run_script(four_items_then(4, OpCode::Xdrop)).state, VMState::Fault);
+	}
+
+	#[test]
+	fn test_reverse_n() {
+		let five_items_then = |n: OpCode| {
+			let mut script: Vec<u8> = (1..=5).map(|i| OpCode::Push0 as u8 + i).collect();
+			script.extend([n as u8, OpCode::ReverseN as u8]);
+			script
+		};
+
+		let engine = run_script(five_items_then(OpCode::Push3));
+		assert_eq!(engine.state, VMState::Halt);
+		for (index, value) in [3, 4, 5, 2, 1].into_iter().enumerate() {
+			assert_eq!(result(&engine, index as i32), StackItem::from(value));
+		}
+
+		for n in [OpCode::Push0, OpCode::Push1] {
+			let engine = run_script(five_items_then(n));
+			assert_eq!(engine.state, VMState::Halt);
+			for (index, value) in [5, 4, 3, 2, 1].into_iter().enumerate() {
+				assert_eq!(result(&engine, index as i32), StackItem::from(value));
+			}
+		}
+
+		assert_eq!(run_script(five_items_then(OpCode::Push6)).state, VMState::Fault);
+		assert_eq!(run_script(five_items_then(OpCode::PushM1)).state, VMState::Fault);
+
+		// The count itself is popped before reversing, so 0 is fine on an otherwise empty stack.
+		let engine = run_script(vec![OpCode::Push0 as u8, OpCode::ReverseN as u8]);
+		assert_eq!(engine.state, VMState::Halt);
+		assert_eq!(engine.result_stack.borrow().count(), 0);
+		let engine = run_script(vec![OpCode::Push1 as u8, OpCode::ReverseN as u8]);
+		assert_eq!(engine.state, VMState::Fault);
+	}
+
+	#[test]
+	fn test_drop_on_empty_stack_faults() {
+		assert_eq!(run_script(vec![OpCode::Drop as u8]).state, VMState::Fault);
+	}
+}
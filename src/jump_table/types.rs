@@ -0,0 +1,188 @@
+use crate::{
+	execution_engine::ExecutionEngine, instruction::Instruction, jump_table::JumpTable,
+	stack_item::StackItem, stack_item_type::StackItemType, vm_error::VMError,
+};
+
+impl JumpTable {
+	pub fn is_null(
+		&self,
+		engine: &mut ExecutionEngine,
+		_instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let x = engine.pop()?.borrow().is_null();
+		engine.push(StackItem::Boolean(x).into())
+	}
+
+	pub fn is_type(
+		&self,
+		engine: &mut ExecutionEngine,
+		instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let x = engine.pop()?;
+		let ty = StackItemType::try_from_u8(instruction.token_u8())?;
+		if ty == StackItemType::Any {
+			return Err(VMError::InvalidType(format!("Invalid type: {ty:?}")))
+		}
+		let result = x.borrow().get_type() == ty;
+		engine.push(StackItem::Boolean(result).into())
+	}
+
+	pub fn convert(
+		&self,
+		engine: &mut ExecutionEngine,
+		instruction: &Instruction,
+	) -> Result<(), VMError> {
+		let x = engine.pop()?;
+		let ty = StackItemType::try_from_u8(instruction.token_u8())?;
+		if !x.borrow().is_null() && x.borrow().get_type() == ty {
+			return engine.push(x)
+		}
+		let mut result = x.borrow().convert_to(ty)?;
+		if let StackItem::Array(items) | StackItem::Struct(items) = &mut result {
+			// The elements of a struct are stored by value, so its nested structs are copied.
+			if matches!(*x.borrow(), StackItem::Struct(_)) {
+				for item in items.iter_mut() {
+					*item = Self::copy_if_struct(engine, item.clone())?;
+				}
+			}
+			for _ in 0..items.len() {
+				engine.reference_counter.borrow_mut().add_reference();
+			}
+		}
+		engine.push(result.into())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::{
+		jump_table::test_helpers::{result, run_script},
+		op_code::OpCode,
+		stack_item::StackItem,
+		stack_item_type::StackItemType,
+		vm_state::VMState,
+	};
+	use std::rc::Rc;
+
+	#[test]
+	fn test_is_null_and_is_type() {
+		let engine = run_script(vec![
+			OpCode::PushNull as u8,
+			OpCode::IsNull as u8,
+			OpCode::Push1 as u8,
+			OpCode::IsType as u8,
+			StackItemType::Integer as u8,
+		]);
+		assert_eq!(engine.state, VMState::Halt);
+		assert_eq!(result(&engine, 0), StackItem::Boolean(true));
+		assert_eq!(result(&engine, 1), StackItem::Boolean(true));
+	}
+
+	#[test]
+	fn test_convert() {
+		let engine = run_script(vec![
+			OpCode::PushInt16 as u8,
+			0x00,
+			0x01,
+			OpCode::Convert as u8,
+			StackItemType::ByteString as u8,
+		]);
+		assert_eq!(engine.state, VMState::Halt);
+		assert_eq!(result(&engine, 0), StackItem::ByteString(vec![0x00, 0x01]));
+	}
+
+	#[test]
+	fn test_is_type_buffer_and_pointer() {
+		let engine = run_script(vec![
+			OpCode::Push1 as u8,
+			OpCode::NewBuffer as u8,
+			OpCode::Dup as u8,
+			OpCode::IsType as u8,
+			StackItemType::Buffer as u8,
+			OpCode::Swap as u8,
+			OpCode::IsType as u8,
+			StackItemType::ByteString as u8,
+			OpCode::PushA as u8,
+			0x00,
+			0x00,
+			0x00,
+			0x00,
+			OpCode::IsType as u8,
+			StackItemType::Pointer as u8,
+		]);
+		assert_eq!(engine.state, VMState::Halt);
+		assert_eq!(result(&engine, 0), StackItem::Boolean(true));
+		assert_eq!(result(&engine, 1), StackItem::Boolean(false));
+		assert_eq!(result(&engine, 2), StackItem::Boolean(true));
+
+		let engine = run_script(vec![OpCode::PushNull as u8, OpCode::IsType as u8, 0x00]);
+		assert_eq!(engine.state, VMState::Fault);
+	}
+
+	#[test]
+	fn test_convert_compound() {
+		// [1, 2] converted to a struct and back
+		let engine = run_script(vec![
+			OpCode::Push2 as u8,
+			OpCode::Push1 as u8,
+			OpCode::Push2 as u8,
+			OpCode::Pack as u8,
+			OpCode::Convert as u8,
+			StackItemType::Struct as u8,
+			OpCode::Dup as u8,
+			OpCode::Convert as u8,
+			StackItemType::Array as u8,
+		]);
+		assert_eq!(engine.state, VMState::Halt);
+		let StackItem::Array(array) = result(&engine, 0) else { panic!("expected an array") };
+		let StackItem::Struct(structure) = result(&engine, 1) else { panic!("expected a struct") };
+		assert_eq!(array.len(), 2);
+		assert!(Rc::ptr_eq(&array[0], &structure[0]));
+
+		// A nested struct is copied when its parent struct is converted to an array.
+		let engine = run_script(vec![
+			OpCode::Push0 as u8,
+			OpCode::NewStruct as u8,
+			OpCode::Push1 as u8,
+			OpCode::PackStruct as u8,
+			OpCode::Dup as u8,
+			OpCode::Convert as u8,
+			StackItemType::Array as u8,
+		]);
+		assert_eq!(engine.state, VMState::Halt);
+		let StackItem::Array(array) = result(&engine, 0) else { panic!("expected an array") };
+		let StackItem::Struct(structure) = result(&engine, 1) else { panic!("expected a struct") };
+		assert!(!Rc::ptr_eq(&array[0], &structure[0]));
+		assert_eq!(*array[0].borrow(), *structure[0].borrow());
+
+		let engine = run_script(vec![
+			OpCode::NewMap as u8,
+			OpCode::Convert as u8,
+			StackItemType::Integer as u8,
+		]);
+		assert_eq!(engine.state, VMState::Fault);
+	}
+
+	#[test]
+	fn test_convert_integer_boolean() {
+		let convert = |push: OpCode, ty: StackItemType| {
+			let engine = run_script(vec![push as u8, OpCode::Convert as u8, ty as u8]);
+			assert_eq!(engine.state, VMState::Halt);
+			result(&engine, 0)
+		};
+		assert_eq!(convert(OpCode::Push0, StackItemType::Boolean), StackItem::Boolean(false));
+		assert_eq!(convert(OpCode::Push5, StackItemType::Boolean), StackItem::Boolean(true));
+		assert_eq!(convert(OpCode::PushTrue, StackItemType::Integer), StackItem::from(1));
+		assert_eq!(convert(OpCode::PushFalse, StackItemType::Integer), StackItem::from(0));
+
+		// The round trip is lossy for integers other than 0 and 1.
+		let engine = run_script(vec![
+			OpCode::Push5 as u8,
+			OpCode::Convert as u8,
+			StackItemType::Boolean as u8,
+			OpCode::Convert as u8,
+			StackItemType::Integer as u8,
+		]);
+		assert_eq!(result(&engine, 0), StackItem::from(1));
+	}
+}
@@ -1,19 +1,21 @@
-#![feature(associated_type_defaults)]
-#![feature(linked_list_remove)]
-#![feature(exclusive_range_pattern)]
+// Map keys are primitive items, whose hash can't change while they are stored.
+#![allow(clippy::mutable_key_type)]
 
 extern crate core;
 
 pub use num_bigint::BigInt;
 pub mod exception;
+pub mod jump_table;
 pub mod script;
-// pub mod types;
+pub mod types;
+pub mod utility;
 
 pub mod vm;
 
 pub use exception::*;
+pub use jump_table::JumpTable;
 pub use script::*;
-// pub use types::*;
+pub use types::*;
 pub use vm::*;
 
 pub fn add(left: usize, right: usize) -> usize {
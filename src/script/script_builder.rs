@@ -1,15 +1,36 @@
-use std::{error::Error, num::ParseIntError, vec};
-
-use crate::op_code::OpCode;
-use num_bigint::{BigInt, BigUint, Sign};
-use num_traits::{pow, FromPrimitive, ToBytes};
+use std::vec;
+
+use crate::{
+	interop_service::interop_hash,
+	op_code::OpCode,
+	utility::to_hex,
+	vm::script::{Script, ScriptError},
+};
+use num_bigint::{BigInt, Sign};
+use num_traits::{pow, FromPrimitive, ToPrimitive};
 use serde::{Deserialize, Serialize};
 
+/// A value that `ScriptBuilder::emit_push_param` pushes with the matching instruction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PushParam {
+	Integer(BigInt),
+	Boolean(bool),
+	Bytes(Vec<u8>),
+	String(String),
+	Null,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash,Serialize,Deserialize)]
 pub struct ScriptBuilder {
 	output: Vec<u8>,
 }
 
+impl Default for ScriptBuilder {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
 impl ScriptBuilder {
 	pub fn new() -> Self {
 		Self { output: Vec::new() }
@@ -19,29 +40,33 @@ impl ScriptBuilder {
 		self.output.len()
 	}
 
+	pub fn is_empty(&self) -> bool {
+		self.output.is_empty()
+	}
+
 	pub fn emit(&mut self, opcode: OpCode, operand: Vec<u8>) -> &ScriptBuilder {
 		self.output.push(opcode as u8);
 		self.output.extend(operand);
-		return self
+		self
 	}
 
 	pub fn emit_call(&mut self, offset: i32) -> &ScriptBuilder {
 		if offset < i8::MIN.into() || offset > i8::MAX.into() {
-			return self.emit(OpCode::CallL, offset.to_le_bytes().to_vec())
+			self.emit(OpCode::CallL, offset.to_le_bytes().to_vec())
 		} else {
-			return self.emit(OpCode::Call, vec![offset as u8]);
+			self.emit(OpCode::Call, vec![offset as u8])
 		}
 	}
 
 	pub fn emit_jump(&mut self, opcode: OpCode, offset: i32) -> Result<&ScriptBuilder, String> {
 		let mut opcode_u8 = opcode as u8;
-		if opcode_u8 < (OpCode::Jmp as u8) || opcode_u8 > (OpCode::JmpLeL as u8) {
+		if !(OpCode::Jmp as u8..=OpCode::JmpLeL as u8).contains(&opcode_u8) {
 			return Err(format!("Invalid OpCode {}", opcode_u8));
 		}
-		if opcode_u8 % 2 == 0 && (offset < i8::MIN.into() || offset > i8::MAX.into()) {
+		if opcode_u8.is_multiple_of(2) && (offset < i8::MIN.into() || offset > i8::MAX.into()) {
 			opcode_u8 += 1;
 		}
-		if opcode_u8 % 2 == 0 {
+		if opcode_u8.is_multiple_of(2) {
 			Ok(self.emit(OpCode::from_u8(opcode_u8).unwrap(), vec![offset as u8]))
 		} else {
 			Ok(self.emit(OpCode::from_u8(opcode_u8).unwrap(), offset.to_le_bytes().to_vec()))
@@ -52,38 +77,19 @@ impl ScriptBuilder {
 		if value == BigInt::from(-1) {
 			return Ok(self.emit(OpCode::PushM1, vec![]));
 		}
-		if value > BigInt::from(-1) && value <= BigInt::from(16) {
-			return Ok(self.emit(OpCode::from_u8(OpCode::Push0 as u8 + value.to_signed_bytes_le()[0]).unwrap(), vec![]));
+		if let Some(small @ 0..=16) = value.to_u8() {
+			return Ok(self.emit(OpCode::from_u8(OpCode::Push0 as u8 + small).unwrap(), vec![]));
 		}
-		let bits = value.bits();
-		let is_negative = value.sign() == Sign::Minus;
-		if bits > 255 && value != -pow(BigInt::from(2), 255) {
-			return Err("Only 32 bytes of BigInt allowed".to_string());
-		}
-		// let mut buffer = [0u8; 32];
-		let mut bytes_written = value.to_signed_bytes_le();
-		// buffer[..bytes_written.len()].copy_from_slice(&bytes_written);
-
-		let written_len = bytes_written.len();
-		if written_len > 32 {return Err("Only 32 bytes of BigInt allowed".to_string());}
-		let (opcode, pad_len) = match bytes_written.len() {
-			1 => (OpCode::PushInt8, 1),
-			2 => (OpCode::PushInt16, 2),
-			bytes if bytes <= 4 => (OpCode::PushInt32, 4),
-			bytes if bytes <= 8 => (OpCode::PushInt64, 8),
-			bytes if bytes <= 16 => (OpCode::PushInt128, 16),
-			_ => (OpCode::PushInt256, 32),
-		};
-
-		let sign_byte = if is_negative { 0xFF } else { 0x00 };
-		let padded = vec![sign_byte; pad_len - written_len];
-		bytes_written.extend(padded);
-		Ok(self.emit(opcode, bytes_written))
+		let (opcode, operand) = encode_pushint(&value)?;
+		Ok(self.emit(opcode, operand))
 	}
 
 	pub fn emit_bool(&mut self, value: bool) -> &ScriptBuilder {
-		if value {return self.emit(OpCode::PushTrue, vec![]);}
-		else {return self.emit(OpCode::PushFalse, vec![]);}
+		if value {
+			self.emit(OpCode::PushTrue, vec![])
+		} else {
+			self.emit(OpCode::PushFalse, vec![])
+		}
 	}
 
 	pub fn emit_bytes(&mut self, data: Vec<u8>) -> &ScriptBuilder {
@@ -104,19 +110,40 @@ impl ScriptBuilder {
 				self.output.extend_from_slice(&data);
 			},
 		}
-		return self;
+		self
 	}
 
 	// Other push methods
 	pub fn emit_string(&mut self, data: &str) -> &ScriptBuilder {
 		let bytes = data.as_bytes().to_vec();
 		self.emit_bytes(bytes);
-		return self;
+		self
+	}
+
+	/// Pushes `value` with the shortest `PUSH*` or `PUSHINT*` instruction.
+	pub fn emit_push_int(&mut self, value: BigInt) -> Result<&ScriptBuilder, String> {
+		self.emit_int(value)
+	}
+
+	/// Pushes `data` with the shortest `PUSHDATA*` instruction.
+	pub fn emit_push_bytes(&mut self, data: Vec<u8>) -> &ScriptBuilder {
+		self.emit_bytes(data)
+	}
+
+	/// Pushes `param` with the instruction matching its kind, `PUSHNULL` for `PushParam::Null`.
+	pub fn emit_push_param(&mut self, param: PushParam) -> Result<&ScriptBuilder, String> {
+		match param {
+			PushParam::Integer(value) => self.emit_push_int(value),
+			PushParam::Boolean(value) => Ok(self.emit_bool(value)),
+			PushParam::Bytes(data) => Ok(self.emit_push_bytes(data)),
+			PushParam::String(data) => Ok(self.emit_string(&data)),
+			PushParam::Null => Ok(self.emit(OpCode::PushNull, vec![])),
+		}
 	}
 
 	pub fn emit_raw(&mut self, script: Vec<u8>) -> &ScriptBuilder {
 		self.output.extend(script);
-		return self;
+		self
 	}
 
 	pub fn emit_syscall(&mut self, api: u32) {
@@ -125,16 +152,72 @@ impl ScriptBuilder {
 		self.emit(opcode, operand);
 	}
 
+	/// Emits a `SYSCALL` to the well-known interop service called `name`, e.g.
+	/// `System.Runtime.Log`.
+	pub fn emit_syscall_by_name(&mut self, name: &str) -> Result<&ScriptBuilder, String> {
+		let hash = interop_hash(name).ok_or_else(|| format!("Unknown interop service: {name}"))?;
+		self.emit_syscall(hash);
+		Ok(self)
+	}
+
 	pub fn to_bytes(self) -> Vec<u8> {
 		self.output
 	}
+
+	/// Encodes the emitted bytes as lowercase hex.
+	pub fn to_hex(self) -> String {
+		to_hex(&self.output)
+	}
+
+	/// Wraps the emitted bytes in a `Script` without validating them.
+	pub fn to_script(self) -> Script {
+		Script::new(self.output)
+	}
+
+	/// Wraps the emitted bytes in a strict-mode `Script`, validating them up front.
+	pub fn to_script_strict(self) -> Result<Script, ScriptError> {
+		Script::new_strict(self.output)
+	}
+}
+
+/// Encodes `value` as the operand of the smallest `PUSHINT*` instruction able to hold it,
+/// returning that opcode and the little-endian, sign-extended operand.
+pub fn encode_pushint(value: &BigInt) -> Result<(OpCode, Vec<u8>), String> {
+	let bits = value.bits();
+	let is_negative = value.sign() == Sign::Minus;
+	if bits > 255 && *value != -pow(BigInt::from(2), 255) {
+		return Err("Only 32 bytes of BigInt allowed".to_string());
+	}
+	let mut bytes_written = value.to_signed_bytes_le();
+
+	let written_len = bytes_written.len();
+	if written_len > 32 {
+		return Err("Only 32 bytes of BigInt allowed".to_string());
+	}
+	let (opcode, pad_len) = match bytes_written.len() {
+		1 => (OpCode::PushInt8, 1),
+		2 => (OpCode::PushInt16, 2),
+		bytes if bytes <= 4 => (OpCode::PushInt32, 4),
+		bytes if bytes <= 8 => (OpCode::PushInt64, 8),
+		bytes if bytes <= 16 => (OpCode::PushInt128, 16),
+		_ => (OpCode::PushInt256, 32),
+	};
+
+	let sign_byte = if is_negative { 0xFF } else { 0x00 };
+	let padded = vec![sign_byte; pad_len - written_len];
+	bytes_written.extend(padded);
+	Ok((opcode, bytes_written))
 }
 
 #[cfg(test)]
 mod tests {
-	use crate::script;
-
-use super::*;
+	use super::*;
+	use crate::{
+		execution_engine::ExecutionEngine,
+		stack_item::StackItem,
+		utility::{hex_to_bytes, script_from_hex},
+		vm_state::VMState,
+	};
 
 	#[test]
 	fn test_emit() {
@@ -181,6 +264,20 @@ use super::*;
 		assert_eq!(err, "Only 32 bytes of BigInt allowed");
 	}
 
+	#[test]
+	fn test_encode_pushint() {
+		assert_eq!(encode_pushint(&BigInt::from(-128)).unwrap(), (OpCode::PushInt8, vec![0x80]));
+		assert_eq!(
+			encode_pushint(&BigInt::from(256)).unwrap(),
+			(OpCode::PushInt16, vec![0x00, 0x01])
+		);
+		assert_eq!(
+			encode_pushint(&BigInt::from(-65536)).unwrap(),
+			(OpCode::PushInt32, vec![0x00, 0x00, 0xFF, 0xFF])
+		);
+		assert!(encode_pushint(&pow(BigInt::from(2), 255)).is_err());
+	}
+
 	#[test]
 	fn test_emit_syscall() {
 		let mut script = ScriptBuilder::new();
@@ -188,6 +285,18 @@ use super::*;
 		assert_eq!(vec![OpCode::Syscall as u8, 0x75, 0xC8, 0x93, 0xE3], script.to_bytes());
 	}
 
+	#[test]
+	fn test_emit_syscall_by_name() {
+		let mut by_hash = ScriptBuilder::new();
+		by_hash.emit_syscall(0x9647e7cf);
+		let mut by_name = ScriptBuilder::new();
+		by_name.emit_syscall_by_name("System.Runtime.Log").unwrap();
+		assert_eq!(by_name.to_bytes(), by_hash.to_bytes());
+
+		let err = ScriptBuilder::new().emit_syscall_by_name("System.Runtime.Unknown").unwrap_err();
+		assert_eq!(err, "Unknown interop service: System.Runtime.Unknown");
+	}
+
 	#[test]
 	fn test_emit_call() {
 		let mut script = ScriptBuilder::new();
@@ -205,19 +314,45 @@ use super::*;
 
 	#[test]
 	fn test_emit_jump() {
-		// Hard to iterate all values of enum. Skip for now.
+		let offsets = [
+			i8::MIN as i32,
+			i8::MAX as i32,
+			i8::MIN as i32 - 1,
+			i8::MAX as i32 + 1,
+			i32::MIN,
+			i32::MAX,
+		];
+		for opcode_u8 in OpCode::Jmp as u8..=OpCode::JmpLeL as u8 {
+			let opcode = OpCode::from_u8(opcode_u8).unwrap();
+			for offset in offsets {
+				let script =
+					ScriptBuilder::new().emit_jump(opcode, offset).unwrap().to_owned().to_bytes();
+				if opcode_u8.is_multiple_of(2) && i8::try_from(offset).is_ok() {
+					assert_eq!(script, vec![opcode_u8, offset as u8]);
+				} else {
+					// Long forms are kept, and short forms are promoted when the offset doesn't fit.
+					let mut expected = vec![opcode_u8 | 1];
+					expected.extend(offset.to_le_bytes());
+					assert_eq!(script, expected);
+				}
+			}
+		}
+
+		assert!(ScriptBuilder::new().emit_jump(OpCode::Nop, 0).is_err());
+		assert!(ScriptBuilder::new().emit_jump(OpCode::Call, 0).is_err());
 	}
 
-	fn hex_to_bytes(s: &str) -> Option<Vec<u8>> {
-		if s.len() % 2 == 0 {
-			(0..s.len())
-				.step_by(2)
-				.map(|i| s.get(i..i + 2)
-						  .and_then(|sub| u8::from_str_radix(sub, 16).ok()))
-				.collect()
-		} else {
-			None
-		}
+	#[test]
+	fn test_hex_round_trip() {
+		let mut builder = ScriptBuilder::new();
+		builder.emit_int(BigInt::from(1000)).unwrap();
+		builder.emit_string("neo");
+		builder.emit(OpCode::Ret, vec![]);
+		let bytes = builder.clone().to_bytes();
+		let hex = builder.to_hex();
+		assert_eq!(hex, "01e8030c036e656f40");
+		assert_eq!(script_from_hex(&hex).unwrap(), bytes);
+		assert_eq!(script_from_hex(&hex.to_uppercase()).unwrap(), bytes);
 	}
 
 	#[test]
@@ -255,6 +390,41 @@ use super::*;
 		assert_eq!(err, "Only 32 bytes of BigInt allowed");
 	}
 
+	#[test]
+	fn test_emit_push_small_int_boundaries() {
+		assert_eq!(
+			ScriptBuilder::new().emit_int(BigInt::from(0)).unwrap().to_owned().to_bytes(),
+			vec![OpCode::Push0 as u8]
+		);
+		assert_eq!(
+			ScriptBuilder::new().emit_int(BigInt::from(16)).unwrap().to_owned().to_bytes(),
+			vec![OpCode::Push16 as u8]
+		);
+		assert_eq!(
+			ScriptBuilder::new().emit_int(BigInt::from(17)).unwrap().to_owned().to_bytes(),
+			vec![OpCode::PushInt8 as u8, 17]
+		);
+		assert_eq!(
+			ScriptBuilder::new().emit_int(BigInt::from(-2)).unwrap().to_owned().to_bytes(),
+			vec![OpCode::PushInt8 as u8, 0xfe]
+		);
+	}
+
+	#[test]
+	fn test_to_script_strict() {
+		let mut builder = ScriptBuilder::new();
+		builder.emit(OpCode::Push1, vec![]);
+		builder.emit(OpCode::Push2, vec![]);
+		builder.emit(OpCode::Add, vec![]);
+		let script = builder.to_script_strict().unwrap();
+		assert!(script.strict_mode());
+
+		let mut engine = ExecutionEngine::new();
+		engine.load_script(script, -1, 0).unwrap();
+		assert_eq!(engine.execute(), VMState::Halt);
+		assert_eq!(*engine.result_stack.borrow().peek(0).unwrap().borrow(), StackItem::from(3));
+	}
+
 	#[test]
 	fn test_emit_push_bool() {
 		assert_eq!(ScriptBuilder::new().emit_bool(true).to_owned().to_bytes(), vec![OpCode::PushTrue as u8]);
@@ -269,4 +439,46 @@ use super::*;
 
 		// TODO: more tests
 	}
+
+	#[test]
+	fn test_emit_push_null() {
+		let mut script = ScriptBuilder::new();
+		script.emit_push_param(PushParam::Null).unwrap();
+		assert_eq!(script.to_bytes(), vec![OpCode::PushNull as u8]);
+	}
+
+	#[test]
+	fn test_emit_push_string() {
+		let mut script = ScriptBuilder::new();
+		script.emit_push_param(PushParam::String("neo".to_string())).unwrap();
+		assert_eq!(script.to_bytes(), vec![OpCode::PushData1 as u8, 3, b'n', b'e', b'o']);
+	}
+
+	#[test]
+	fn test_emit_push_param() {
+		let mut script = ScriptBuilder::new();
+		script.emit_push_param(PushParam::Integer(BigInt::from(5))).unwrap();
+		script.emit_push_param(PushParam::Boolean(true)).unwrap();
+		script.emit_push_param(PushParam::Bytes(vec![0xAB])).unwrap();
+		script.emit_push_int(BigInt::from(100)).unwrap();
+		script.emit_push_bytes(vec![]);
+		assert_eq!(
+			script.to_bytes(),
+			vec![
+				OpCode::Push5 as u8,
+				OpCode::PushTrue as u8,
+				OpCode::PushData1 as u8,
+				1,
+				0xAB,
+				OpCode::PushInt8 as u8,
+				100,
+				OpCode::PushData1 as u8,
+				0,
+			]
+		);
+		let err = ScriptBuilder::new()
+			.emit_push_param(PushParam::Integer(pow(BigInt::from(2), 255)))
+			.unwrap_err();
+		assert_eq!(err, "Only 32 bytes of BigInt allowed");
+	}
 }
@@ -0,0 +1,350 @@
+//! The binary format used by `System.Binary.Serialize` and `System.Binary.Deserialize`.
+//!
+//! Each item is written as its `StackItemType` byte followed by its payload: nothing for
+//! `Null`, one byte for `Boolean`, length-prefixed bytes for `Integer`, `ByteString` and
+//! `Buffer`, and an item count followed by the serialized elements (or key-value pairs) for
+//! `Array`, `Struct` and `Map`.
+
+use crate::{
+	execution_engine_limits::ExecutionEngineLimits, ordered_map::OrderedMap, stack_item::StackItem,
+	stack_item_type::StackItemType, vm_error::VMError,
+};
+use num_bigint::BigInt;
+use num_traits::{FromPrimitive, Zero};
+use std::{cell::RefCell, rc::Rc};
+
+/// Serializes `item` to a byte array.
+///
+/// Map entries are written in insertion order, or sorted by the serialized form of their keys
+/// when `canonical` is set, so that equal maps serialize identically, e.g. for hashing.
+pub fn serialize(
+	item: &StackItem,
+	limits: &ExecutionEngineLimits,
+	canonical: bool,
+) -> Result<Vec<u8>, VMError> {
+	let mut writer = Vec::new();
+	let mut serialized = Vec::new();
+	serialize_item(item, limits, canonical, &mut writer, &mut serialized)?;
+	Ok(writer)
+}
+
+/// Deserializes a `StackItem` from a byte array.
+pub fn deserialize(
+	bytes: &[u8],
+	limits: &ExecutionEngineLimits,
+) -> Result<Rc<RefCell<StackItem>>, VMError> {
+	let mut reader = Reader::new(bytes);
+	let mut count = 0;
+	deserialize_item(&mut reader, limits, &mut count)
+}
+
+fn serialize_item(
+	item: &StackItem,
+	limits: &ExecutionEngineLimits,
+	canonical: bool,
+	writer: &mut Vec<u8>,
+	serialized: &mut Vec<*const StackItem>,
+) -> Result<(), VMError> {
+	if matches!(item, StackItem::Array(_) | StackItem::Struct(_) | StackItem::Map(_)) {
+		let pointer = item as *const StackItem;
+		if serialized.contains(&pointer) {
+			return Err(VMError::InvalidType(
+				"Circular or shared references can't be serialized.".to_string(),
+			))
+		}
+		serialized.push(pointer);
+	}
+	writer.push(item.get_type() as u8);
+	match item {
+		StackItem::Null => {},
+		StackItem::Boolean(value) => writer.push(*value as u8),
+		StackItem::Integer(value) => {
+			let bytes = if value.is_zero() { Vec::new() } else { value.to_signed_bytes_le() };
+			write_var_bytes(writer, &bytes);
+		},
+		StackItem::ByteString(bytes) | StackItem::Buffer(bytes) => write_var_bytes(writer, bytes),
+		StackItem::Array(items) | StackItem::Struct(items) => {
+			write_var_int(writer, items.len() as u64);
+			for child in items {
+				serialize_child(child, limits, canonical, writer, serialized)?;
+			}
+		},
+		StackItem::Map(map) => {
+			write_var_int(writer, map.len() as u64);
+			let mut entries = Vec::with_capacity(map.len());
+			for (key, value) in map {
+				let mut key_bytes = Vec::new();
+				serialize_item(key, limits, canonical, &mut key_bytes, serialized)?;
+				entries.push((key_bytes, value));
+			}
+			if canonical {
+				entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+			}
+			for (key_bytes, value) in entries {
+				writer.extend(key_bytes);
+				serialize_child(value, limits, canonical, writer, serialized)?;
+			}
+		},
+		StackItem::InteropInterface(_) | StackItem::Pointer { .. } =>
+			return Err(VMError::InvalidType(format!("{:?} can't be serialized.", item.get_type()))),
+	}
+	if writer.len() > limits.max_item_size {
+		return Err(VMError::ItemTooLarge(format!("MaxItemSize exceed: {}", writer.len())))
+	}
+	Ok(())
+}
+
+fn serialize_child(
+	child: &Rc<RefCell<StackItem>>,
+	limits: &ExecutionEngineLimits,
+	canonical: bool,
+	writer: &mut Vec<u8>,
+	serialized: &mut Vec<*const StackItem>,
+) -> Result<(), VMError> {
+	if serialized.contains(&(child.as_ptr() as *const StackItem)) {
+		return Err(VMError::InvalidType(
+			"Circular or shared references can't be serialized.".to_string(),
+		))
+	}
+	serialize_item(&child.borrow(), limits, canonical, writer, serialized)
+}
+
+fn deserialize_item(
+	reader: &mut Reader,
+	limits: &ExecutionEngineLimits,
+	count: &mut usize,
+) -> Result<Rc<RefCell<StackItem>>, VMError> {
+	*count += 1;
+	if *count > limits.max_stack_size {
+		return Err(VMError::InvalidParameter(format!("MaxStackSize exceed: {count}")))
+	}
+	let ty = reader.read_u8()?;
+	let item = match StackItemType::from_u8(ty) {
+		Some(StackItemType::Any) => StackItem::Null,
+		Some(StackItemType::Boolean) => match reader.read_u8()? {
+			0 => StackItem::Boolean(false),
+			1 => StackItem::Boolean(true),
+			value => return Err(invalid_format(format!("Invalid boolean value {value}"))),
+		},
+		Some(StackItemType::Integer) => {
+			let bytes = reader.read_var_bytes(StackItem::MAX_INTEGER_SIZE)?;
+			StackItem::Integer(BigInt::from_signed_bytes_le(bytes))
+		},
+		Some(StackItemType::ByteString) =>
+			StackItem::ByteString(reader.read_var_bytes(limits.max_item_size)?.to_vec()),
+		Some(StackItemType::Buffer) =>
+			StackItem::Buffer(reader.read_var_bytes(limits.max_item_size)?.to_vec()),
+		Some(ty @ (StackItemType::Array | StackItemType::Struct)) => {
+			let length = reader.read_var_int(limits.max_stack_size as u64)? as usize;
+			let mut items = Vec::with_capacity(length);
+			for _ in 0..length {
+				items.push(deserialize_item(reader, limits, count)?);
+			}
+			if ty == StackItemType::Array {
+				StackItem::Array(items.into())
+			} else {
+				StackItem::Struct(items.into())
+			}
+		},
+		Some(StackItemType::Map) => {
+			let length = reader.read_var_int(limits.max_stack_size as u64)? as usize;
+			let mut map = OrderedMap::with_capacity(length);
+			for _ in 0..length {
+				let key = deserialize_item(reader, limits, count)?;
+				let key = key.borrow().clone();
+				key.check_map_key()?;
+				let value = deserialize_item(reader, limits, count)?;
+				map.insert(key, value);
+			}
+			StackItem::Map(map)
+		},
+		_ => return Err(invalid_format(format!("Invalid stack item type {ty:#04x}"))),
+	};
+	Ok(item.into())
+}
+
+pub(crate) fn write_var_int(writer: &mut Vec<u8>, value: u64) {
+	if value < 0xFD {
+		writer.push(value as u8);
+	} else if value <= 0xFFFF {
+		writer.push(0xFD);
+		writer.extend_from_slice(&(value as u16).to_le_bytes());
+	} else if value <= 0xFFFF_FFFF {
+		writer.push(0xFE);
+		writer.extend_from_slice(&(value as u32).to_le_bytes());
+	} else {
+		writer.push(0xFF);
+		writer.extend_from_slice(&value.to_le_bytes());
+	}
+}
+
+pub(crate) fn write_var_bytes(writer: &mut Vec<u8>, bytes: &[u8]) {
+	write_var_int(writer, bytes.len() as u64);
+	writer.extend_from_slice(bytes);
+}
+
+fn invalid_format(message: String) -> VMError {
+	VMError::InvalidParameter(message)
+}
+
+/// Reads the primitives of the binary format from a byte slice.
+pub(crate) struct Reader<'a> {
+	bytes: &'a [u8],
+	position: usize,
+}
+
+impl<'a> Reader<'a> {
+	pub(crate) fn new(bytes: &'a [u8]) -> Self {
+		Self { bytes, position: 0 }
+	}
+
+	/// Indicates whether every byte has been read.
+	pub(crate) fn is_at_end(&self) -> bool {
+		self.position == self.bytes.len()
+	}
+
+	pub(crate) fn read_bytes(&mut self, count: usize) -> Result<&'a [u8], VMError> {
+		let end = self
+			.position
+			.checked_add(count)
+			.filter(|end| *end <= self.bytes.len())
+			.ok_or_else(|| invalid_format("Unexpected end of data".to_string()))?;
+		let bytes = &self.bytes[self.position..end];
+		self.position = end;
+		Ok(bytes)
+	}
+
+	pub(crate) fn read_u8(&mut self) -> Result<u8, VMError> {
+		Ok(self.read_bytes(1)?[0])
+	}
+
+	/// Reads a variable-length integer, rejecting non-canonical encodings and values above
+	/// `max`.
+	pub(crate) fn read_var_int(&mut self, max: u64) -> Result<u64, VMError> {
+		let prefix = self.read_u8()?;
+		let (value, min) = match prefix {
+			0xFD => (u16::from_le_bytes(self.read_bytes(2)?.try_into().unwrap()) as u64, 0xFD),
+			0xFE => (u32::from_le_bytes(self.read_bytes(4)?.try_into().unwrap()) as u64, 0x1_0000),
+			0xFF => (u64::from_le_bytes(self.read_bytes(8)?.try_into().unwrap()), 0x1_0000_0000),
+			value => (value as u64, 0),
+		};
+		if value < min {
+			return Err(invalid_format(format!("Non-canonical variable integer {value}")))
+		}
+		if value > max {
+			return Err(invalid_format(format!("The value {value} exceeds the maximum {max}")))
+		}
+		Ok(value)
+	}
+
+	pub(crate) fn read_var_bytes(&mut self, max: usize) -> Result<&'a [u8], VMError> {
+		let length = self.read_var_int(max as u64)? as usize;
+		self.read_bytes(length)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn round_trip(item: &StackItem) -> StackItem {
+		let limits = ExecutionEngineLimits::default();
+		let bytes = serialize(item, &limits, false).unwrap();
+		let result = deserialize(&bytes, &limits).unwrap();
+		let result = result.borrow().clone();
+		result
+	}
+
+	#[test]
+	fn test_primitives() {
+		let limits = ExecutionEngineLimits::default();
+		assert_eq!(serialize(&StackItem::Null, &limits, false).unwrap(), vec![0x00]);
+		assert_eq!(serialize(&StackItem::Boolean(true), &limits, false).unwrap(), vec![0x20, 0x01]);
+		assert_eq!(serialize(&StackItem::from(0), &limits, false).unwrap(), vec![0x21, 0x00]);
+		assert_eq!(
+			serialize(&StackItem::from(-1), &limits, false).unwrap(),
+			vec![0x21, 0x01, 0xFF]
+		);
+		assert_eq!(
+			serialize(&StackItem::from(vec![0xAB, 0xCD]), &limits, false).unwrap(),
+			vec![0x28, 0x02, 0xAB, 0xCD]
+		);
+		for item in [
+			StackItem::Null,
+			StackItem::Boolean(false),
+			StackItem::from(123456789),
+			StackItem::Buffer(vec![1, 2, 3]),
+		] {
+			assert_eq!(round_trip(&item), item);
+		}
+	}
+
+	#[test]
+	fn test_nested_array() {
+		let inner =
+			StackItem::Array(vec![StackItem::from(1).into(), StackItem::Null.into()].into());
+		let item = StackItem::Struct(vec![inner.into(), StackItem::Boolean(true).into()].into());
+		let StackItem::Struct(items) = round_trip(&item) else { panic!("expected a struct") };
+		assert_eq!(items.len(), 2);
+		assert_eq!(*items[1].borrow(), StackItem::Boolean(true));
+		let StackItem::Array(inner) = &*items[0].borrow() else { panic!("expected an array") };
+		assert_eq!(*inner[0].borrow(), StackItem::from(1));
+		assert_eq!(*inner[1].borrow(), StackItem::Null);
+	}
+
+	#[test]
+	fn test_map_keys() {
+		let mut map = OrderedMap::new();
+		map.insert(StackItem::from(7), StackItem::Boolean(false).into());
+		map.insert(StackItem::from(b"key".to_vec()), StackItem::from(42).into());
+		let StackItem::Map(map) = round_trip(&StackItem::Map(map)) else {
+			panic!("expected a map")
+		};
+		assert_eq!(map.len(), 2);
+		assert_eq!(*map[&StackItem::from(7)].borrow(), StackItem::Boolean(false));
+		assert_eq!(*map[&StackItem::from(b"key".to_vec())].borrow(), StackItem::from(42));
+	}
+
+	#[test]
+	fn test_canonical_map_order() {
+		let mut map = OrderedMap::new();
+		for key in [3, 1, 2] {
+			map.insert(StackItem::from(key), StackItem::Null.into());
+		}
+		let map = StackItem::Map(map);
+		let limits = ExecutionEngineLimits::default();
+		let entries = |keys: [u8; 3]| {
+			let mut bytes = vec![0x48, 0x03];
+			for key in keys {
+				bytes.extend([0x21, 0x01, key, 0x00]);
+			}
+			bytes
+		};
+		assert_eq!(serialize(&map, &limits, false).unwrap(), entries([3, 1, 2]));
+		assert_eq!(serialize(&map, &limits, true).unwrap(), entries([1, 2, 3]));
+	}
+
+	#[test]
+	fn test_circular_reference_rejected() {
+		let array: Rc<RefCell<StackItem>> = StackItem::Array(Vec::new().into()).into();
+		if let StackItem::Array(items) = &mut *array.borrow_mut() {
+			items.push(array.clone());
+		}
+		let limits = ExecutionEngineLimits::default();
+		assert!(serialize(&array.borrow(), &limits, false).is_err());
+		// Break the cycle so the test doesn't leak.
+		if let StackItem::Array(items) = &mut *array.borrow_mut() {
+			items.clear();
+		};
+	}
+
+	#[test]
+	fn test_limits() {
+		let limits = ExecutionEngineLimits { max_item_size: 4, ..Default::default() };
+		assert!(serialize(&StackItem::from(vec![0; 4]), &limits, false).is_err());
+		assert!(deserialize(&[0x28, 0x05, 0, 0, 0, 0, 0], &limits).is_err());
+		assert!(deserialize(&[0x28, 0x02, 0], &limits).is_err());
+		assert!(deserialize(&[0x20, 0x02], &limits).is_err());
+		assert!(deserialize(&[0x60], &limits).is_err());
+	}
+}
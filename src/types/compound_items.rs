@@ -0,0 +1,80 @@
+use crate::{stack_item::StackItem, vm_error::VMError};
+use std::{
+	cell::RefCell,
+	ops::{Deref, DerefMut},
+	rc::Rc,
+};
+
+/// The elements of an `Array` or `Struct`, which can be frozen against modification by the
+/// VM.
+///
+/// The elements are reachable through `Deref` like a `Vec`; the opcodes that add, replace or
+/// remove elements call `check_writable` first.
+#[derive(Clone, Debug, Default)]
+pub struct CompoundItems {
+	items: Vec<Rc<RefCell<StackItem>>>,
+	read_only: bool,
+}
+
+impl CompoundItems {
+	pub fn new(items: Vec<Rc<RefCell<StackItem>>>) -> Self {
+		Self { items, read_only: false }
+	}
+
+	/// Indicates whether the elements are frozen.
+	pub fn is_read_only(&self) -> bool {
+		self.read_only
+	}
+
+	/// Freezes the elements so that the VM can no longer modify them.
+	pub fn set_read_only(&mut self) {
+		self.read_only = true;
+	}
+
+	/// Fails if the elements are frozen.
+	pub fn check_writable(&self) -> Result<(), VMError> {
+		if self.read_only {
+			return Err(VMError::InvalidParameter("The object is read-only.".to_string()))
+		}
+		Ok(())
+	}
+
+	pub fn into_vec(self) -> Vec<Rc<RefCell<StackItem>>> {
+		self.items
+	}
+}
+
+impl Deref for CompoundItems {
+	type Target = Vec<Rc<RefCell<StackItem>>>;
+
+	fn deref(&self) -> &Self::Target {
+		&self.items
+	}
+}
+
+impl DerefMut for CompoundItems {
+	fn deref_mut(&mut self) -> &mut Self::Target {
+		&mut self.items
+	}
+}
+
+impl From<Vec<Rc<RefCell<StackItem>>>> for CompoundItems {
+	fn from(items: Vec<Rc<RefCell<StackItem>>>) -> Self {
+		Self::new(items)
+	}
+}
+
+impl FromIterator<Rc<RefCell<StackItem>>> for CompoundItems {
+	fn from_iter<I: IntoIterator<Item = Rc<RefCell<StackItem>>>>(iter: I) -> Self {
+		Self::new(iter.into_iter().collect())
+	}
+}
+
+impl<'a> IntoIterator for &'a CompoundItems {
+	type Item = &'a Rc<RefCell<StackItem>>;
+	type IntoIter = std::slice::Iter<'a, Rc<RefCell<StackItem>>>;
+
+	fn into_iter(self) -> Self::IntoIter {
+		self.items.iter()
+	}
+}
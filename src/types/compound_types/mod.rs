@@ -1,4 +0,0 @@
-pub mod Struct;
-pub mod array;
-pub mod compound_type;
-pub mod map;
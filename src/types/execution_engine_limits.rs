@@ -1,7 +1,9 @@
-use std::num::NonZeroU32;
+use crate::vm_error::VMError;
+use num_bigint::BigInt;
+use num_traits::Signed;
 
 /// Represents the restrictions on the vm.
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct ExecutionEngineLimits {
 	/// The maximum number of bits that `OpCode::SHL` and `OpCode::SHR` can shift.
 	pub max_shift: usize,
@@ -21,6 +23,9 @@ pub struct ExecutionEngineLimits {
 	/// The maximum nesting depth of `try` blocks.
 	pub max_try_nesting_depth: usize,
 
+	/// The maximum size of a script that can be loaded into the vm, in bytes.
+	pub max_script_size: usize,
+
 	/// Allow catching the ExecutionEngine Exceptions
 	pub catch_engine_exceptions: bool,
 }
@@ -34,25 +39,194 @@ impl Default for ExecutionEngineLimits {
 			max_comparable_size: 65536,
 			max_invocation_stack_size: 1024,
 			max_try_nesting_depth: 16,
+			max_script_size: 1024 * 1024,
 			catch_engine_exceptions: true,
 		}
 	}
 }
 
 impl ExecutionEngineLimits {
+	/// The maximum number of bits of an integer in the vm, sign bit included.
+	pub const MAX_INTEGER_BITS: u64 = 256;
+
+	/// Starts building limits from the defaults.
+	pub fn builder() -> LimitsBuilder {
+		LimitsBuilder::default()
+	}
+
 	/// Assert that the size of the item meets the limit.
 	#[inline]
-	pub fn assert_max_item_size(&self, size: u32) {
-		if size == 0 || size > self.max_item_size as u32 {
-			panic!("MaxItemSize exceeded: {size}");
+	pub fn assert_max_item_size(&self, size: usize) -> Result<(), VMError> {
+		if size > self.max_item_size {
+			return Err(VMError::ItemTooLarge(format!("MaxItemSize exceed: {size}")))
 		}
+		Ok(())
 	}
 
 	/// Assert that the number of bits shifted meets the limit.
 	#[inline]
-	pub fn assert_shift(&self, shift: i32) {
-		if shift > self.max_shift as i32 || shift < 0 {
-			panic!("Invalid shift value: {shift}");
+	pub fn assert_shift(&self, shift: i32) -> Result<(), VMError> {
+		if shift < 0 || shift as usize > self.max_shift {
+			return Err(VMError::InvalidParameter(format!("Invalid shift value: {shift}")))
 		}
+		Ok(())
+	}
+
+	/// Assert that the size of a script meets the limit.
+	#[inline]
+	pub fn assert_max_script_size(&self, size: usize) -> Result<(), VMError> {
+		if size > self.max_script_size {
+			return Err(VMError::InvalidParameter(format!(
+				"MaxScriptSize exceed: {size}/{}",
+				self.max_script_size
+			)))
+		}
+		Ok(())
+	}
+
+	/// Assert that an integer fits in the 256-bit signed range of the vm.
+	#[inline]
+	pub fn assert_integer_limit(&self, value: &BigInt) -> Result<(), VMError> {
+		let bits = get_bit_length(value) + 1;
+		if bits > Self::MAX_INTEGER_BITS {
+			return Err(VMError::ItemTooLarge(format!("MaxIntegerSize exceed: {bits} bits")))
+		}
+		Ok(())
+	}
+}
+
+/// Builds `ExecutionEngineLimits`, starting from the defaults or a network preset.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LimitsBuilder {
+	limits: ExecutionEngineLimits,
+}
+
+impl LimitsBuilder {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// The limits of the NEO N3 main network.
+	pub fn mainnet() -> Self {
+		Self::default()
+	}
+
+	/// The limits of the NEO N3 test network, which match the main network's.
+	pub fn testnet() -> Self {
+		Self::default()
+	}
+
+	pub fn max_stack_size(mut self, max_stack_size: usize) -> Self {
+		self.limits.max_stack_size = max_stack_size;
+		self
+	}
+
+	pub fn max_item_size(mut self, max_item_size: usize) -> Self {
+		self.limits.max_item_size = max_item_size;
+		self
+	}
+
+	pub fn max_invocation_stack_size(mut self, max_invocation_stack_size: usize) -> Self {
+		self.limits.max_invocation_stack_size = max_invocation_stack_size;
+		self
+	}
+
+	pub fn max_try_nesting_depth(mut self, max_try_nesting_depth: usize) -> Self {
+		self.limits.max_try_nesting_depth = max_try_nesting_depth;
+		self
+	}
+
+	pub fn max_comparable_size(mut self, max_comparable_size: usize) -> Self {
+		self.limits.max_comparable_size = max_comparable_size;
+		self
+	}
+
+	pub fn max_shift(mut self, max_shift: usize) -> Self {
+		self.limits.max_shift = max_shift;
+		self
+	}
+
+	/// Returns the limits, failing when `max_item_size` is 0.
+	pub fn build(self) -> Result<ExecutionEngineLimits, VMError> {
+		if self.limits.max_item_size == 0 {
+			return Err(VMError::InvalidParameter("MaxItemSize must be at least 1.".to_string()))
+		}
+		Ok(self.limits)
+	}
+}
+
+/// The number of bits needed to represent `value` in two's complement, excluding the sign bit.
+fn get_bit_length(value: &BigInt) -> u64 {
+	if value.is_negative() {
+		(-value - 1u8).bits()
+	} else {
+		value.bits()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use num_traits::One;
+
+	#[test]
+	fn test_assert_integer_limit() {
+		let limits = ExecutionEngineLimits::default();
+		let max = (BigInt::one() << 255) - 1;
+		let min = -(BigInt::one() << 255);
+		assert!(limits.assert_integer_limit(&max).is_ok());
+		assert!(limits.assert_integer_limit(&min).is_ok());
+		assert!(limits.assert_integer_limit(&(&max + 1)).is_err());
+		assert!(limits.assert_integer_limit(&(&min - 1)).is_err());
+	}
+
+	#[test]
+	fn test_assert_shift_bounds() {
+		let limits = ExecutionEngineLimits::default();
+		assert!(limits.assert_shift(-1).is_err());
+		assert!(limits.assert_shift(0).is_ok());
+		assert!(limits.assert_shift(256).is_ok());
+		assert!(matches!(limits.assert_shift(257), Err(VMError::InvalidParameter(_))));
+	}
+
+	#[test]
+	fn test_assert_max_item_size_bounds() {
+		let limits = ExecutionEngineLimits::default();
+		assert!(limits.assert_max_item_size(0).is_ok());
+		assert!(limits.assert_max_item_size(limits.max_item_size).is_ok());
+		assert!(matches!(
+			limits.assert_max_item_size(limits.max_item_size + 1),
+			Err(VMError::ItemTooLarge(_))
+		));
+	}
+
+	#[test]
+	fn test_builder() {
+		let limits = ExecutionEngineLimits::builder()
+			.max_stack_size(16)
+			.max_item_size(1024)
+			.max_invocation_stack_size(8)
+			.max_try_nesting_depth(2)
+			.max_comparable_size(32)
+			.max_shift(64)
+			.build()
+			.unwrap();
+		assert_eq!(limits.max_stack_size, 16);
+		assert_eq!(limits.max_item_size, 1024);
+		assert_eq!(limits.max_invocation_stack_size, 8);
+		assert_eq!(limits.max_try_nesting_depth, 2);
+		assert_eq!(limits.max_comparable_size, 32);
+		assert_eq!(limits.max_shift, 64);
+		assert_eq!(limits.max_script_size, ExecutionEngineLimits::default().max_script_size);
+
+		let engine = crate::execution_engine::ExecutionEngine::new_with_limits(limits);
+		assert_eq!(engine.limits, limits);
+
+		assert_eq!(LimitsBuilder::mainnet().build().unwrap(), ExecutionEngineLimits::default());
+		assert_eq!(LimitsBuilder::testnet().build().unwrap(), ExecutionEngineLimits::default());
+		assert!(matches!(
+			LimitsBuilder::new().max_item_size(0).build(),
+			Err(VMError::InvalidParameter(_))
+		));
 	}
 }
@@ -0,0 +1,443 @@
+//! The JSON representation of stack items.
+//!
+//! `Null` maps to `null`, `Boolean` to a JSON boolean, `Integer` to a string of its decimal
+//! digits, `ByteString` and `Buffer` to a base64 string, `Array` and `Struct` to a JSON array
+//! and `Map` to a JSON object whose property names are the UTF-8 contents of its `ByteString`
+//! keys, in the map's insertion order.
+//!
+//! Deserialization maps JSON strings holding an integer in the form `to_json` writes back to
+//! `Integer`, other JSON strings to `ByteString` and JSON arrays to `Array`. A base64 string of
+//! digits only, such as `"1234"`, therefore reads back as an integer. JSON numbers, as written by
+//! neo-node, are read as integers too when they lie within ±(2^53 - 1), the range a JSON number
+//! holds exactly.
+
+use crate::{
+	ordered_map::OrderedMap,
+	stack_item::StackItem,
+	utility::{base64_decode, base64_encode},
+	vm_error::VMError,
+};
+use num_bigint::BigInt;
+use num_traits::ToPrimitive;
+use std::{cell::RefCell, fmt::Write, rc::Rc};
+
+/// The maximum nesting depth of arrays and objects accepted by `from_json`.
+const MAX_DEPTH: usize = 64;
+
+/// The largest magnitude of an integer that can be read from a JSON number.
+const MAX_SAFE_INTEGER: i64 = (1 << 53) - 1;
+
+fn is_safe_integer(value: &BigInt) -> bool {
+	value.to_i64().is_some_and(|value| value.abs() <= MAX_SAFE_INTEGER)
+}
+
+/// Reads `value` as an integer if it is the decimal string `to_json` writes for one.
+fn parse_decimal(value: &str) -> Option<BigInt> {
+	let digits = value.strip_prefix('-').unwrap_or(value);
+	if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+		return None
+	}
+	let integer: BigInt = value.parse().ok()?;
+	let canonical = integer.to_string() == value;
+	(canonical && integer.to_signed_bytes_le().len() <= StackItem::MAX_INTEGER_SIZE)
+		.then_some(integer)
+}
+
+/// Serializes `item` to a JSON string.
+pub fn to_json(item: &StackItem) -> Result<String, VMError> {
+	let mut writer = String::new();
+	let mut serialized = Vec::new();
+	write_item(item, &mut writer, &mut serialized)?;
+	Ok(writer)
+}
+
+/// Deserializes a stack item from a JSON string.
+pub fn from_json(s: &str) -> Result<Rc<RefCell<StackItem>>, VMError> {
+	let mut parser = Parser { bytes: s.as_bytes(), position: 0 };
+	let item = parser.parse_value(0)?;
+	parser.skip_whitespace();
+	if parser.position != parser.bytes.len() {
+		return Err(parser.error("Unexpected trailing characters"))
+	}
+	Ok(item)
+}
+
+fn write_item(
+	item: &StackItem,
+	writer: &mut String,
+	serialized: &mut Vec<*const StackItem>,
+) -> Result<(), VMError> {
+	match item {
+		StackItem::Null => writer.push_str("null"),
+		StackItem::Boolean(value) => writer.push_str(if *value { "true" } else { "false" }),
+		StackItem::Integer(value) => write_string(writer, &value.to_string()),
+		StackItem::ByteString(bytes) | StackItem::Buffer(bytes) =>
+			write_string(writer, &base64_encode(bytes)),
+		StackItem::Array(items) | StackItem::Struct(items) => {
+			enter(item, serialized)?;
+			writer.push('[');
+			for (index, child) in items.iter().enumerate() {
+				if index > 0 {
+					writer.push(',');
+				}
+				write_child(child, writer, serialized)?;
+			}
+			writer.push(']');
+			serialized.pop();
+		},
+		StackItem::Map(map) => {
+			enter(item, serialized)?;
+			writer.push('{');
+			for (index, (key, value)) in map.iter().enumerate() {
+				let StackItem::ByteString(bytes) = key else {
+					return Err(VMError::InvalidType(format!(
+						"{:?} map keys can't be serialized to JSON.",
+						key.get_type()
+					)))
+				};
+				let name = std::str::from_utf8(bytes).map_err(|_| {
+					VMError::InvalidType("Map keys must be valid UTF-8 strings.".to_string())
+				})?;
+				if index > 0 {
+					writer.push(',');
+				}
+				write_string(writer, name);
+				writer.push(':');
+				write_child(value, writer, serialized)?;
+			}
+			writer.push('}');
+			serialized.pop();
+		},
+		StackItem::InteropInterface(_) | StackItem::Pointer { .. } =>
+			return Err(VMError::InvalidType(format!(
+				"{:?} can't be serialized to JSON.",
+				item.get_type()
+			))),
+	}
+	Ok(())
+}
+
+fn write_child(
+	child: &Rc<RefCell<StackItem>>,
+	writer: &mut String,
+	serialized: &mut Vec<*const StackItem>,
+) -> Result<(), VMError> {
+	write_item(&child.borrow(), writer, serialized)
+}
+
+/// Records that the compound `item` is being serialized, failing on circular references.
+fn enter(item: &StackItem, serialized: &mut Vec<*const StackItem>) -> Result<(), VMError> {
+	let pointer = item as *const StackItem;
+	if serialized.contains(&pointer) {
+		return Err(VMError::InvalidType("Circular references can't be serialized.".to_string()))
+	}
+	serialized.push(pointer);
+	Ok(())
+}
+
+fn write_string(writer: &mut String, value: &str) {
+	writer.push('"');
+	for c in value.chars() {
+		match c {
+			'"' => writer.push_str("\\\""),
+			'\\' => writer.push_str("\\\\"),
+			'\n' => writer.push_str("\\n"),
+			'\r' => writer.push_str("\\r"),
+			'\t' => writer.push_str("\\t"),
+			c if (c as u32) < 0x20 => write!(writer, "\\u{:04x}", c as u32).unwrap(),
+			c => writer.push(c),
+		}
+	}
+	writer.push('"');
+}
+
+/// A recursive-descent parser for the subset of JSON produced by `to_json`.
+struct Parser<'a> {
+	bytes: &'a [u8],
+	position: usize,
+}
+
+impl Parser<'_> {
+	fn error(&self, message: &str) -> VMError {
+		VMError::InvalidParameter(format!("{message} at position {} of the JSON", self.position))
+	}
+
+	fn skip_whitespace(&mut self) {
+		while self.bytes.get(self.position).is_some_and(|c| c.is_ascii_whitespace()) {
+			self.position += 1;
+		}
+	}
+
+	fn peek(&mut self) -> Option<u8> {
+		self.skip_whitespace();
+		self.bytes.get(self.position).copied()
+	}
+
+	fn expect(&mut self, c: u8) -> Result<(), VMError> {
+		if self.peek() != Some(c) {
+			return Err(self.error(&format!("Expected '{}'", c as char)))
+		}
+		self.position += 1;
+		Ok(())
+	}
+
+	fn parse_literal(&mut self, literal: &str) -> Result<(), VMError> {
+		if !self.bytes[self.position..].starts_with(literal.as_bytes()) {
+			return Err(self.error("Invalid literal"))
+		}
+		self.position += literal.len();
+		Ok(())
+	}
+
+	fn parse_value(&mut self, depth: usize) -> Result<Rc<RefCell<StackItem>>, VMError> {
+		let item = match self.peek() {
+			Some(b'n') => {
+				self.parse_literal("null")?;
+				StackItem::Null
+			},
+			Some(b't') => {
+				self.parse_literal("true")?;
+				StackItem::Boolean(true)
+			},
+			Some(b'f') => {
+				self.parse_literal("false")?;
+				StackItem::Boolean(false)
+			},
+			Some(b'"') => {
+				let value = self.parse_string()?;
+				match parse_decimal(&value) {
+					Some(integer) => StackItem::Integer(integer),
+					None => StackItem::ByteString(
+						base64_decode(&value).map_err(|_| self.error("Invalid base64 string"))?,
+					),
+				}
+			},
+			Some(b'-' | b'0'..=b'9') => StackItem::Integer(self.parse_integer()?),
+			Some(b'[') => {
+				let depth = self.nest(depth)?;
+				self.position += 1;
+				let mut items = Vec::new();
+				if self.peek() == Some(b']') {
+					self.position += 1;
+				} else {
+					loop {
+						items.push(self.parse_value(depth)?);
+						match self.peek() {
+							Some(b',') => self.position += 1,
+							Some(b']') => {
+								self.position += 1;
+								break
+							},
+							_ => return Err(self.error("Expected ',' or ']'")),
+						}
+					}
+				}
+				StackItem::Array(items.into())
+			},
+			Some(b'{') => {
+				let depth = self.nest(depth)?;
+				self.position += 1;
+				let mut map = OrderedMap::new();
+				if self.peek() == Some(b'}') {
+					self.position += 1;
+				} else {
+					loop {
+						if self.peek() != Some(b'"') {
+							return Err(self.error("Expected a property name"))
+						}
+						let name = self.parse_string()?;
+						self.expect(b':')?;
+						let value = self.parse_value(depth)?;
+						map.insert(StackItem::ByteString(name.into_bytes()), value);
+						match self.peek() {
+							Some(b',') => self.position += 1,
+							Some(b'}') => {
+								self.position += 1;
+								break
+							},
+							_ => return Err(self.error("Expected ',' or '}'")),
+						}
+					}
+				}
+				StackItem::Map(map)
+			},
+			_ => return Err(self.error("Unexpected character")),
+		};
+		Ok(item.into())
+	}
+
+	fn nest(&self, depth: usize) -> Result<usize, VMError> {
+		if depth >= MAX_DEPTH {
+			return Err(self.error("Maximum nesting depth exceeded"))
+		}
+		Ok(depth + 1)
+	}
+
+	fn parse_integer(&mut self) -> Result<BigInt, VMError> {
+		let start = self.position;
+		if self.bytes[self.position] == b'-' {
+			self.position += 1;
+		}
+		while self.bytes.get(self.position).is_some_and(u8::is_ascii_digit) {
+			self.position += 1;
+		}
+		if matches!(self.bytes.get(self.position), Some(b'.' | b'e' | b'E')) {
+			return Err(self.error("Only integer numbers are supported"))
+		}
+		let digits = std::str::from_utf8(&self.bytes[start..self.position]).unwrap();
+		let value: BigInt = digits.parse().map_err(|_| self.error("Invalid number"))?;
+		if !is_safe_integer(&value) {
+			return Err(self.error("Integer out of range"))
+		}
+		Ok(value)
+	}
+
+	fn parse_string(&mut self) -> Result<String, VMError> {
+		self.position += 1;
+		let mut value = String::new();
+		loop {
+			let Some(&c) = self.bytes.get(self.position) else {
+				return Err(self.error("Unterminated string"))
+			};
+			self.position += 1;
+			match c {
+				b'"' => return Ok(value),
+				b'\\' => {
+					let Some(&escape) = self.bytes.get(self.position) else {
+						return Err(self.error("Unterminated string"))
+					};
+					self.position += 1;
+					match escape {
+						b'"' => value.push('"'),
+						b'\\' => value.push('\\'),
+						b'/' => value.push('/'),
+						b'b' => value.push('\u{8}'),
+						b'f' => value.push('\u{c}'),
+						b'n' => value.push('\n'),
+						b'r' => value.push('\r'),
+						b't' => value.push('\t'),
+						b'u' => {
+							let mut code = self.parse_hex4()?;
+							if (0xD800..0xDC00).contains(&code) {
+								self.parse_literal("\\u")?;
+								let low = self.parse_hex4()?;
+								if !(0xDC00..0xE000).contains(&low) {
+									return Err(self.error("Invalid surrogate pair"))
+								}
+								code = 0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00);
+							}
+							value.push(
+								char::from_u32(code).ok_or_else(|| self.error("Invalid escape"))?,
+							);
+						},
+						_ => return Err(self.error("Invalid escape")),
+					}
+				},
+				c if c < 0x20 => return Err(self.error("Control character in string")),
+				_ => {
+					// Copy the whole UTF-8 sequence starting at `c`.
+					let start = self.position - 1;
+					let mut end = self.position;
+					while self.bytes.get(end).is_some_and(|b| b & 0xC0 == 0x80) {
+						end += 1;
+					}
+					let s = std::str::from_utf8(&self.bytes[start..end])
+						.map_err(|_| self.error("Invalid UTF-8"))?;
+					value.push_str(s);
+					self.position = end;
+				},
+			}
+		}
+	}
+
+	fn parse_hex4(&mut self) -> Result<u32, VMError> {
+		let digits = self
+			.bytes
+			.get(self.position..self.position + 4)
+			.and_then(|digits| std::str::from_utf8(digits).ok())
+			.and_then(|digits| u32::from_str_radix(digits, 16).ok())
+			.ok_or_else(|| self.error("Invalid unicode escape"))?;
+		self.position += 4;
+		Ok(digits)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_to_json() {
+		let mut map = OrderedMap::new();
+		map.insert(
+			StackItem::from(b"b".to_vec()),
+			StackItem::Array(
+				vec![
+					StackItem::from(-123).into(),
+					StackItem::Boolean(true).into(),
+					StackItem::Null.into(),
+				]
+				.into(),
+			)
+			.into(),
+		);
+		map.insert(StackItem::from(b"a".to_vec()), StackItem::from(b"hello".to_vec()).into());
+		assert_eq!(
+			to_json(&StackItem::Map(map)).unwrap(),
+			r#"{"b":["-123",true,null],"a":"aGVsbG8="}"#
+		);
+		assert_eq!(to_json(&StackItem::Buffer(vec![1, 2])).unwrap(), r#""AQI=""#);
+	}
+
+	#[test]
+	fn test_invalid_items() {
+		let mut map = OrderedMap::new();
+		map.insert(StackItem::from(1), StackItem::Null.into());
+		assert!(to_json(&StackItem::Map(map)).is_err());
+		assert!(to_json(&StackItem::InteropInterface(Rc::new(0))).is_err());
+	}
+
+	#[test]
+	fn test_round_trip() {
+		let json = r#"{"key":["AAE=","12345678901234567890",false,{}],"x":null}"#;
+		let item = from_json(json).unwrap();
+		assert_eq!(to_json(&item.borrow()).unwrap(), json);
+
+		let item = from_json(" [ \"\" , -1 ] ").unwrap();
+		let StackItem::Array(items) = &*item.borrow() else { panic!("expected an array") };
+		assert_eq!(*items[0].borrow(), StackItem::ByteString(vec![]));
+		assert_eq!(*items[1].borrow(), StackItem::from(-1));
+	}
+
+	#[test]
+	fn test_integers() {
+		let large = BigInt::from(2).pow(200);
+		let json = to_json(&StackItem::Integer(large.clone())).unwrap();
+		assert_eq!(json, format!("\"{large}\""));
+		assert_eq!(*from_json(&json).unwrap().borrow(), StackItem::Integer(large));
+
+		// Only the decimal form `to_json` writes is read as an integer; "0123" is base64.
+		assert_eq!(*from_json(r#""1234""#).unwrap().borrow(), StackItem::from(1234));
+		assert_eq!(
+			*from_json(r#""0123""#).unwrap().borrow(),
+			StackItem::ByteString(base64_decode("0123").unwrap())
+		);
+
+		// JSON numbers are read within ±(2^53 - 1).
+		let max = MAX_SAFE_INTEGER;
+		for value in [-max, max] {
+			assert_eq!(*from_json(&value.to_string()).unwrap().borrow(), StackItem::from(value));
+		}
+		for value in [-max - 1, max + 1] {
+			assert!(from_json(&value.to_string()).is_err(), "{value}");
+		}
+	}
+
+	#[test]
+	fn test_from_json_errors() {
+		for json in ["", "[1,]", "1.5", "\"abc\"", "{1:2}", "[1] x", "tru", "\"\\x\""] {
+			assert!(from_json(json).is_err(), "{json}");
+		}
+		assert!(from_json(&"[".repeat(MAX_DEPTH + 1)).is_err());
+	}
+}
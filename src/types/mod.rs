@@ -1,18 +1,10 @@
+pub mod binary_serializer;
+pub mod compound_items;
 pub mod execution_engine_limits;
-pub mod interop_interface;
-pub mod reference_counter;
+pub mod json_serializer;
+pub mod ordered_map;
 pub mod stack_item;
 pub mod stack_item_type;
-pub mod tarjan;
-
-pub mod buffer;
-
-pub mod null;
-
-pub mod pointer;
-
-pub mod compound_types;
-pub mod primitive_types;
 
 pub fn add(left: usize, right: usize) -> usize {
 	left + right
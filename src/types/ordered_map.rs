@@ -0,0 +1,184 @@
+use std::{
+	borrow::Borrow,
+	collections::HashMap,
+	hash::Hash,
+	ops::{Index, IndexMut},
+};
+
+/// A hash map that iterates over its entries in insertion order.
+///
+/// Replacing the value of an existing key keeps the key at its original position, and removing
+/// a key preserves the order of the remaining entries, so iteration is deterministic.
+#[derive(Clone, Debug)]
+pub struct OrderedMap<K, V> {
+	entries: Vec<(K, V)>,
+	indexes: HashMap<K, usize>,
+}
+
+impl<K, V> Default for OrderedMap<K, V> {
+	fn default() -> Self {
+		Self { entries: Vec::new(), indexes: HashMap::new() }
+	}
+}
+
+impl<K: Hash + Eq + Clone, V> OrderedMap<K, V> {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn with_capacity(capacity: usize) -> Self {
+		Self { entries: Vec::with_capacity(capacity), indexes: HashMap::with_capacity(capacity) }
+	}
+
+	pub fn len(&self) -> usize {
+		self.entries.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.entries.is_empty()
+	}
+
+	pub fn contains_key<Q: Hash + Eq + ?Sized>(&self, key: &Q) -> bool
+	where
+		K: Borrow<Q>,
+	{
+		self.indexes.contains_key(key)
+	}
+
+	pub fn get<Q: Hash + Eq + ?Sized>(&self, key: &Q) -> Option<&V>
+	where
+		K: Borrow<Q>,
+	{
+		self.indexes.get(key).map(|&index| &self.entries[index].1)
+	}
+
+	pub fn get_mut<Q: Hash + Eq + ?Sized>(&mut self, key: &Q) -> Option<&mut V>
+	where
+		K: Borrow<Q>,
+	{
+		self.indexes.get(key).map(|&index| &mut self.entries[index].1)
+	}
+
+	/// Inserts `value` under `key`, returning the value it replaces. A new key is appended
+	/// after the existing ones; a replaced key keeps its position.
+	pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+		match self.indexes.get(&key) {
+			Some(&index) => Some(std::mem::replace(&mut self.entries[index].1, value)),
+			None => {
+				self.indexes.insert(key.clone(), self.entries.len());
+				self.entries.push((key, value));
+				None
+			},
+		}
+	}
+
+	/// Removes `key`, returning its value. The remaining entries keep their order.
+	pub fn remove<Q: Hash + Eq + ?Sized>(&mut self, key: &Q) -> Option<V>
+	where
+		K: Borrow<Q>,
+	{
+		let index = self.indexes.remove(key)?;
+		let (_, value) = self.entries.remove(index);
+		for (key, _) in &self.entries[index..] {
+			*self.indexes.get_mut::<K>(key).unwrap() -= 1;
+		}
+		Some(value)
+	}
+
+	pub fn clear(&mut self) {
+		self.entries.clear();
+		self.indexes.clear();
+	}
+
+	/// Iterates over the entries in insertion order.
+	pub fn iter(&self) -> Iter<'_, K, V> {
+		self.into_iter()
+	}
+
+	/// Iterates over the keys in insertion order.
+	pub fn keys(&self) -> impl DoubleEndedIterator<Item = &K> + ExactSizeIterator {
+		self.entries.iter().map(|(key, _)| key)
+	}
+
+	/// Iterates over the values in insertion order.
+	pub fn values(&self) -> impl DoubleEndedIterator<Item = &V> + ExactSizeIterator {
+		self.entries.iter().map(|(_, value)| value)
+	}
+}
+
+impl<K: Hash + Eq + Borrow<Q>, Q: Hash + Eq + ?Sized, V> Index<&Q> for OrderedMap<K, V> {
+	type Output = V;
+
+	fn index(&self, key: &Q) -> &V {
+		let index = self.indexes.get(key).expect("key not found in map");
+		&self.entries[*index].1
+	}
+}
+
+impl<K: Hash + Eq + Borrow<Q>, Q: Hash + Eq + ?Sized, V> IndexMut<&Q> for OrderedMap<K, V> {
+	fn index_mut(&mut self, key: &Q) -> &mut V {
+		let index = self.indexes.get(key).expect("key not found in map");
+		&mut self.entries[*index].1
+	}
+}
+
+impl<K: Hash + Eq + Clone, V> FromIterator<(K, V)> for OrderedMap<K, V> {
+	fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+		let mut map = Self::new();
+		for (key, value) in iter {
+			map.insert(key, value);
+		}
+		map
+	}
+}
+
+/// An iterator over the entries of an `OrderedMap`, in insertion order.
+pub type Iter<'a, K, V> =
+	std::iter::Map<std::slice::Iter<'a, (K, V)>, fn(&'a (K, V)) -> (&'a K, &'a V)>;
+
+impl<'a, K, V> IntoIterator for &'a OrderedMap<K, V> {
+	type Item = (&'a K, &'a V);
+	type IntoIter = Iter<'a, K, V>;
+
+	fn into_iter(self) -> Self::IntoIter {
+		self.entries.iter().map(|(key, value)| (key, value))
+	}
+}
+
+impl<K, V> IntoIterator for OrderedMap<K, V> {
+	type Item = (K, V);
+	type IntoIter = std::vec::IntoIter<(K, V)>;
+
+	fn into_iter(self) -> Self::IntoIter {
+		self.entries.into_iter()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_insertion_order() {
+		let mut map = OrderedMap::new();
+		for key in [5, 1, 4, 2, 3] {
+			assert_eq!(map.insert(key, key * 10), None);
+		}
+		assert_eq!(map.insert(4, 0), Some(40));
+		assert_eq!(map.keys().copied().collect::<Vec<_>>(), [5, 1, 4, 2, 3]);
+		assert_eq!(map.values().copied().collect::<Vec<_>>(), [50, 10, 0, 20, 30]);
+
+		assert_eq!(map.remove(&1), Some(10));
+		assert_eq!(map.remove(&1), None);
+		assert_eq!(map.keys().copied().collect::<Vec<_>>(), [5, 4, 2, 3]);
+		assert_eq!(map[&2], 20);
+		assert_eq!(map.get(&3), Some(&30));
+		assert!(!map.contains_key(&1));
+
+		map.insert(1, 11);
+		assert_eq!(
+			map.into_iter().collect::<Vec<_>>(),
+			[(5, 50), (4, 0), (2, 20), (3, 30), (1, 11)]
+		);
+	}
+}
@@ -1,4 +0,0 @@
-pub mod boolean;
-pub mod byte_string;
-pub mod integer;
-pub mod primitive_type;
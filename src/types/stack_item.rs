@@ -1,130 +1,557 @@
 use crate::{
-	stack_item_type::StackItemType,
+	compound_items::CompoundItems, execution_engine_limits::ExecutionEngineLimits,
+	ordered_map::OrderedMap, stack_item_type::StackItemType, vm::script::Script, vm_error::VMError,
 };
+use num_bigint::BigInt;
+use num_traits::Zero;
 use std::{
+	any::Any,
 	cell::RefCell,
-	fmt::{Debug},
+	collections::HashMap,
 	hash::{Hash, Hasher},
 	rc::Rc,
-	string::FromUtf8Error,
 };
-use std::any::Any;
-use std::collections::HashMap;
-use num_bigint::BigInt;
-use serde::{Deserialize, Serialize};
-use crate::execution_engine_limits::ExecutionEngineLimits;
-use crate::interop_interface::InteropInterface;
-use crate::null::Null;
 
-pub trait StackItem: Clone + Hash + Eq+PartialEq+Serialize+Deserialize {
-	const TRUE: Self;
-	const FALSE: Self;
-	const NULL: Self;
+/// The base of all the items in the VM.
+#[derive(Clone, Debug)]
+pub enum StackItem {
+	/// Represents `null` in the VM.
+	Null,
+
+	/// Represents a boolean (`true` or `false`) value in the VM.
+	Boolean(bool),
+
+	/// Represents an integer value in the VM.
+	Integer(BigInt),
+
+	/// Represents an immutable memory block in the VM.
+	ByteString(Vec<u8>),
 
-	fn dfn(&self) -> isize;
+	/// Represents a memory block that can be used for reading and writing in the VM.
+	Buffer(Vec<u8>),
 
-	fn set_dfn(&mut self, dfn: isize);
+	/// Represents an array or a complex object in the VM.
+	Array(CompoundItems),
 
-	fn low_link(&self) -> usize;
-	fn set_low_link(&mut self, link: usize);
+	/// Represents a structure in the VM.
+	Struct(CompoundItems),
 
-	fn on_stack(&self) -> bool;
-	fn set_on_stack(&mut self, on_stack: bool);
+	/// Represents a collection of key-value pairs in the VM.
+	Map(OrderedMap<StackItem, Rc<RefCell<StackItem>>>),
 
-	fn set_object_references(&mut self, refs: Self::ObjectReferences);
-	fn object_references(&self) -> &Self::ObjectReferences;
+	/// Represents an interface used to interoperate with the outside of the VM.
+	InteropInterface(Rc<dyn Any>),
 
-	fn set_stack_references(&mut self, count: usize);
+	/// Represents the instruction at `position` in `script`.
+	Pointer { script: Rc<RefCell<Script>>, position: usize },
+}
+
+/// The text encodings `StackItem::get_string_with` can decode.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StringEncoding {
+	/// Strict UTF-8, failing on invalid sequences.
+	Utf8,
 
-	fn stack_references(&self) -> usize;
+	/// ISO-8859-1, mapping every byte to the character with the same code point.
+	Latin1,
+}
 
-	fn successors(&self) -> Vec<dyn StackItem> {
-		self.object_references()
-			.borrow()
-			.as_ref()
-			.unwrap()
-			.values()
-			.map(|v| v.item())
-			.collect()
+impl StackItem {
+	/// The maximum size of an integer in bytes.
+	pub const MAX_INTEGER_SIZE: usize = 32;
+
+	/// The maximum size of a map key in bytes.
+	pub const MAX_KEY_SIZE: usize = 64;
+
+	/// The type of this item.
+	pub fn get_type(&self) -> StackItemType {
+		match self {
+			Self::Null => StackItemType::Any,
+			Self::Boolean(_) => StackItemType::Boolean,
+			Self::Integer(_) => StackItemType::Integer,
+			Self::ByteString(_) => StackItemType::ByteString,
+			Self::Buffer(_) => StackItemType::Buffer,
+			Self::Array(_) => StackItemType::Array,
+			Self::Struct(_) => StackItemType::Struct,
+			Self::Map(_) => StackItemType::Map,
+			Self::InteropInterface(_) => StackItemType::InteropInterface,
+			Self::Pointer { .. } => StackItemType::Pointer,
+		}
 	}
 
-	fn reset(&mut self) {
-		self.set_dfn(-1);
-		self.set_low_link(0);
-		self.set_on_stack(false);
+	/// Indicates whether the item is `StackItem::Null`.
+	pub fn is_null(&self) -> bool {
+		matches!(self, Self::Null)
 	}
 
-	fn is_null(&self) -> bool {
-		false
+	/// Indicates whether the item is a `Boolean`, `Integer` or `ByteString`.
+	pub fn is_primitive(&self) -> bool {
+		matches!(self, Self::Boolean(_) | Self::Integer(_) | Self::ByteString(_))
 	}
 
-	fn cleanup(&mut self);
+	/// Indicates whether the item is an `Array` or `Struct` frozen against modification.
+	pub fn is_read_only(&self) -> bool {
+		match self {
+			Self::Array(items) | Self::Struct(items) => items.is_read_only(),
+			_ => false,
+		}
+	}
 
-	fn convert_to(&self, type_: StackItemType) -> Result<Self, Err> {
-		if type_ == self.get_type() {
-			Ok(self.to_owned())
-		} else if type_ == StackItemType::Boolean {
-			Ok(self.get_boolean())
-		} else {
-			Err(())
+	/// Checks that the item can be used as a map key: it must be primitive and at most
+	/// `MAX_KEY_SIZE` bytes long.
+	pub fn check_map_key(&self) -> Result<(), VMError> {
+		if !self.is_primitive() {
+			return Err(VMError::InvalidType(format!("Invalid key type: {:?}", self.get_type())))
+		}
+		let size = self.size()?;
+		if size > Self::MAX_KEY_SIZE {
+			return Err(VMError::InvalidParameter(format!(
+				"MaxKeySize exceed: {size}/{}",
+				Self::MAX_KEY_SIZE
+			)))
 		}
+		Ok(())
 	}
 
-	fn get_slice(&self) -> &[u8];
+	/// Converts the item to a boolean value.
+	pub fn get_boolean(&self) -> Result<bool, VMError> {
+		match self {
+			Self::Null => Ok(false),
+			Self::Boolean(value) => Ok(*value),
+			Self::Integer(value) => Ok(!value.is_zero()),
+			Self::ByteString(bytes) => {
+				if bytes.len() > Self::MAX_INTEGER_SIZE {
+					return Err(VMError::InvalidType(format!(
+						"Can't convert a ByteString of size {} to Boolean",
+						bytes.len()
+					)))
+				}
+				Ok(bytes.iter().any(|&b| b != 0))
+			},
+			_ => Ok(true),
+		}
+	}
 
-	fn get_string(&self) -> Result<String, FromUtf8Error> {
-		String::from_utf8(self.get_slice().to_vec())
+	/// Converts the item to an integer.
+	pub fn get_integer(&self) -> Result<BigInt, VMError> {
+		match self {
+			Self::Boolean(value) => Ok(BigInt::from(*value as u8)),
+			Self::Integer(value) => Ok(value.clone()),
+			Self::ByteString(bytes) | Self::Buffer(bytes) => {
+				if bytes.len() > Self::MAX_INTEGER_SIZE {
+					return Err(VMError::InvalidType(format!("MaxSize exceed: {}", bytes.len())))
+				}
+				Ok(BigInt::from_signed_bytes_le(bytes))
+			},
+			_ =>
+				Err(VMError::InvalidType(format!("Can't convert {:?} to Integer", self.get_type()))),
+		}
 	}
 
-	fn get_hash_code(&self) -> u64 {
-		use std::hash::Hasher;
-		let mut hasher = std::collections::hash_map::DefaultHasher::new();
-		self.hash(&mut hasher);
-		hasher.finish()
+	/// Gets the memory represented by a primitive item or a buffer.
+	pub fn get_span(&self) -> Result<Vec<u8>, VMError> {
+		match self {
+			Self::Boolean(value) => Ok(vec![*value as u8]),
+			Self::Integer(value) =>
+				Ok(if value.is_zero() { Vec::new() } else { value.to_signed_bytes_le() }),
+			Self::ByteString(bytes) | Self::Buffer(bytes) => Ok(bytes.clone()),
+			_ => Err(VMError::InvalidType(format!("{:?} has no span", self.get_type()))),
+		}
 	}
 
-	fn get_type(&self) -> StackItemType;
+	/// Gets a copy of the memory of a `Buffer`.
+	pub fn get_buffer(&self) -> Result<Vec<u8>, VMError> {
+		match self {
+			Self::Buffer(bytes) => Ok(bytes.clone()),
+			_ =>
+				Err(VMError::InvalidType(format!("Expected a Buffer, found {:?}", self.get_type()))),
+		}
+	}
 
-	fn get_boolean(&self) -> bool;
+	/// Gets a copy of the memory of a `Buffer` or a `ByteString`.
+	pub fn get_buffer_or_byte_string(&self) -> Result<Vec<u8>, VMError> {
+		match self {
+			Self::Buffer(bytes) | Self::ByteString(bytes) => Ok(bytes.clone()),
+			_ => Err(VMError::InvalidType(format!(
+				"Expected a Buffer or a ByteString, found {:?}",
+				self.get_type()
+			))),
+		}
+	}
 
+	/// Converts the item to a UTF-8 string, if its memory holds one.
+	pub fn get_string(&self) -> Option<String> {
+		self.get_string_with(StringEncoding::Utf8)
+	}
 
-	fn deep_copy(&self, asImmutable:bool) -> Box<dyn StackItem>;
+	/// Decodes the memory of the item as a string in `encoding`.
+	pub fn get_string_with(&self, encoding: StringEncoding) -> Option<String> {
+		let span = self.get_span().ok()?;
+		match encoding {
+			StringEncoding::Utf8 => String::from_utf8(span).ok(),
+			StringEncoding::Latin1 => Some(span.into_iter().map(char::from).collect()),
+		}
+	}
 
-	fn deep_copy_with_ref_map(&self, ref_map: &HashMap<&dyn StackItem, &dyn StackItem>, asImmutable:bool) -> Box<dyn StackItem>;
+	/// Gets the size of a primitive item or a buffer in bytes.
+	pub fn size(&self) -> Result<usize, VMError> {
+		match self {
+			Self::Boolean(_) => Ok(1),
+			_ => Ok(self.get_span()?.len()),
+		}
+	}
 
-	fn equals(&self, other: &dyn StackItem) -> bool;
+	/// Converts the item to another type.
+	///
+	/// Converting an `Integer` to `Boolean` yields whether it is non-zero and converting a
+	/// `Boolean` to `Integer` yields 0 or 1, so an integer other than 0 or 1 doesn't survive a
+	/// round trip through `Boolean`: `Integer(5)` becomes `Boolean(true)` and then `Integer(1)`.
+	///
+	/// Converting between `Array` and `Struct` yields a new item holding the same element
+	/// references; `OpCode::Convert` copies the nested structs of a converted `Struct`.
+	pub fn convert_to(&self, ty: StackItemType) -> Result<StackItem, VMError> {
+		if self.is_null() {
+			if ty == StackItemType::Any {
+				return Err(VMError::InvalidType(format!("Type can't be converted to {ty:?}")))
+			}
+			return Ok(Self::Null)
+		}
+		if ty == self.get_type() {
+			return Ok(self.clone())
+		}
+		match (self, ty) {
+			(_, StackItemType::Boolean) => Ok(Self::Boolean(self.get_boolean()?)),
+			(
+				Self::Boolean(_) | Self::Integer(_) | Self::ByteString(_) | Self::Buffer(_),
+				StackItemType::Integer,
+			) => Ok(Self::Integer(self.get_integer()?)),
+			(
+				Self::Boolean(_) | Self::Integer(_) | Self::ByteString(_) | Self::Buffer(_),
+				StackItemType::ByteString,
+			) => Ok(Self::ByteString(self.get_span()?)),
+			(Self::Boolean(_) | Self::Integer(_) | Self::ByteString(_), StackItemType::Buffer) =>
+				Ok(Self::Buffer(self.get_span()?)),
+			(Self::Array(items), StackItemType::Struct) => Ok(Self::Struct(items.to_vec().into())),
+			(Self::Struct(items), StackItemType::Array) => Ok(Self::Array(items.to_vec().into())),
+			_ =>
+				Err(VMError::InvalidType(format!("Can't convert {:?} to {ty:?}", self.get_type()))),
+		}
+	}
 
-	fn equals_with_limits(&self, other: &dyn StackItem, limits: &ExecutionEngineLimits) -> bool;
+	/// Determines whether the item is equal to `other` as observed by the `OpCode::Equal`
+	/// family of instructions. Buffers, arrays, maps and interop interfaces compare by
+	/// reference; structs compare by value, and pointers by script and position.
+	///
+	/// A comparison may read at most `MaxComparableSize` bytes of byte strings, with every
+	/// other struct element counting as one, and fails beyond that.
+	pub fn equals(
+		&self,
+		other: &StackItem,
+		limits: &ExecutionEngineLimits,
+	) -> Result<bool, VMError> {
+		let mut budget = limits.max_comparable_size;
+		self.equals_with(other, &mut budget)
+	}
 
-	fn from_interface(value: Some(dyn Any)) -> Box<dyn StackItem>{
+	fn equals_with(&self, other: &StackItem, budget: &mut usize) -> Result<bool, VMError> {
+		if std::ptr::eq(self, other) {
+			return Ok(true)
+		}
+		let exceeded = || {
+			VMError::InvalidParameter(
+				"The operand exceeds the maximum comparable size.".to_string(),
+			)
+		};
+		match (self, other) {
+			(Self::ByteString(a), Self::ByteString(b)) => {
+				if a.len() > *budget || b.len() > *budget {
+					return Err(exceeded())
+				}
+				*budget -= a.len().max(b.len());
+				Ok(a == b)
+			},
+			(Self::Struct(a), Self::Struct(b)) => {
+				if a.len() != b.len() {
+					return Ok(false)
+				}
+				for (x, y) in a.iter().zip(b) {
+					let (x, y) = (x.borrow(), y.borrow());
+					if !matches!(*x, Self::ByteString(_)) {
+						*budget = budget.checked_sub(1).ok_or_else(exceeded)?;
+					}
+					if !x.equals_with(&y, budget)? {
+						return Ok(false)
+					}
+				}
+				Ok(true)
+			},
+			(Self::Null, Self::Null) => Ok(true),
+			(Self::Boolean(a), Self::Boolean(b)) => Ok(a == b),
+			(Self::Integer(a), Self::Integer(b)) => Ok(a == b),
+			(Self::Pointer { .. }, Self::Pointer { .. }) => Ok(self == other),
+			_ => Ok(false),
+		}
+	}
 
-		match value {
-			Some(value)=>InteropInterface::new(value),
-			None => Null::new(),
+	/// Copies `item` and every item reachable from it. An item reachable along several paths,
+	/// including `item` itself through a cycle, is copied once and the copies share it.
+	pub fn deep_copy(item: &Rc<RefCell<StackItem>>) -> Rc<RefCell<StackItem>> {
+		Self::deep_copy_with(item, &mut HashMap::new())
+	}
+
+	fn deep_copy_with(
+		item: &Rc<RefCell<StackItem>>,
+		ref_map: &mut HashMap<*const RefCell<StackItem>, Rc<RefCell<StackItem>>>,
+	) -> Rc<RefCell<StackItem>> {
+		if let Some(copy) = ref_map.get(&Rc::as_ptr(item)) {
+			return copy.clone()
+		}
+		let source = item.borrow();
+		let empty = match &*source {
+			Self::Array(_) => Self::Array(CompoundItems::default()),
+			Self::Struct(_) => Self::Struct(CompoundItems::default()),
+			Self::Map(_) => Self::Map(OrderedMap::new()),
+			primitive => return Rc::new(RefCell::new(primitive.clone())),
+		};
+		// The copy is registered before its children, so that those referring back to it
+		// resolve to it instead of recursing forever.
+		let copy = Rc::new(RefCell::new(empty));
+		ref_map.insert(Rc::as_ptr(item), copy.clone());
+		match &*source {
+			Self::Array(items) | Self::Struct(items) => {
+				let children: Vec<_> =
+					items.iter().map(|child| Self::deep_copy_with(child, ref_map)).collect();
+				if let Self::Array(copies) | Self::Struct(copies) = &mut *copy.borrow_mut() {
+					copies.extend(children);
+				}
+			},
+			Self::Map(map) => {
+				let entries: Vec<_> = map
+					.iter()
+					.map(|(key, value)| (key.clone(), Self::deep_copy_with(value, ref_map)))
+					.collect();
+				if let Self::Map(copies) = &mut *copy.borrow_mut() {
+					for (key, value) in entries {
+						copies.insert(key, value);
+					}
+				}
+			},
+			_ => {},
 		}
+		copy
 	}
-	fn get_integer(&self) -> BigInt;
+}
 
-	fn get_interface<T: Any>(&self) -> Option<&T>{
-		panic!("Not implemented")
+/// Compares items as map keys: items of different types are never equal, so `Integer(1)`,
+/// `Boolean(true)` and `ByteString([1])` are distinct keys even though their spans match.
+/// Compound items compare their elements by reference. `equals` implements the rules of
+/// `OpCode::Equal` instead.
+impl PartialEq for StackItem {
+	fn eq(&self, other: &Self) -> bool {
+		match (self, other) {
+			(Self::Null, Self::Null) => true,
+			(Self::Boolean(a), Self::Boolean(b)) => a == b,
+			(Self::Integer(a), Self::Integer(b)) => a == b,
+			(Self::ByteString(a), Self::ByteString(b)) | (Self::Buffer(a), Self::Buffer(b)) =>
+				a == b,
+			(Self::Array(a), Self::Array(b)) | (Self::Struct(a), Self::Struct(b)) =>
+				a.len() == b.len() && a.iter().zip(b).all(|(x, y)| Rc::ptr_eq(x, y)),
+			(Self::Map(a), Self::Map(b)) =>
+				a.len() == b.len()
+					&& a.iter().all(|(k, v)| b.get(k).is_some_and(|w| Rc::ptr_eq(v, w))),
+			(Self::InteropInterface(a), Self::InteropInterface(b)) => Rc::ptr_eq(a, b),
+			(
+				Self::Pointer { script: a, position: x },
+				Self::Pointer { script: b, position: y },
+			) => Rc::ptr_eq(a, b) && x == y,
+			_ => false,
+		}
 	}
+}
 
+impl Eq for StackItem {}
+
+impl Hash for StackItem {
+	fn hash<H: Hasher>(&self, state: &mut H) {
+		std::mem::discriminant(self).hash(state);
+		match self {
+			Self::Boolean(value) => value.hash(state),
+			Self::Integer(value) => value.hash(state),
+			Self::ByteString(bytes) | Self::Buffer(bytes) => bytes.hash(state),
+			Self::Array(items) | Self::Struct(items) => items.len().hash(state),
+			Self::Map(map) => map.len().hash(state),
+			Self::Pointer { position, .. } => position.hash(state),
+			Self::Null | Self::InteropInterface(_) => {},
+		}
+	}
+}
 
-	fn get_bytes(&self) -> &[u8];
+impl From<bool> for StackItem {
+	fn from(value: bool) -> Self {
+		Self::Boolean(value)
+	}
+}
 
-	fn to_ref(&self) -> Rc<RefCell<dyn StackItem>> {
-		Rc::new(RefCell::new(self.clone()))
+impl From<BigInt> for StackItem {
+	fn from(value: BigInt) -> Self {
+		Self::Integer(value)
 	}
+}
 
+impl From<i32> for StackItem {
+	fn from(value: i32) -> Self {
+		Self::Integer(BigInt::from(value))
+	}
 }
 
-pub struct ObjectReferenceEntry {
-	pub(crate) item: Rc<RefCell<dyn StackItem>>,
-	pub(crate) references: i32,
+impl From<i64> for StackItem {
+	fn from(value: i64) -> Self {
+		Self::Integer(BigInt::from(value))
+	}
 }
 
-impl ObjectReferenceEntry {
-	pub fn new(item: Rc<RefCell<dyn StackItem>>) -> Self {
-		Self { item, references: 0 }
+impl From<usize> for StackItem {
+	fn from(value: usize) -> Self {
+		Self::Integer(BigInt::from(value))
+	}
+}
+
+impl From<Vec<u8>> for StackItem {
+	fn from(value: Vec<u8>) -> Self {
+		Self::ByteString(value)
+	}
+}
+
+impl From<StackItem> for Rc<RefCell<StackItem>> {
+	fn from(item: StackItem) -> Self {
+		Rc::new(RefCell::new(item))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_check_map_key() {
+		assert!(StackItem::ByteString(vec![0; 64]).check_map_key().is_ok());
+		assert!(StackItem::from(-1).check_map_key().is_ok());
+		assert!(matches!(
+			StackItem::ByteString(vec![0; 65]).check_map_key(),
+			Err(VMError::InvalidParameter(_))
+		));
+		assert!(matches!(StackItem::Buffer(vec![0]).check_map_key(), Err(VMError::InvalidType(_))));
+	}
+
+	#[test]
+	fn test_one_byte_keys_of_different_types() {
+		let keys = [StackItem::from(1), StackItem::Boolean(true), StackItem::ByteString(vec![1])];
+		for (i, a) in keys.iter().enumerate() {
+			for (j, b) in keys.iter().enumerate() {
+				assert_eq!(a == b, i == j, "{a:?} {b:?}");
+			}
+			assert_eq!(a.get_span().unwrap(), [1]);
+		}
+		let mut map = OrderedMap::new();
+		for (value, key) in keys.iter().enumerate() {
+			map.insert(key.clone(), Rc::new(RefCell::new(StackItem::from(value))));
+		}
+		assert_eq!(map.len(), 3);
+		assert_eq!(*map.get(&StackItem::Boolean(true)).unwrap().borrow(), StackItem::from(1));
+		assert_eq!(*map.get(&StackItem::ByteString(vec![1])).unwrap().borrow(), StackItem::from(2));
+	}
+
+	#[test]
+	fn test_get_buffer() {
+		assert_eq!(StackItem::Buffer(vec![1, 2]).get_buffer().unwrap(), vec![1, 2]);
+		assert!(StackItem::ByteString(vec![1, 2]).get_buffer().is_err());
+		assert_eq!(StackItem::ByteString(vec![1]).get_buffer_or_byte_string().unwrap(), vec![1]);
+		assert_eq!(StackItem::Buffer(vec![]).get_buffer_or_byte_string().unwrap(), vec![]);
+		assert!(StackItem::from(1).get_buffer_or_byte_string().is_err());
+		assert!(StackItem::Null.get_buffer_or_byte_string().is_err());
+	}
+
+	#[test]
+	fn test_get_integer_sizes() {
+		let mut max = vec![0xff; 32];
+		max[31] = 0x7f;
+		let expected = (BigInt::from(1) << 255) - 1;
+		assert_eq!(StackItem::ByteString(max.clone()).get_integer().unwrap(), expected);
+		assert_eq!(StackItem::Buffer(max).get_integer().unwrap(), expected);
+
+		// -2 sign-extended to 16 bytes
+		let mut negative = vec![0xff; 16];
+		negative[0] = 0xfe;
+		assert_eq!(StackItem::ByteString(negative).get_integer().unwrap(), BigInt::from(-2));
+		assert_eq!(StackItem::ByteString(vec![]).get_integer().unwrap(), BigInt::from(0));
+
+		assert!(matches!(
+			StackItem::ByteString(vec![0; 33]).get_integer(),
+			Err(VMError::InvalidType(_))
+		));
+	}
+
+	#[test]
+	fn test_convert_compound() {
+		let element: Rc<RefCell<StackItem>> = StackItem::from(1).into();
+		let array = StackItem::Array(vec![element.clone()].into());
+		let StackItem::Struct(items) = array.convert_to(StackItemType::Struct).unwrap() else {
+			panic!("expected a struct")
+		};
+		assert!(Rc::ptr_eq(&items[0], &element));
+		let structure = StackItem::Struct(items);
+		let StackItem::Array(items) = structure.convert_to(StackItemType::Array).unwrap() else {
+			panic!("expected an array")
+		};
+		assert!(Rc::ptr_eq(&items[0], &element));
+
+		let map = StackItem::Map(OrderedMap::new());
+		assert!(map.convert_to(StackItemType::Integer).is_err());
+		assert!(map.convert_to(StackItemType::Array).is_err());
+		assert!(array.convert_to(StackItemType::Map).is_err());
+	}
+
+	#[test]
+	fn test_deep_copy_self_reference() {
+		let array: Rc<RefCell<StackItem>> = StackItem::Array(CompoundItems::default()).into();
+		let mut map = OrderedMap::new();
+		map.insert(StackItem::from(1), array.clone());
+		let map: Rc<RefCell<StackItem>> = StackItem::Map(map).into();
+		// array = [array, 7, { 1: array }]
+		if let StackItem::Array(items) = &mut *array.borrow_mut() {
+			items.extend([array.clone(), StackItem::from(7).into(), map]);
+		}
+
+		let copy = StackItem::deep_copy(&array);
+		assert!(!Rc::ptr_eq(&copy, &array));
+		if let StackItem::Array(items) = &*copy.borrow() {
+			assert_eq!(items.len(), 3);
+			assert!(Rc::ptr_eq(&items[0], &copy));
+			assert_eq!(*items[1].borrow(), StackItem::from(7));
+			let StackItem::Map(map) = &*items[2].borrow() else { panic!("expected a map") };
+			assert!(Rc::ptr_eq(map.get(&StackItem::from(1)).unwrap(), &copy));
+		} else {
+			panic!("expected an array");
+		}
+
+		// Break the cycles so that the items can be dropped.
+		for item in [array, copy] {
+			if let StackItem::Array(items) = &mut *item.borrow_mut() {
+				items.clear();
+			}
+		}
+	}
+
+	#[test]
+	fn test_get_string_with() {
+		// "café" in Latin-1; a lone 0xE9 is not valid UTF-8.
+		let item = StackItem::ByteString(vec![0x63, 0x61, 0x66, 0xE9]);
+		assert_eq!(item.get_string(), None);
+		assert_eq!(item.get_string_with(StringEncoding::Utf8), None);
+		assert_eq!(item.get_string_with(StringEncoding::Latin1).as_deref(), Some("café"));
+
+		let item = StackItem::ByteString("café".as_bytes().to_vec());
+		assert_eq!(item.get_string_with(StringEncoding::Utf8).as_deref(), Some("café"));
+		assert_eq!(StackItem::Null.get_string_with(StringEncoding::Latin1), None);
 	}
 }
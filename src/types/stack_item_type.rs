@@ -1,37 +1,72 @@
+use crate::vm_error::VMError;
+use num_derive::FromPrimitive;
+use num_traits::FromPrimitive as _;
+
+/// An enumeration representing the types in the VM.
 #[repr(u8)]
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, FromPrimitive)]
 pub enum StackItemType {
+	/// Represents any type.
 	Any = 0x00,
+
+	/// Represents a code pointer.
 	Pointer = 0x10,
+
+	/// Represents the boolean (`true` or `false`) type.
 	Boolean = 0x20,
+
+	/// Represents an integer.
 	Integer = 0x21,
+
+	/// Represents an immutable memory block.
 	ByteString = 0x28,
+
+	/// Represents a memory block that can be used for reading and writing.
 	Buffer = 0x30,
+
+	/// Represents an array or a complex object.
 	Array = 0x40,
+
+	/// Represents a structure.
 	Struct = 0x41,
+
+	/// Represents an ordered collection of key-value pairs.
 	Map = 0x48,
+
+	/// Represents an interface used to interoperate with the outside of the VM.
 	InteropInterface = 0x60,
 }
 
 impl StackItemType {
+	/// Gets the type encoded as `tp`, failing with `VMError::InvalidType` when it isn't one.
+	pub fn try_from_u8(tp: u8) -> Result<Self, VMError> {
+		Self::from_u8(tp).ok_or_else(|| VMError::InvalidType(format!("Invalid type: {tp:#04x}")))
+	}
+
 	pub fn is_valid(tp: u8) -> bool {
-		match tp {
-			0x00 | 0x10 | 0x20 | 0x21 | 0x28 | 0x30 | 0x40 | 0x41 | 0x48 | 0x60 => true,
-			_ => false,
-		}
+		matches!(tp, 0x00 | 0x10 | 0x20 | 0x21 | 0x28 | 0x30 | 0x40 | 0x41 | 0x48 | 0x60)
 	}
 
 	pub fn is_primitive(tp: u8) -> bool {
-		match tp {
-			0x20 | 0x21 | 0x28 => true,
-			_ => false,
-		}
+		matches!(tp, 0x20 | 0x21 | 0x28)
 	}
 
 	pub fn is_compound(tp: u8) -> bool {
-		match tp {
-			0x40 | 0x41 | 0x48 => true,
-			_ => false,
+		matches!(tp, 0x40 | 0x41 | 0x48)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_try_from_u8() {
+		assert_eq!(StackItemType::try_from_u8(0x21).unwrap(), StackItemType::Integer);
+		assert_eq!(StackItemType::try_from_u8(0x60).unwrap(), StackItemType::InteropInterface);
+		for tp in [0x01, 0x22, 0xff] {
+			assert!(matches!(StackItemType::try_from_u8(tp), Err(VMError::InvalidType(_))));
+			assert!(!StackItemType::is_valid(tp));
 		}
 	}
 }
@@ -0,0 +1,142 @@
+//! Encodings used by tooling to exchange scripts and item payloads as text, and the modular
+//! arithmetic shared by the numeric opcodes.
+
+use num_bigint::BigInt;
+use num_traits::Signed;
+
+/// Encodes `bytes` as lowercase hex.
+pub fn to_hex(bytes: &[u8]) -> String {
+	bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Decodes a script from hex, in either case.
+pub fn script_from_hex(s: &str) -> Result<Vec<u8>, String> {
+	if !s.len().is_multiple_of(2) {
+		return Err(format!("Odd hex length: {}", s.len()))
+	}
+	if let Some(position) = s.bytes().position(|b| !b.is_ascii_hexdigit()) {
+		return Err(format!("Invalid hex character at {position}"))
+	}
+	Ok((0..s.len())
+		.step_by(2)
+		.map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+		.collect())
+}
+
+/// Decodes hex into bytes, or `None` if `s` isn't valid hex.
+pub fn hex_to_bytes(s: &str) -> Option<Vec<u8>> {
+	script_from_hex(s).ok()
+}
+
+const BASE64_ALPHABET: &[u8; 64] =
+	b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encodes `bytes` as padded base64 with the standard alphabet.
+pub fn base64_encode(bytes: &[u8]) -> String {
+	let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+	for chunk in bytes.chunks(3) {
+		let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+		let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+		for i in 0..4 {
+			if i <= chunk.len() {
+				encoded.push(BASE64_ALPHABET[(n >> (18 - 6 * i) & 0x3F) as usize] as char);
+			} else {
+				encoded.push('=');
+			}
+		}
+	}
+	encoded
+}
+
+/// Decodes padded base64 with the standard alphabet.
+pub fn base64_decode(s: &str) -> Result<Vec<u8>, String> {
+	let bytes = s.as_bytes();
+	if !bytes.len().is_multiple_of(4) {
+		return Err(format!("Invalid base64 length: {}", bytes.len()))
+	}
+	let mut decoded = Vec::with_capacity(bytes.len() / 4 * 3);
+	for (index, chunk) in bytes.chunks(4).enumerate() {
+		let last = index == bytes.len() / 4 - 1;
+		let padding = chunk.iter().rev().take_while(|&&c| c == b'=').count();
+		if padding > 2 || (padding > 0 && !last) {
+			return Err(format!("Invalid base64 padding at {}", index * 4))
+		}
+		let mut n = 0u32;
+		for (offset, &c) in chunk[..4 - padding].iter().enumerate() {
+			let value = BASE64_ALPHABET
+				.iter()
+				.position(|&a| a == c)
+				.ok_or_else(|| format!("Invalid base64 character at {}", index * 4 + offset))?;
+			n = n << 6 | value as u32;
+		}
+		n <<= 6 * padding as u32;
+		decoded.extend_from_slice(&n.to_be_bytes()[1..4 - padding]);
+	}
+	Ok(decoded)
+}
+
+/// Finds the inverse of a positive `value` modulo `modulus`, as `BigInteger.ModInverse` does in
+/// NEO. The inverse lies in `[0, modulus)`.
+pub fn mod_inverse(value: &BigInt, modulus: &BigInt) -> Result<BigInt, String> {
+	if !value.is_positive() {
+		return Err(format!("Value must be positive: {value}"))
+	}
+	if modulus < &BigInt::from(2) {
+		return Err(format!("Modulus must be at least 2: {modulus}"))
+	}
+	value
+		.modinv(modulus)
+		.ok_or_else(|| format!("No modular inverse of {value} modulo {modulus}"))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use num_traits::One;
+
+	#[test]
+	fn test_hex() {
+		assert_eq!(to_hex(&[0x00, 0x0a, 0xff]), "000aff");
+		assert_eq!(script_from_hex("000aFF").unwrap(), [0x00, 0x0a, 0xff]);
+		assert_eq!(script_from_hex("").unwrap(), []);
+		assert_eq!(script_from_hex("abc").unwrap_err(), "Odd hex length: 3");
+		assert_eq!(script_from_hex("0g").unwrap_err(), "Invalid hex character at 1");
+		// `from_str_radix` alone would accept the sign.
+		assert!(hex_to_bytes("+f").is_none());
+		assert!(hex_to_bytes("é0").is_none());
+	}
+
+	#[test]
+	fn test_mod_inverse() {
+		let inverse =
+			|value: i32, modulus: i32| mod_inverse(&BigInt::from(value), &BigInt::from(modulus));
+		assert_eq!(inverse(19, 141).unwrap(), BigInt::from(52));
+		assert_eq!(inverse(1, 2).unwrap(), BigInt::one());
+		// The inverse is reduced even when `value` exceeds the modulus.
+		assert_eq!(inverse(160, 141).unwrap(), BigInt::from(52));
+		assert_eq!(inverse(6, 9).unwrap_err(), "No modular inverse of 6 modulo 9");
+		assert_eq!(inverse(0, 7).unwrap_err(), "Value must be positive: 0");
+		assert_eq!(inverse(-3, 7).unwrap_err(), "Value must be positive: -3");
+		assert_eq!(inverse(3, 1).unwrap_err(), "Modulus must be at least 2: 1");
+	}
+
+	#[test]
+	fn test_base64() {
+		for (bytes, encoded) in [
+			(&b""[..], ""),
+			(b"f", "Zg=="),
+			(b"fo", "Zm8="),
+			(b"foo", "Zm9v"),
+			(b"foob", "Zm9vYg=="),
+			// PUSH1 PUSH2 ADD RET, as NEO tooling prints it.
+			(&[0x11, 0x12, 0x9e, 0x40], "ERKeQA=="),
+			(&[0x0c, 0x03, 0x6e, 0x65, 0x6f], "DANuZW8="),
+		] {
+			assert_eq!(base64_encode(bytes), encoded);
+			assert_eq!(base64_decode(encoded).unwrap(), bytes);
+		}
+		assert_eq!(base64_decode("Zg=").unwrap_err(), "Invalid base64 length: 3");
+		assert_eq!(base64_decode("Zg==Zm8=").unwrap_err(), "Invalid base64 padding at 0");
+		assert_eq!(base64_decode("Zm9*").unwrap_err(), "Invalid base64 character at 3");
+	}
+}
@@ -0,0 +1,161 @@
+/// A pool of byte vectors reused by the `Buffer` items created by the splice instructions.
+///
+/// Rented buffers are always zeroed, so no data from a previously released item can leak into
+/// a new one.
+#[derive(Debug, Default, Clone)]
+pub struct BufferPool {
+	buffers: Vec<Vec<u8>>,
+	max_buffers: usize,
+}
+
+impl BufferPool {
+	/// Creates a pool retaining at most `max_buffers` released buffers.
+	pub fn new(max_buffers: usize) -> Self {
+		Self { buffers: Vec::new(), max_buffers }
+	}
+
+	/// The number of buffers currently available for reuse.
+	pub fn len(&self) -> usize {
+		self.buffers.len()
+	}
+
+	/// Indicates whether there is no buffer available for reuse.
+	pub fn is_empty(&self) -> bool {
+		self.buffers.is_empty()
+	}
+
+	/// Returns a zeroed buffer of `length` bytes, reusing a pooled allocation when one is large
+	/// enough.
+	pub fn rent(&mut self, length: usize) -> Vec<u8> {
+		let position = self
+			.buffers
+			.iter()
+			.enumerate()
+			.filter(|(_, buffer)| buffer.capacity() >= length)
+			.min_by_key(|(_, buffer)| buffer.capacity())
+			.map(|(position, _)| position);
+		match position {
+			Some(position) => {
+				let mut buffer = self.buffers.swap_remove(position);
+				buffer.clear();
+				buffer.resize(length, 0);
+				buffer
+			},
+			None => vec![0; length],
+		}
+	}
+
+	/// Returns `buffer` to the pool so that its allocation can be reused.
+	pub fn release(&mut self, buffer: Vec<u8>) {
+		if buffer.capacity() > 0 && self.buffers.len() < self.max_buffers {
+			self.buffers.push(buffer);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		execution_engine::ExecutionEngine, op_code::OpCode, stack_item::StackItem,
+		vm::script::Script, vm_state::VMState,
+	};
+
+	fn run(script: &[u8], pooled: bool) -> ExecutionEngine {
+		let mut engine = ExecutionEngine::new();
+		if pooled {
+			engine.enable_buffer_pool(16);
+		}
+		engine.load_script(Script::new(script.to_vec()), -1, 0).unwrap();
+		engine.execute();
+		engine
+	}
+
+	#[test]
+	fn test_rent_is_zeroed() {
+		let mut pool = BufferPool::new(4);
+		pool.release(vec![0xFF; 8]);
+		assert_eq!(pool.len(), 1);
+		assert_eq!(pool.rent(4), vec![0; 4]);
+		assert!(pool.is_empty());
+	}
+
+	#[test]
+	fn test_release_respects_capacity() {
+		let mut pool = BufferPool::new(1);
+		pool.release(vec![1]);
+		pool.release(vec![2]);
+		assert_eq!(pool.len(), 1);
+	}
+
+	#[test]
+	fn test_drop_and_substr_release_buffers() {
+		let mut engine = ExecutionEngine::new();
+		engine.enable_buffer_pool(16);
+		let script = vec![
+			OpCode::Push16 as u8,
+			OpCode::NewBuffer as u8,
+			OpCode::Dup as u8,
+			OpCode::Drop as u8,
+			OpCode::Drop as u8,
+			OpCode::Push16 as u8,
+			OpCode::NewBuffer as u8,
+			OpCode::Push0 as u8,
+			OpCode::Push2 as u8,
+			OpCode::Substr as u8,
+			OpCode::Drop as u8,
+		];
+		engine.load_script(Script::new(script), -1, 0).unwrap();
+		let pooled = |engine: &ExecutionEngine| engine.buffer_pool.as_ref().unwrap().len();
+
+		// The first DROP leaves a reference on the stack, the second pops the last one.
+		engine.execute_with_limit(4).unwrap();
+		assert_eq!(pooled(&engine), 0);
+		engine.execute_next().unwrap();
+		assert_eq!(pooled(&engine), 1);
+
+		// NEWBUFFER rents the released buffer, and SUBSTR releases it for its own result.
+		engine.execute_with_limit(2).unwrap();
+		assert_eq!(pooled(&engine), 0);
+		engine.execute_with_limit(3).unwrap();
+		assert_eq!(pooled(&engine), 0);
+		match &*engine.peek(0).unwrap().borrow() {
+			StackItem::Buffer(buffer) => assert_eq!((buffer.len(), buffer.capacity()), (2, 16)),
+			item => panic!("expected a buffer, found {item:?}"),
+		}
+
+		engine.execute_next().unwrap();
+		assert_eq!(pooled(&engine), 1);
+	}
+
+	#[test]
+	fn test_pooled_matches_unpooled() {
+		let script = [
+			OpCode::Push4 as u8,
+			OpCode::NewBuffer as u8,
+			OpCode::Drop as u8,
+			OpCode::PushData1 as u8,
+			0x02,
+			0xAA,
+			0xBB,
+			OpCode::Push2 as u8,
+			OpCode::NewBuffer as u8,
+			OpCode::Cat as u8,
+			OpCode::Push1 as u8,
+			OpCode::Push2 as u8,
+			OpCode::Substr as u8,
+			OpCode::Push3 as u8,
+			OpCode::NewBuffer as u8,
+		];
+		let plain = run(&script, false);
+		let pooled = run(&script, true);
+		assert_eq!(plain.state, VMState::Halt);
+		assert_eq!(pooled.state, VMState::Halt);
+		let plain = plain.result_stack.borrow();
+		let pooled = pooled.result_stack.borrow();
+		assert_eq!(plain.count(), pooled.count());
+		for index in 0..plain.count() as i32 {
+			assert_eq!(*plain.peek(index).unwrap().borrow(), *pooled.peek(index).unwrap().borrow());
+		}
+	}
+}
@@ -0,0 +1,15 @@
+use crate::{instruction::Instruction, vm_state::VMState};
+
+/// Receives the events of an `ExecutionEngine`, e.g. to trace or profile the execution.
+///
+/// Every method does nothing by default, so an observer only implements the events it needs.
+pub trait EngineObserver {
+	/// Called before `instruction` is executed.
+	fn on_pre_execute(&mut self, _instruction: &Instruction) {}
+
+	/// Called after `instruction` is executed without error.
+	fn on_post_execute(&mut self, _instruction: &Instruction) {}
+
+	/// Called when the state of the engine changes from `old` to `new`.
+	fn on_state_changed(&mut self, _old: VMState, _new: VMState) {}
+}
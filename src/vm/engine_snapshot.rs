@@ -0,0 +1,856 @@
+use crate::{
+	binary_serializer::{write_var_bytes, write_var_int, Reader},
+	evaluation_stack::EvaluationStack,
+	exception::{
+		exception_handling_context::ExceptionHandlingContext,
+		exception_handling_state::ExceptionHandlingState,
+	},
+	execution_context::ExecutionContext,
+	execution_engine::ExecutionEngine,
+	execution_engine_limits::ExecutionEngineLimits,
+	interop_service::InteropService,
+	jump_table::JumpTable,
+	ordered_map::OrderedMap,
+	reference_counter::ReferenceCounter,
+	slot::Slot,
+	stack_item::StackItem,
+	stack_item_type::StackItemType,
+	vm::script::Script,
+	vm_error::VMError,
+	vm_state::VMState,
+};
+use num_bigint::BigInt;
+use num_traits::{FromPrimitive, Zero};
+use std::{any::Any, cell::RefCell, collections::HashMap, rc::Rc};
+
+/// A self-contained copy of the execution state of an engine.
+///
+/// Every item reachable from the stacks and slots is stored once in `items`, and compound
+/// items refer to their elements by index, so shared and circular references survive a
+/// round trip. A snapshot without interop interfaces can be converted to bytes with
+/// `to_bytes`, e.g. to resume the execution in another process.
+#[derive(Clone, Debug)]
+pub struct EngineSnapshot {
+	/// The restrictions on the engine.
+	pub limits: ExecutionEngineLimits,
+
+	/// The state of the engine.
+	pub state: VMState,
+
+	/// The items reachable from the engine.
+	pub items: Vec<SnapshotItem>,
+
+	/// The scripts, evaluation stacks and static fields of the contexts.
+	pub shared_states: Vec<SharedStatesSnapshot>,
+
+	/// The invocation stack, from the entry context to the current one.
+	pub contexts: Vec<ContextSnapshot>,
+
+	/// The result stack, from bottom to top.
+	pub result_stack: Vec<usize>,
+
+	/// The uncaught exception, if any.
+	pub uncaught_exception: Option<usize>,
+}
+
+/// An item of an `EngineSnapshot`, whose compound elements are indexes into
+/// `EngineSnapshot::items`.
+#[derive(Clone, Debug)]
+pub enum SnapshotItem {
+	Null,
+	Boolean(bool),
+	Integer(BigInt),
+	ByteString(Vec<u8>),
+	Buffer(Vec<u8>),
+	Array(Vec<usize>),
+	Struct(Vec<usize>),
+	Map(Vec<(usize, usize)>),
+	InteropInterface(Rc<dyn Any>),
+	/// A pointer into the script of `EngineSnapshot::shared_states[shared_states]` if that is
+	/// set, so that it points into the restored script, or into `script` otherwise.
+	Pointer {
+		script: Rc<RefCell<Script>>,
+		shared_states: Option<usize>,
+		position: usize,
+	},
+}
+
+/// The states shared by an execution context and the contexts cloned from it.
+#[derive(Clone, Debug)]
+pub struct SharedStatesSnapshot {
+	pub script: Vec<u8>,
+	pub strict_mode: bool,
+	pub evaluation_stack: Vec<usize>,
+	pub static_fields: Option<Vec<usize>>,
+}
+
+/// A frame of the invocation stack.
+#[derive(Clone, Debug)]
+pub struct ContextSnapshot {
+	/// The index of the states of this context in `EngineSnapshot::shared_states`.
+	pub shared_states: usize,
+	pub instruction_pointer: usize,
+	pub rv_count: i32,
+	pub local_variables: Option<Vec<usize>>,
+	pub arguments: Option<Vec<usize>>,
+	pub try_stack: Option<Vec<ExceptionHandlingContext>>,
+}
+
+/// Assigns an index to every item reachable from the engine.
+#[derive(Default)]
+struct SnapshotBuilder {
+	items: Vec<SnapshotItem>,
+	indexes: HashMap<*const RefCell<StackItem>, usize>,
+}
+
+impl SnapshotBuilder {
+	fn add(&mut self, item: &Rc<RefCell<StackItem>>) -> usize {
+		if let Some(&index) = self.indexes.get(&Rc::as_ptr(item)) {
+			return index
+		}
+		let index = self.items.len();
+		self.indexes.insert(Rc::as_ptr(item), index);
+		self.items.push(SnapshotItem::Null);
+		let snapshot = match &*item.borrow() {
+			StackItem::Array(items) => SnapshotItem::Array(self.add_all(items)),
+			StackItem::Struct(items) => SnapshotItem::Struct(self.add_all(items)),
+			StackItem::Map(map) => SnapshotItem::Map(
+				map.iter().map(|(key, value)| (self.add_value(key), self.add(value))).collect(),
+			),
+			item => Self::primitive(item),
+		};
+		self.items[index] = snapshot;
+		index
+	}
+
+	fn add_all(&mut self, items: &[Rc<RefCell<StackItem>>]) -> Vec<usize> {
+		items.iter().map(|item| self.add(item)).collect()
+	}
+
+	/// Adds an item that is stored by value, such as a map key.
+	fn add_value(&mut self, item: &StackItem) -> usize {
+		self.items.push(Self::primitive(item));
+		self.items.len() - 1
+	}
+
+	fn primitive(item: &StackItem) -> SnapshotItem {
+		match item {
+			StackItem::Boolean(value) => SnapshotItem::Boolean(*value),
+			StackItem::Integer(value) => SnapshotItem::Integer(value.clone()),
+			StackItem::ByteString(bytes) => SnapshotItem::ByteString(bytes.clone()),
+			StackItem::Buffer(bytes) => SnapshotItem::Buffer(bytes.clone()),
+			StackItem::InteropInterface(value) => SnapshotItem::InteropInterface(value.clone()),
+			StackItem::Pointer { script, position } => SnapshotItem::Pointer {
+				script: script.clone(),
+				shared_states: None,
+				position: *position,
+			},
+			_ => SnapshotItem::Null,
+		}
+	}
+
+	fn add_stack(&mut self, stack: &EvaluationStack) -> Vec<usize> {
+		stack.iter().map(|item| self.add(item)).collect()
+	}
+
+	fn add_slot(&mut self, slot: &Option<Rc<RefCell<Slot>>>) -> Option<Vec<usize>> {
+		let slot = slot.as_ref()?.borrow();
+		Some(slot.iter().map(|item| self.add(item)).collect())
+	}
+}
+
+impl ExecutionEngine {
+	/// Captures the execution state of the engine. Interop interfaces and the scripts of
+	/// pointers are shared with the snapshot rather than copied; every other item reachable
+	/// from the stacks and slots is copied, so the cost grows with their number.
+	pub fn snapshot(&self) -> EngineSnapshot {
+		let mut builder = SnapshotBuilder::default();
+		let mut shared_states: Vec<SharedStatesSnapshot> = Vec::new();
+		let mut owners: Vec<Rc<RefCell<ExecutionContext>>> = Vec::new();
+		let mut contexts = Vec::with_capacity(self.invocation_stack.len());
+		for context in &self.invocation_stack {
+			let context_ref = context.borrow();
+			let shared = match owners
+				.iter()
+				.position(|owner| owner.borrow().shares_states_with(&context_ref))
+			{
+				Some(shared) => shared,
+				None => {
+					let script = context_ref.script();
+					let script = script.borrow();
+					shared_states.push(SharedStatesSnapshot {
+						script: script.value().to_vec(),
+						strict_mode: script.strict_mode(),
+						evaluation_stack: builder
+							.add_stack(&context_ref.evaluation_stack().borrow()),
+						static_fields: builder.add_slot(&context_ref.static_fields()),
+					});
+					owners.push(context.clone());
+					owners.len() - 1
+				},
+			};
+			contexts.push(ContextSnapshot {
+				shared_states: shared,
+				instruction_pointer: context_ref.instruction_pointer,
+				rv_count: context_ref.rv_count,
+				local_variables: builder.add_slot(&context_ref.local_variables),
+				arguments: builder.add_slot(&context_ref.arguments),
+				try_stack: context_ref.try_stack.clone(),
+			});
+		}
+		let result_stack = builder.add_stack(&self.result_stack.borrow());
+		let uncaught_exception = self.uncaught_exception.as_ref().map(|item| builder.add(item));
+		let scripts: Vec<_> = owners.iter().map(|owner| owner.borrow().script()).collect();
+		for item in &mut builder.items {
+			if let SnapshotItem::Pointer { script, shared_states, .. } = item {
+				*shared_states = scripts.iter().position(|owned| Rc::ptr_eq(owned, script));
+			}
+		}
+		EngineSnapshot {
+			limits: self.limits,
+			state: self.state,
+			items: builder.items,
+			shared_states,
+			contexts,
+			result_stack,
+			uncaught_exception,
+		}
+	}
+
+	/// Rebuilds an engine from `snapshot`, executing instructions with `jump_table` and
+	/// `syscalls`.
+	pub fn restore(
+		snapshot: EngineSnapshot,
+		jump_table: Rc<JumpTable>,
+		syscalls: InteropService,
+	) -> Result<ExecutionEngine, VMError> {
+		let mut engine = ExecutionEngine::new_with_limits(snapshot.limits);
+		engine.jump_table = jump_table;
+		engine.interop_service = syscalls;
+		let reference_counter = engine.reference_counter.clone();
+		let mut owners = Vec::with_capacity(snapshot.shared_states.len());
+		for shared in &snapshot.shared_states {
+			let script = Script::new_with_mode(shared.script.clone(), shared.strict_mode)?;
+			engine.limits.assert_max_script_size(script.len())?;
+			owners.push(ExecutionContext::new(script, 0, reference_counter.clone()));
+		}
+		let scripts: Vec<_> = owners.iter().map(ExecutionContext::script).collect();
+		let items = restore_items(&snapshot.items, &scripts, &reference_counter)?;
+		let item = |index: usize| {
+			items.get(index).cloned().ok_or_else(|| {
+				VMError::InvalidParameter(format!("Invalid snapshot item index {index}"))
+			})
+		};
+		let slot = |indexes: &Option<Vec<usize>>| -> Result<_, VMError> {
+			let Some(indexes) = indexes else { return Ok(None) };
+			let items = indexes.iter().map(|&index| item(index)).collect::<Result<_, _>>()?;
+			Ok(Some(Rc::new(RefCell::new(Slot::new(items, reference_counter.clone())))))
+		};
+
+		// The stacks and static fields are only filled for the states a context refers to.
+		let mut filled = vec![false; owners.len()];
+		for context in &snapshot.contexts {
+			let owner = owners.get(context.shared_states).ok_or_else(|| {
+				VMError::InvalidParameter(format!(
+					"Invalid snapshot shared states index {}",
+					context.shared_states
+				))
+			})?;
+			if !std::mem::replace(&mut filled[context.shared_states], true) {
+				let shared = &snapshot.shared_states[context.shared_states];
+				for &index in &shared.evaluation_stack {
+					owner.evaluation_stack().borrow_mut().push(item(index)?);
+				}
+				owner.set_static_fields(slot(&shared.static_fields)?);
+			}
+			let mut restored = owner.clone_at(context.instruction_pointer);
+			restored.instruction_pointer = context.instruction_pointer;
+			restored.rv_count = context.rv_count;
+			restored.local_variables = slot(&context.local_variables)?;
+			restored.arguments = slot(&context.arguments)?;
+			restored.try_stack = context.try_stack.clone();
+			engine.load_context(Rc::new(RefCell::new(restored)))?;
+		}
+		for &index in &snapshot.result_stack {
+			engine.result_stack.borrow_mut().push(item(index)?);
+		}
+		engine.uncaught_exception = snapshot.uncaught_exception.map(item).transpose()?;
+		engine.state = snapshot.state;
+		Ok(engine)
+	}
+
+	/// Replaces the execution state of the engine with the one captured by `snapshot`, e.g. to
+	/// roll back a speculative execution.
+	///
+	/// Like `restore`, this copies every item of the snapshot, so the snapshot can be rolled
+	/// back to again. The jump table, syscalls, hooks, breakpoints and GAS accounting of the
+	/// engine are kept.
+	pub fn rollback(&mut self, snapshot: EngineSnapshot) -> Result<(), VMError> {
+		let ExecutionEngine {
+			limits,
+			reference_counter,
+			invocation_stack,
+			current_context,
+			entry_context,
+			result_stack,
+			uncaught_exception,
+			state,
+			..
+		} = Self::restore(snapshot, self.jump_table.clone(), InteropService::new())?;
+		self.limits = limits;
+		self.reference_counter = reference_counter;
+		self.invocation_stack = invocation_stack;
+		self.current_context = current_context;
+		self.entry_context = entry_context;
+		self.result_stack = result_stack;
+		self.uncaught_exception = uncaught_exception;
+		self.state = state;
+		self.is_jumping = false;
+		self.stopped_at_breakpoint = false;
+		Ok(())
+	}
+}
+
+/// Rebuilds the items of a snapshot, counting a reference from each compound item to each of
+/// its elements. Pointers into the script of a snapshot shared state point into the matching
+/// script of `scripts`.
+fn restore_items(
+	snapshot: &[SnapshotItem],
+	scripts: &[Rc<RefCell<Script>>],
+	reference_counter: &Rc<RefCell<ReferenceCounter>>,
+) -> Result<Vec<Rc<RefCell<StackItem>>>, VMError> {
+	let items: Vec<Rc<RefCell<StackItem>>> = snapshot
+		.iter()
+		.map(|item| {
+			let item = match item {
+				SnapshotItem::Null => StackItem::Null,
+				SnapshotItem::Boolean(value) => StackItem::Boolean(*value),
+				SnapshotItem::Integer(value) => StackItem::Integer(value.clone()),
+				SnapshotItem::ByteString(bytes) => StackItem::ByteString(bytes.clone()),
+				SnapshotItem::Buffer(bytes) => StackItem::Buffer(bytes.clone()),
+				SnapshotItem::Array(_) => StackItem::Array(Vec::new().into()),
+				SnapshotItem::Struct(_) => StackItem::Struct(Vec::new().into()),
+				SnapshotItem::Map(_) => StackItem::Map(OrderedMap::new()),
+				SnapshotItem::InteropInterface(value) => StackItem::InteropInterface(value.clone()),
+				SnapshotItem::Pointer { script, shared_states, position } => {
+					let script = match shared_states {
+						Some(index) => scripts.get(*index).cloned().ok_or_else(|| {
+							VMError::InvalidParameter(format!(
+								"Invalid snapshot shared states index {index}"
+							))
+						})?,
+						None => script.clone(),
+					};
+					StackItem::Pointer { script, position: *position }
+				},
+			};
+			Ok(Rc::new(RefCell::new(item)))
+		})
+		.collect::<Result<_, VMError>>()?;
+	let get = |index: usize| {
+		items.get(index).cloned().ok_or_else(|| {
+			VMError::InvalidParameter(format!("Invalid snapshot item index {index}"))
+		})
+	};
+	// Keys are checked before any map is borrowed mutably, as a key could be the map itself.
+	for snapshot in snapshot {
+		let SnapshotItem::Map(entries) = snapshot else { continue };
+		for &(key, _) in entries {
+			let key = get(key)?;
+			let key = key.borrow();
+			if !key.is_primitive() {
+				return Err(VMError::InvalidType(format!(
+					"{:?} can't be used as a map key.",
+					key.get_type()
+				)))
+			}
+		}
+	}
+	let mut reference_counter = reference_counter.borrow_mut();
+	for (item, snapshot) in items.iter().zip(snapshot) {
+		match (&mut *item.borrow_mut(), snapshot) {
+			(StackItem::Array(elements), SnapshotItem::Array(indexes))
+			| (StackItem::Struct(elements), SnapshotItem::Struct(indexes)) =>
+				for &index in indexes {
+					elements.push(get(index)?);
+					reference_counter.add_reference();
+				},
+			(StackItem::Map(map), SnapshotItem::Map(entries)) =>
+				for &(key, value) in entries {
+					map.insert(get(key)?.borrow().clone(), get(value)?);
+					reference_counter.add_reference();
+					reference_counter.add_reference();
+				},
+			_ => {},
+		}
+	}
+	Ok(items)
+}
+
+impl EngineSnapshot {
+	/// Encodes the snapshot, failing when it contains an interop interface or a pointer.
+	pub fn to_bytes(&self) -> Result<Vec<u8>, VMError> {
+		let mut writer = Vec::new();
+		let limits = &self.limits;
+		for value in [
+			limits.max_shift,
+			limits.max_stack_size,
+			limits.max_item_size,
+			limits.max_comparable_size,
+			limits.max_invocation_stack_size,
+			limits.max_try_nesting_depth,
+			limits.max_script_size,
+		] {
+			write_var_int(&mut writer, value as u64);
+		}
+		writer.push(limits.catch_engine_exceptions as u8);
+		writer.push(self.state as u8);
+
+		write_var_int(&mut writer, self.items.len() as u64);
+		for item in &self.items {
+			match item {
+				SnapshotItem::Null => writer.push(StackItemType::Any as u8),
+				SnapshotItem::Boolean(value) => {
+					writer.push(StackItemType::Boolean as u8);
+					writer.push(*value as u8);
+				},
+				SnapshotItem::Integer(value) => {
+					writer.push(StackItemType::Integer as u8);
+					let bytes =
+						if value.is_zero() { Vec::new() } else { value.to_signed_bytes_le() };
+					write_var_bytes(&mut writer, &bytes);
+				},
+				SnapshotItem::ByteString(bytes) => {
+					writer.push(StackItemType::ByteString as u8);
+					write_var_bytes(&mut writer, bytes);
+				},
+				SnapshotItem::Buffer(bytes) => {
+					writer.push(StackItemType::Buffer as u8);
+					write_var_bytes(&mut writer, bytes);
+				},
+				SnapshotItem::Array(indexes) => {
+					writer.push(StackItemType::Array as u8);
+					write_indexes(&mut writer, indexes);
+				},
+				SnapshotItem::Struct(indexes) => {
+					writer.push(StackItemType::Struct as u8);
+					write_indexes(&mut writer, indexes);
+				},
+				SnapshotItem::Map(entries) => {
+					writer.push(StackItemType::Map as u8);
+					write_var_int(&mut writer, entries.len() as u64);
+					for &(key, value) in entries {
+						write_var_int(&mut writer, key as u64);
+						write_var_int(&mut writer, value as u64);
+					}
+				},
+				SnapshotItem::InteropInterface(_) =>
+					return Err(VMError::InvalidType(
+						"Snapshots containing interop interfaces can't be encoded.".to_string(),
+					)),
+				SnapshotItem::Pointer { .. } =>
+					return Err(VMError::InvalidType(
+						"Snapshots containing pointers can't be encoded.".to_string(),
+					)),
+			}
+		}
+
+		write_var_int(&mut writer, self.shared_states.len() as u64);
+		for shared in &self.shared_states {
+			write_var_bytes(&mut writer, &shared.script);
+			writer.push(shared.strict_mode as u8);
+			write_indexes(&mut writer, &shared.evaluation_stack);
+			write_optional_indexes(&mut writer, &shared.static_fields);
+		}
+
+		write_var_int(&mut writer, self.contexts.len() as u64);
+		for context in &self.contexts {
+			write_var_int(&mut writer, context.shared_states as u64);
+			write_var_int(&mut writer, context.instruction_pointer as u64);
+			writer.extend_from_slice(&context.rv_count.to_le_bytes());
+			write_optional_indexes(&mut writer, &context.local_variables);
+			write_optional_indexes(&mut writer, &context.arguments);
+			match &context.try_stack {
+				None => writer.push(0),
+				Some(try_stack) => {
+					writer.push(1);
+					write_var_int(&mut writer, try_stack.len() as u64);
+					for try_context in try_stack {
+						for pointer in [
+							try_context.catch_pointer,
+							try_context.finally_pointer,
+							try_context.end_pointer,
+						] {
+							writer.extend_from_slice(&pointer.to_le_bytes());
+						}
+						writer.push(try_context.state as u8);
+					}
+				},
+			}
+		}
+
+		write_indexes(&mut writer, &self.result_stack);
+		write_optional_indexes(
+			&mut writer,
+			&self.uncaught_exception.map(|exception| vec![exception]),
+		);
+		Ok(writer)
+	}
+
+	/// Decodes a snapshot encoded by `to_bytes`.
+	pub fn from_bytes(bytes: &[u8]) -> Result<Self, VMError> {
+		let mut reader = Reader::new(bytes);
+		let reader = &mut reader;
+		let limits = ExecutionEngineLimits {
+			max_shift: read_usize(reader)?,
+			max_stack_size: read_usize(reader)?,
+			max_item_size: read_usize(reader)?,
+			max_comparable_size: read_usize(reader)?,
+			max_invocation_stack_size: read_usize(reader)?,
+			max_try_nesting_depth: read_usize(reader)?,
+			max_script_size: read_usize(reader)?,
+			catch_engine_exceptions: read_bool(reader)?,
+		};
+		let state = match reader.read_u8()? {
+			0 => VMState::None,
+			1 => VMState::Halt,
+			2 => VMState::Fault,
+			4 => VMState::Break,
+			state => return Err(invalid_snapshot(format!("invalid state {state}"))),
+		};
+
+		let count = read_usize(reader)?;
+		let mut items = Vec::new();
+		for _ in 0..count {
+			let ty = reader.read_u8()?;
+			let item = match StackItemType::from_u8(ty) {
+				Some(StackItemType::Any) => SnapshotItem::Null,
+				Some(StackItemType::Boolean) => SnapshotItem::Boolean(read_bool(reader)?),
+				Some(StackItemType::Integer) =>
+					SnapshotItem::Integer(BigInt::from_signed_bytes_le(
+						reader.read_var_bytes(StackItem::MAX_INTEGER_SIZE)?,
+					)),
+				Some(StackItemType::ByteString) =>
+					SnapshotItem::ByteString(reader.read_var_bytes(limits.max_item_size)?.to_vec()),
+				Some(StackItemType::Buffer) =>
+					SnapshotItem::Buffer(reader.read_var_bytes(limits.max_item_size)?.to_vec()),
+				Some(StackItemType::Array) => SnapshotItem::Array(read_indexes(reader)?),
+				Some(StackItemType::Struct) => SnapshotItem::Struct(read_indexes(reader)?),
+				Some(StackItemType::Map) => {
+					let count = read_usize(reader)?;
+					let mut entries = Vec::new();
+					for _ in 0..count {
+						entries.push((read_usize(reader)?, read_usize(reader)?));
+					}
+					SnapshotItem::Map(entries)
+				},
+				_ => return Err(invalid_snapshot(format!("invalid item type {ty:#04x}"))),
+			};
+			items.push(item);
+		}
+
+		let count = read_usize(reader)?;
+		let mut shared_states = Vec::new();
+		for _ in 0..count {
+			shared_states.push(SharedStatesSnapshot {
+				script: reader.read_var_bytes(limits.max_script_size)?.to_vec(),
+				strict_mode: read_bool(reader)?,
+				evaluation_stack: read_indexes(reader)?,
+				static_fields: read_optional_indexes(reader)?,
+			});
+		}
+
+		let count = read_usize(reader)?;
+		let mut contexts = Vec::new();
+		for _ in 0..count {
+			let shared_states = read_usize(reader)?;
+			let instruction_pointer = read_usize(reader)?;
+			let rv_count = read_i32(reader)?;
+			let local_variables = read_optional_indexes(reader)?;
+			let arguments = read_optional_indexes(reader)?;
+			let try_stack = if read_bool(reader)? {
+				let count = read_usize(reader)?;
+				let mut try_stack = Vec::new();
+				for _ in 0..count {
+					let mut try_context =
+						ExceptionHandlingContext::new(read_i32(reader)?, read_i32(reader)?);
+					try_context.end_pointer = read_i32(reader)?;
+					try_context.state = match reader.read_u8()? {
+						0 => ExceptionHandlingState::Try,
+						1 => ExceptionHandlingState::Catch,
+						2 => ExceptionHandlingState::Finally,
+						state => return Err(invalid_snapshot(format!("invalid try state {state}"))),
+					};
+					try_stack.push(try_context);
+				}
+				Some(try_stack)
+			} else {
+				None
+			};
+			contexts.push(ContextSnapshot {
+				shared_states,
+				instruction_pointer,
+				rv_count,
+				local_variables,
+				arguments,
+				try_stack,
+			});
+		}
+
+		let result_stack = read_indexes(reader)?;
+		let uncaught_exception = read_optional_indexes(reader)?.and_then(|e| e.first().copied());
+		if !reader.is_at_end() {
+			return Err(invalid_snapshot("unexpected trailing bytes".to_string()))
+		}
+		Ok(Self { limits, state, items, shared_states, contexts, result_stack, uncaught_exception })
+	}
+}
+
+fn invalid_snapshot(message: String) -> VMError {
+	VMError::InvalidParameter(format!("Invalid snapshot: {message}"))
+}
+
+fn write_indexes(writer: &mut Vec<u8>, indexes: &[usize]) {
+	write_var_int(writer, indexes.len() as u64);
+	for &index in indexes {
+		write_var_int(writer, index as u64);
+	}
+}
+
+fn write_optional_indexes(writer: &mut Vec<u8>, indexes: &Option<Vec<usize>>) {
+	match indexes {
+		None => writer.push(0),
+		Some(indexes) => {
+			writer.push(1);
+			write_indexes(writer, indexes);
+		},
+	}
+}
+
+fn read_usize(reader: &mut Reader) -> Result<usize, VMError> {
+	Ok(reader.read_var_int(usize::MAX as u64)? as usize)
+}
+
+fn read_i32(reader: &mut Reader) -> Result<i32, VMError> {
+	Ok(i32::from_le_bytes(reader.read_bytes(4)?.try_into().unwrap()))
+}
+
+fn read_bool(reader: &mut Reader) -> Result<bool, VMError> {
+	match reader.read_u8()? {
+		0 => Ok(false),
+		1 => Ok(true),
+		value => Err(invalid_snapshot(format!("invalid boolean {value}"))),
+	}
+}
+
+fn read_indexes(reader: &mut Reader) -> Result<Vec<usize>, VMError> {
+	let count = read_usize(reader)?;
+	let mut indexes = Vec::new();
+	for _ in 0..count {
+		indexes.push(read_usize(reader)?);
+	}
+	Ok(indexes)
+}
+
+fn read_optional_indexes(reader: &mut Reader) -> Result<Option<Vec<usize>>, VMError> {
+	if read_bool(reader)? {
+		Ok(Some(read_indexes(reader)?))
+	} else {
+		Ok(None)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::op_code::OpCode;
+
+	/// A script calling a function that stores its arguments in a local and a static field.
+	fn script() -> Vec<u8> {
+		vec![
+			OpCode::InitSSLot as u8,
+			0x01,
+			OpCode::Push5 as u8,
+			OpCode::Push1 as u8,
+			OpCode::Pack as u8,
+			OpCode::StSFLd0 as u8,
+			OpCode::Push2 as u8,
+			OpCode::Push3 as u8,
+			OpCode::Call as u8,
+			0x03,
+			OpCode::Ret as u8,
+			OpCode::InitSlot as u8,
+			0x01,
+			0x02,
+			OpCode::LdArg0 as u8,
+			OpCode::LdArg1 as u8,
+			OpCode::Mul as u8,
+			OpCode::StLoc0 as u8,
+			OpCode::LdLoc0 as u8,
+			OpCode::LdSFLd0 as u8,
+			OpCode::Ret as u8,
+		]
+	}
+
+	fn load(script: Vec<u8>) -> ExecutionEngine {
+		let mut engine = ExecutionEngine::new();
+		engine.load_script(Script::new(script), -1, 0).unwrap();
+		engine
+	}
+
+	#[test]
+	fn test_restore_mid_execution() {
+		let mut expected = load(script());
+		assert_eq!(expected.execute(), VMState::Halt);
+
+		for steps in 1..17 {
+			let mut engine = load(script());
+			for _ in 0..steps {
+				engine.execute_next().unwrap();
+			}
+			let references = engine.reference_counter.borrow().count();
+			let bytes = engine.snapshot().to_bytes().unwrap();
+			drop(engine);
+
+			let snapshot = EngineSnapshot::from_bytes(&bytes).unwrap();
+			let mut restored = ExecutionEngine::restore(
+				snapshot,
+				Rc::new(JumpTable::new()),
+				InteropService::new(),
+			)
+			.unwrap();
+			assert_eq!(restored.reference_counter.borrow().count(), references, "step {steps}");
+			assert_eq!(restored.execute(), VMState::Halt, "step {steps}");
+
+			let expected_results = expected.snapshot();
+			let results = restored.snapshot();
+			assert_eq!(results.result_stack.len(), expected_results.result_stack.len());
+			assert_eq!(format!("{:?}", results.items), format!("{:?}", expected_results.items));
+		}
+	}
+
+	#[test]
+	fn test_rollback() {
+		let mut engine = load(vec![
+			OpCode::Push1 as u8,
+			OpCode::Push2 as u8,
+			OpCode::Push1 as u8,
+			OpCode::Pack as u8,
+			OpCode::Add as u8,
+			OpCode::Drop as u8,
+		]);
+		engine.register_syscall(0x01020304, |_| Ok(()));
+		engine.execute_with_limit(4).unwrap();
+		let snapshot = engine.snapshot();
+		let stack = |engine: &ExecutionEngine| {
+			let stack = engine.evaluation_stack().unwrap();
+			let stack = stack.borrow();
+			stack.iter().map(|item| item.borrow().get_type()).collect::<Vec<_>>()
+		};
+		let before = stack(&engine);
+		assert_eq!(before, [StackItemType::Integer, StackItemType::Array]);
+		let references = engine.reference_counter.borrow().count();
+
+		// ADD faults on the array, leaving the engine unusable.
+		assert_eq!(engine.execute(), VMState::Fault);
+		engine.rollback(snapshot.clone()).unwrap();
+		assert_eq!(engine.state, VMState::None);
+		assert_eq!(stack(&engine), before);
+		assert_eq!(engine.reference_counter.borrow().count(), references);
+		assert_eq!(engine.registered_syscalls(), vec![0x01020304]);
+
+		// The snapshot can be rolled back to again.
+		engine.rollback(snapshot).unwrap();
+		assert_eq!(stack(&engine), before);
+	}
+
+	#[test]
+	fn test_rollback_with_pointer() {
+		let mut engine = load(vec![
+			OpCode::PushA as u8,
+			0x08,
+			0x00,
+			0x00,
+			0x00,
+			OpCode::Nop as u8,
+			OpCode::CallA as u8,
+			OpCode::Ret as u8,
+			OpCode::Push1 as u8,
+			OpCode::Ret as u8,
+		]);
+		engine.execute_next().unwrap();
+		let snapshot = engine.snapshot();
+		engine.rollback(snapshot).unwrap();
+
+		// The pointer refers to the restored script rather than the one of the snapshot.
+		let pointer = engine.peek(0).unwrap();
+		if let StackItem::Pointer { script, position } = &*pointer.borrow() {
+			let context = engine.current_context.as_ref().unwrap();
+			assert!(Rc::ptr_eq(script, &context.borrow().script()));
+			assert_eq!(*position, 8);
+		} else {
+			panic!("expected a pointer")
+		}
+
+		assert_eq!(engine.execute(), VMState::Halt);
+		let result = engine.result_stack.borrow().peek(0).unwrap();
+		assert_eq!(result.borrow().get_integer().unwrap(), 1.into());
+	}
+
+	#[test]
+	fn test_shared_items_survive() {
+		let mut engine = load(vec![
+			OpCode::NewArray0 as u8,
+			OpCode::Dup as u8,
+			OpCode::Dup as u8,
+			OpCode::Push1 as u8,
+			OpCode::Pack as u8,
+		]);
+		engine.execute();
+		let snapshot = engine.snapshot();
+		assert_eq!(snapshot.items.len(), 2);
+		let restored =
+			ExecutionEngine::restore(snapshot, Rc::new(JumpTable::new()), InteropService::new())
+				.unwrap();
+		let results = restored.result_stack.borrow();
+		let outer = results.peek(0).unwrap();
+		let inner = results.peek(1).unwrap();
+		let StackItem::Array(items) = &*outer.borrow() else { panic!("expected an array") };
+		assert!(Rc::ptr_eq(&items[0], &inner));
+	}
+
+	#[test]
+	fn test_map_key_must_be_primitive() {
+		let mut engine = load(vec![OpCode::NewMap as u8, OpCode::NewArray0 as u8]);
+		engine.execute();
+		let restore = |snapshot: EngineSnapshot| {
+			let bytes = snapshot.to_bytes().unwrap();
+			let snapshot = EngineSnapshot::from_bytes(&bytes).unwrap();
+			ExecutionEngine::restore(snapshot, Rc::new(JumpTable::new()), InteropService::new())
+		};
+		assert!(matches!(engine.snapshot().items[0], SnapshotItem::Map(_)));
+
+		// A map keyed by itself.
+		let mut snapshot = engine.snapshot();
+		snapshot.items[0] = SnapshotItem::Map(vec![(0, 1)]);
+		assert!(matches!(restore(snapshot), Err(VMError::InvalidType(_))));
+
+		// A map keyed by an array.
+		let mut snapshot = engine.snapshot();
+		snapshot.items[0] = SnapshotItem::Map(vec![(1, 1)]);
+		assert!(matches!(restore(snapshot), Err(VMError::InvalidType(_))));
+
+		// A map keyed by a missing item.
+		let mut snapshot = engine.snapshot();
+		snapshot.items[0] = SnapshotItem::Map(vec![(2, 1)]);
+		assert!(matches!(restore(snapshot), Err(VMError::InvalidParameter(_))));
+	}
+
+	#[test]
+	fn test_interop_interface_cannot_be_encoded() {
+		let mut engine = load(vec![]);
+		engine.push(StackItem::InteropInterface(Rc::new(1u8)).into()).unwrap();
+		assert!(engine.snapshot().to_bytes().is_err());
+		assert!(EngineSnapshot::from_bytes(&[0x00]).is_err());
+	}
+}
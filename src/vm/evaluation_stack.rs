@@ -1,126 +1,204 @@
-use std::cell::RefCell;
-use std::collections::VecDeque;
-use std::rc::Rc;
-use crate::reference_counter::ReferenceCounter;
-use crate::stack_item::StackItem;
+use crate::{reference_counter::ReferenceCounter, stack_item::StackItem, vm_error::VMError};
+use std::{cell::RefCell, rc::Rc};
 
+/// Represents the evaluation stack in the VM.
+#[derive(Debug)]
 pub struct EvaluationStack {
-	inner_list: VecDeque<Rc<RefCell<dyn StackItem>>>,
+	inner_list: Vec<Rc<RefCell<StackItem>>>,
 	reference_counter: Rc<RefCell<ReferenceCounter>>,
 }
 
 impl EvaluationStack {
-
 	pub fn new(reference_counter: Rc<RefCell<ReferenceCounter>>) -> Self {
-		Self {
-			inner_list: VecDeque::new(),
-			reference_counter,
-		}
+		Self { inner_list: Vec::new(), reference_counter }
+	}
+
+	/// Gets the number of items on the stack.
+	pub fn count(&self) -> usize {
+		self.inner_list.len()
 	}
 
 	pub fn clear(&mut self) {
 		for item in &self.inner_list {
-			self.reference_counter.remove_stack_reference(item);
+			self.reference_counter.borrow_mut().remove_stack_reference(item);
 		}
 		self.inner_list.clear();
 	}
 
-	pub fn copy_to(&self, stack: &mut EvaluationStack, count: i32) {
-		if count < -1 || count as usize > self.inner_list.len() {
-			panic!("Argument out of range");
+	/// Copies the top `count` items to `stack`, or all of them when `count` is -1.
+	pub fn copy_to(&self, stack: &mut EvaluationStack, count: i32) -> Result<(), VMError> {
+		if count < -1 || count > self.inner_list.len() as i32 {
+			return Err(VMError::InvalidParameter(format!("Argument out of range: {count}")))
 		}
 		if count == 0 {
-			return;
+			return Ok(())
 		}
 		if count == -1 || count as usize == self.inner_list.len() {
-			stack.inner_list.extend(&self.inner_list);
+			stack.inner_list.extend(self.inner_list.iter().cloned());
 		} else {
 			let start = self.inner_list.len() - count as usize;
-			stack.inner_list.extend(&self.inner_list[start..]);
+			stack.inner_list.extend(self.inner_list[start..].iter().cloned());
 		}
+		Ok(())
 	}
 
-	pub fn insert(&mut self, index: usize, item: Rc<RefCell<dyn StackItem>>) {
+	/// Inserts an item so that it ends up at `index` from the top of the stack, matching the
+	/// indexing of `peek`: 0 pushes it onto the top and `count()` places it at the bottom.
+	pub fn insert(&mut self, index: usize, item: Rc<RefCell<StackItem>>) -> Result<(), VMError> {
 		if index > self.inner_list.len() {
-			panic!("Insert out of bounds");
+			return Err(VMError::InvalidParameter(format!(
+				"Insert out of bounds: {index}/{}",
+				self.inner_list.len()
+			)))
 		}
 		self.inner_list.insert(self.inner_list.len() - index, item);
-		self.reference_counter.add_stack_reference(&item);
+		self.reference_counter.borrow_mut().add_stack_reference(1);
+		Ok(())
 	}
 
-	pub fn move_to(&mut self, stack: &mut EvaluationStack, count: i32) {
+	/// Moves the top `count` items to `stack`, or all of them when `count` is -1.
+	pub fn move_to(&mut self, stack: &mut EvaluationStack, count: i32) -> Result<(), VMError> {
 		if count == 0 {
-			return;
+			return Ok(())
 		}
-		self.copy_to(stack, count);
+		self.copy_to(stack, count)?;
 		if count == -1 || count as usize == self.inner_list.len() {
 			self.inner_list.clear();
 		} else {
 			let end = self.inner_list.len() - count as usize;
-			self.inner_list.drain(end..);
+			self.inner_list.truncate(end);
 		}
+		Ok(())
 	}
 
-	pub fn peek(&self, index: i32) -> Rc<RefCell<dyn StackItem>> {
-		let index = index as isize;
-		if index >= self.inner_list.len() as isize {
-			panic!("Peek out of bounds");
-		}
-		if index < 0 {
-			let index = self.inner_list.len() as isize + index;
-			if index < 0 {
-				panic!("Peek out of bounds");
-			}
-		}
-		self.inner_list.get((self.inner_list.len() as isize - index - 1) as usize).unwrap().clone()
+	/// Returns the item at `index` from the top of the stack without removing it. Negative
+	/// indexes count from the bottom of the stack.
+	pub fn peek(&self, index: i32) -> Result<Rc<RefCell<StackItem>>, VMError> {
+		let position = self.position(index).ok_or_else(|| {
+			VMError::InvalidParameter(format!("Peek out of bounds: {index}/{}", self.count()))
+		})?;
+		Ok(self.inner_list[position].clone())
 	}
 
-	pub fn push(&mut self, item: Rc<RefCell<dyn StackItem>>) {
-		self.inner_list.push_back(item);
-		self.reference_counter.add_stack_reference(&item);
+	pub fn push(&mut self, item: Rc<RefCell<StackItem>>) {
+		self.inner_list.push(item);
+		self.reference_counter.borrow_mut().add_stack_reference(1);
 	}
 
-	pub fn reverse(&mut self, n: i32) {
-		let n = n as usize;
-		if n < 0 || n > self.inner_list.len() {
-			panic!("Argument out of range");
+	/// Reverses the order of the top `n` items on the stack.
+	pub fn reverse(&mut self, n: i32) -> Result<(), VMError> {
+		if n < 0 || n as usize > self.inner_list.len() {
+			return Err(VMError::InvalidParameter(format!("Argument out of range: {n}")))
 		}
 		if n <= 1 {
-			return;
+			return Ok(())
 		}
-		let end = self.inner_list.len() - n;
-		self.inner_list.make_contiguous().reverse();
+		let start = self.inner_list.len() - n as usize;
+		self.inner_list[start..].reverse();
+		Ok(())
 	}
 
-	pub fn pop(&mut self) -> Rc<RefCell<dyn StackItem>> {
+	/// Iterates over the items from the bottom of the stack to the top.
+	pub(crate) fn iter(&self) -> impl Iterator<Item = &Rc<RefCell<StackItem>>> {
+		self.inner_list.iter()
+	}
+
+	/// Removes and returns the item at the top of the stack.
+	pub fn pop(&mut self) -> Result<Rc<RefCell<StackItem>>, VMError> {
 		self.remove(0)
 	}
 
-	pub fn pop_typed<T: StackItem>(&mut self) -> T {
-		self.remove::<T>(0)
+	/// Removes and returns the item at `index` from the top of the stack, or from the bottom
+	/// when `index` is negative.
+	pub fn remove(&mut self, index: i32) -> Result<Rc<RefCell<StackItem>>, VMError> {
+		let position = self.position(index).ok_or_else(|| {
+			VMError::InvalidParameter(format!("Remove out of bounds: {index}/{}", self.count()))
+		})?;
+		let item = self.inner_list.remove(position);
+		self.reference_counter.borrow_mut().remove_stack_reference(&item);
+		Ok(item)
 	}
 
-	pub fn remove<T: StackItem>(&mut self, index: i32) -> T {
-		let index = index as isize;
-		if index >= self.inner_list.len() as isize {
-			panic!("Argument out of range");
+	/// Maps an index counted from the top (or from the bottom when negative) to a position
+	/// in `inner_list`.
+	fn position(&self, index: i32) -> Option<usize> {
+		let len = self.inner_list.len() as i64;
+		let index = index as i64;
+		let index = if index < 0 { index + len } else { index };
+		if index < 0 || index >= len {
+			return None
 		}
-		if index < 0 {
-			let index = self.inner_list.len() as isize + index;
-			if index < 0 {
-				panic!("Argument out of range");
-			}
-		}
-		let index = self.inner_list.len() as isize - index - 1;
-		let item = self.inner_list.remove(index as usize).unwrap();
-		if !item.is::<T>() {
-			panic!("Invalid cast");
+		Some((len - index - 1) as usize)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn stack_of(values: &[i32]) -> EvaluationStack {
+		let mut stack = EvaluationStack::new(Rc::new(RefCell::new(ReferenceCounter::new())));
+		for &value in values {
+			stack.push(StackItem::from(value).into());
 		}
-		self.reference_counter.remove_stack_reference(&item);
-		item.try_into().unwrap()
+		stack
 	}
 
-	pub fn size(&self) -> usize {
-		self.inner_list.len()
+	fn values(stack: &EvaluationStack) -> Vec<StackItem> {
+		stack.iter().map(|item| item.borrow().clone()).collect()
 	}
-}
\ No newline at end of file
+
+	#[test]
+	fn test_insert() {
+		let mut stack = stack_of(&[1, 2, 3]);
+		stack.insert(0, StackItem::from(4).into()).unwrap();
+		assert_eq!(*stack.peek(0).unwrap().borrow(), StackItem::from(4));
+
+		stack.insert(stack.count(), StackItem::from(0).into()).unwrap();
+		assert_eq!(*stack.peek(-1).unwrap().borrow(), StackItem::from(0));
+
+		stack.insert(2, StackItem::from(9).into()).unwrap();
+		assert_eq!(*stack.peek(2).unwrap().borrow(), StackItem::from(9));
+		let expected = [0, 1, 2, 9, 3, 4].map(StackItem::from);
+		assert_eq!(values(&stack), expected);
+		assert_eq!(stack.reference_counter.borrow().count(), 6);
+	}
+
+	#[test]
+	fn test_reverse() {
+		let mut stack = stack_of(&[1, 2, 3, 4]);
+		stack.reverse(3).unwrap();
+		assert_eq!(values(&stack), [1, 4, 3, 2].map(StackItem::from));
+		stack.reverse(0).unwrap();
+		stack.reverse(1).unwrap();
+		assert_eq!(values(&stack), [1, 4, 3, 2].map(StackItem::from));
+		assert!(matches!(stack.reverse(5), Err(VMError::InvalidParameter(_))));
+		assert!(matches!(stack.reverse(-1), Err(VMError::InvalidParameter(_))));
+
+		let mut empty = stack_of(&[]);
+		empty.reverse(0).unwrap();
+		assert!(empty.reverse(1).is_err());
+	}
+
+	#[test]
+	fn test_remove() {
+		let mut stack = stack_of(&[1, 2, 3, 4]);
+		assert_eq!(*stack.remove(1).unwrap().borrow(), StackItem::from(3));
+		assert_eq!(*stack.remove(-1).unwrap().borrow(), StackItem::from(1));
+		assert_eq!(values(&stack), [2, 4].map(StackItem::from));
+		assert!(matches!(stack.remove(2), Err(VMError::InvalidParameter(_))));
+		assert!(matches!(stack.remove(-3), Err(VMError::InvalidParameter(_))));
+		assert_eq!(stack.reference_counter.borrow().count(), 2);
+	}
+
+	#[test]
+	fn test_insert_out_of_bounds() {
+		let mut stack = stack_of(&[1, 2]);
+		assert!(matches!(
+			stack.insert(3, StackItem::from(3).into()),
+			Err(VMError::InvalidParameter(_))
+		));
+		assert_eq!(stack.count(), 2);
+		assert_eq!(stack.reference_counter.borrow().count(), 2);
+	}
+}
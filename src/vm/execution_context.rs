@@ -1,159 +1,207 @@
 use crate::{
 	evaluation_stack::EvaluationStack,
-	exception::exception_handling_context::ExceptionHandlingContext,
-	reference_counter::ReferenceCounter, slot::Slot, stack_item::StackItem, vm::script::Script,
+	exception::exception_handling_context::ExceptionHandlingContext, instruction::Instruction,
+	reference_counter::ReferenceCounter, slot::Slot, vm::script::Script, vm_error::VMError,
 };
-use std::{
-	any::{Any, TypeId},
-	cell::{Ref, RefCell},
-	collections::HashMap,
-	rc::Rc,
-};
-use crate::instruction::Instruction;
+use std::{cell::RefCell, rc::Rc};
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+/// Represents a frame in the VM execution stack.
+#[derive(Debug)]
 pub struct ExecutionContext {
-	pub shared_states: Rc<RefCell<SharedStates>>,
+	shared_states: Rc<RefCell<SharedStates>>,
+
+	/// The pointer indicating the current instruction.
 	pub instruction_pointer: usize,
 
 	/// The number of return values when this context returns.
 	pub rv_count: i32,
 
 	/// The local variables of this context.
-	pub local_variables: Option<Slot>,
+	pub local_variables: Option<Rc<RefCell<Slot>>>,
 
 	/// The arguments passed to this context.
-	pub arguments: Option<Slot>,
+	pub arguments: Option<Rc<RefCell<Slot>>>,
 
 	/// The try stack to handle exceptions.
 	pub try_stack: Option<Vec<ExceptionHandlingContext>>,
 }
 
-pub struct SharedStates {
-	pub(crate) script: Script,
-	pub(crate) evaluation_stack: Rc<RefCell<EvaluationStack>>,
-	pub(crate) static_fields: Option<Slot>,
-	pub(crate) states: HashMap<TypeId, Box<dyn Any>>,
+/// The states shared by a context and the contexts cloned from it by `OpCode::Call`.
+#[derive(Debug)]
+struct SharedStates {
+	script: Rc<RefCell<Script>>,
+	evaluation_stack: Rc<RefCell<EvaluationStack>>,
+	static_fields: Option<Rc<RefCell<Slot>>>,
 }
 
 impl ExecutionContext {
-	pub fn new(script: Script, reference_counter: Rc<RefCell<ReferenceCounter>>) -> Self {
+	pub fn new(
+		script: Script,
+		rv_count: i32,
+		reference_counter: Rc<RefCell<ReferenceCounter>>,
+	) -> Self {
 		let shared_states = SharedStates {
-			script,
-			evaluation_stack: Ref::new(RefCell::new(EvaluationStack::new(reference_counter))),
+			script: Rc::new(RefCell::new(script)),
+			evaluation_stack: Rc::new(RefCell::new(EvaluationStack::new(reference_counter))),
 			static_fields: None,
-			states: HashMap::new(),
 		};
 		Self {
-			shared_states:Rc::new(RefCell::new(shared_states)),
+			shared_states: Rc::new(RefCell::new(shared_states)),
 			instruction_pointer: 0,
-			rv_count: 0,
+			rv_count,
 			local_variables: None,
 			arguments: None,
 			try_stack: None,
 		}
 	}
 
-	// Other fields and methods
-
-	pub fn get_state<T: 'static>(&mut self) -> &mut T
-	where
-		T: Default + Any,
-	{
-		self.shared_states
-			.borrow()
-			.states
-			.entry(TypeId::of::<T>())
-			.or_insert_with(|| Box::new(Default::default()))
-			.downcast_mut::<T>()
-			.unwrap()
-	}
-
-	// pub fn peek(&self, index: usize) -> Rc<RefCell<dyn StackItem>> {
-	// 	let idx = self.items.len() - index - 1;
-	// 	&self.items[idx]
-	// }
-
-	// pub fn push(&mut self, item:Rc<RefCell<dyn StackItem>>) {
-	// 	self.items.push(item);
-	// 	self.reference_counter.add_stack_reference(&item);
-	// }
-	//
-	// pub fn pop(&mut self) -> Rc<RefCell<dyn StackItem>> {
-	// 	let item = self.items.pop().expect("stack empty");
-	// 	self.reference_counter.remove_stack_reference(&item);
-	// 	item
-	// }
-	//
-	// pub fn remove(&mut self, index: usize) -> Rc<RefCell<dyn StackItem>> {
-	// 	let idx = self.items.len() - index - 1;
-	// 	let item = self.items.remove(idx).expect("index out of bounds");
-	// 	self.reference_counter.remove_stack_reference(&item);
-	// 	item.try_into().unwrap()
-	// }
+	/// The script run by this context.
+	pub fn script(&self) -> Rc<RefCell<Script>> {
+		self.shared_states.borrow().script.clone()
+	}
 
+	/// The evaluation stack for this context.
 	pub fn evaluation_stack(&self) -> Rc<RefCell<EvaluationStack>> {
 		self.shared_states.borrow().evaluation_stack.clone()
 	}
-	pub fn evaluation_stack_mut(&mut self) -> Rc<RefCell<EvaluationStack>> {
-		self.shared_states.borrow().evaluation_stack.clone()
+
+	/// The slot used to store the static fields.
+	pub fn static_fields(&self) -> Option<Rc<RefCell<Slot>>> {
+		self.shared_states.borrow().static_fields.clone()
 	}
 
-	pub fn script(&self) -> &Script {
-		&self.shared_states.borrow().script
+	pub fn set_static_fields(&self, static_fields: Option<Rc<RefCell<Slot>>>) {
+		self.shared_states.borrow_mut().static_fields = static_fields;
 	}
-	pub fn script_mut(&mut self) -> &mut Script {
-		&mut self.shared_states.borrow().script
+
+	/// Removes every item from the evaluation stack, keeping the local variables, arguments and
+	/// static fields.
+	pub fn clear_evaluation_stack(&mut self) {
+		self.evaluation_stack().borrow_mut().clear();
 	}
 
-	pub fn fields(&self) -> Option<&Slot> {
-		self.shared_states.borrow().static_fields.as_ref()
+	/// Creates a context sharing the script, evaluation stack and static fields of this one,
+	/// starting at `initial_position`.
+	pub fn clone_at(&self, initial_position: usize) -> Self {
+		Self {
+			shared_states: self.shared_states.clone(),
+			instruction_pointer: initial_position,
+			rv_count: 0,
+			local_variables: None,
+			arguments: None,
+			try_stack: None,
+		}
 	}
 
-	pub fn fields_mut(&mut self) -> Option<&mut Slot> {
-		self.shared_states.borrow().static_fields.as_mut()
+	/// Indicates whether this context shares its script, evaluation stack and static fields
+	/// with `other`.
+	pub(crate) fn shares_states_with(&self, other: &ExecutionContext) -> bool {
+		Rc::ptr_eq(&self.shared_states, &other.shared_states)
 	}
-	pub fn states(&self) -> &HashMap<TypeId, Box<dyn Any>> {
-		&self.shared_states.borrow().states
+
+	/// Returns the current instruction, or `None` once the instruction pointer has reached
+	/// the end of the script.
+	pub fn current_instruction(&self) -> Result<Option<Rc<Instruction>>, VMError> {
+		self.get_instruction(self.instruction_pointer)
 	}
-	pub fn states_mut(&mut self) -> &mut HashMap<TypeId, Box<dyn Any>> {
-		&mut self.shared_states.borrow().states
+
+	/// Returns the instruction following the current one, if any.
+	pub fn next_instruction(&self) -> Result<Option<Rc<Instruction>>, VMError> {
+		match self.current_instruction()? {
+			Some(current) => self.get_instruction(self.instruction_pointer + current.size()),
+			None => Ok(None),
+		}
 	}
 
-	pub fn move_next(&mut self) {
-		self.instruction_pointer += 1;
+	/// Moves the instruction pointer to the next instruction.
+	pub fn move_next(&mut self) -> Result<(), VMError> {
+		if let Some(current) = self.current_instruction()? {
+			self.instruction_pointer += current.size();
+		}
+		Ok(())
+	}
 
-		if self.instruction_pointer >= self.script().len() {
-			self.instruction_pointer = 0;
+	fn get_instruction(&self, ip: usize) -> Result<Option<Rc<Instruction>>, VMError> {
+		let script = self.script();
+		let mut script = script.borrow_mut();
+		if ip >= script.len() {
+			return Ok(None)
 		}
+		Ok(Some(script.get_instruction(ip)?))
 	}
+}
 
-	pub fn clone(&self) -> Self {
-		Self::clone_with_ip(self, self.instruction_pointer)
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		execution_engine::ExecutionEngine, op_code::OpCode, stack_item::StackItem,
+		vm_state::VMState,
+	};
+
+	fn context(script: Vec<u8>) -> ExecutionContext {
+		ExecutionContext::new(
+			Script::new(script),
+			-1,
+			Rc::new(RefCell::new(ReferenceCounter::new())),
+		)
 	}
 
-	pub fn clone_with_ip(&self, ip: usize) -> Self {
-		let shared_states = Rc::clone(&self.shared_states);
+	#[test]
+	fn test_move_next_stops_at_end_of_script() {
+		let mut context = context(vec![OpCode::Push1 as u8, OpCode::Nop as u8]);
+		assert_eq!(context.next_instruction().unwrap().unwrap().opcode, OpCode::Nop);
+		context.move_next().unwrap();
+		assert_eq!(context.instruction_pointer, 1);
+		assert!(context.next_instruction().unwrap().is_none());
+		context.move_next().unwrap();
+		assert_eq!(context.instruction_pointer, 2);
+		assert!(context.current_instruction().unwrap().is_none());
+		context.move_next().unwrap();
+		assert_eq!(context.instruction_pointer, 2);
+	}
 
-		Self {
-			shared_states,
-			instruction_pointer: ip,
-			rv_count: 0,
+	#[test]
+	fn test_invalid_instruction_pointer() {
+		// The operand of PUSHDATA1 is truncated, and 0xff isn't an opcode.
+		let mut context = context(vec![OpCode::PushData1 as u8, 0x05, 0xff]);
+		assert!(matches!(context.current_instruction(), Err(VMError::InvalidOpcode(_))));
+		assert!(context.move_next().is_err());
+		assert_eq!(context.instruction_pointer, 0);
 
-			local_variables: self.local_variables.clone(),
-			arguments: self.arguments.clone(),
-			try_stack: self.try_stack.clone(),
-		}
-	}
+		context.instruction_pointer = 2;
+		assert!(matches!(context.current_instruction(), Err(VMError::InvalidOpcode(_))));
+		assert!(context.move_next().is_err());
 
-	// Get the current instruction
-	pub fn current_instruction(&self) -> &Instruction {
-		self.script().get_instruction(self.instruction_pointer)?
+		context.instruction_pointer = 10;
+		assert!(context.current_instruction().unwrap().is_none());
 	}
 
-	pub fn next_instruction(&self) -> &Instruction {
-		let next_ip = self.instruction_pointer + self.current_instruction().size();
-		self.script().get_instruction(next_ip)?
+	#[test]
+	fn test_clear_evaluation_stack() {
+		let script = vec![
+			OpCode::InitSlot as u8,
+			0x01,
+			0x00,
+			OpCode::Push5 as u8,
+			OpCode::StLoc0 as u8,
+			OpCode::Push1 as u8,
+			OpCode::Push2 as u8,
+			OpCode::LdLoc0 as u8,
+		];
+		let mut engine = ExecutionEngine::new();
+		engine.load_script(Script::new(script), -1, 0).unwrap();
+		for _ in 0..5 {
+			engine.execute_next().unwrap();
+		}
+		assert_eq!(engine.evaluation_stack().unwrap().borrow().count(), 2);
+
+		engine.context().unwrap().borrow_mut().clear_evaluation_stack();
+		assert_eq!(engine.evaluation_stack().unwrap().borrow().count(), 0);
+		assert_eq!(engine.reference_counter.borrow().count(), 1);
+
+		assert_eq!(engine.execute(), VMState::Halt);
+		assert_eq!(engine.result_stack.borrow().count(), 1);
+		assert_eq!(*engine.result_stack.borrow().peek(0).unwrap().borrow(), StackItem::from(5));
 	}
-	
 }
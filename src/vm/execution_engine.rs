@@ -1,40 +1,70 @@
 use crate::{
-	buffer::Buffer,
-	compound_types::{array::Array, compound_type::CompoundType, map::Map, Struct::Struct},
+	buffer_pool::BufferPool,
+	engine_observer::EngineObserver,
 	evaluation_stack::EvaluationStack,
 	exception::{
-		exception_handling_context::ExceptionHandlingContext,
-		exception_handling_state::ExceptionHandlingState,
+		exception_handling_context::ExceptionHandlingContext, exception_hooks::ExceptionHooks,
 	},
-	execution_context::{ExecutionContext, SharedStates},
+	execution_context::ExecutionContext,
 	execution_engine_limits::ExecutionEngineLimits,
+	gas_table::GasTable,
 	instruction::Instruction,
-	null::Null,
+	interop_service::InteropService,
+	jump_table::JumpTable,
+	method_token::{MethodToken, TokenResolver},
 	op_code::OpCode,
-	pointer::Pointer,
-	primitive_types::{byte_string::ByteString, primitive_type::PrimitiveType},
 	reference_counter::ReferenceCounter,
-	slot::Slot,
-	stack_item_type::StackItemType,
-	vm::{script::Script, vm_exception::VMException},
+	runtime::Notification,
+	stack_item::StackItem,
+	vm::script::{Script, ScriptHash},
+	vm_error::VMError,
 	vm_state::VMState,
 };
-use num_bigint::{BigInt, Sign};
-use num_traits::{FromBytes, Signed, ToPrimitive, Zero};
-use std::{
-	cell::{Ref, RefCell},
-	convert::TryInto,
-	fmt::Error,
-	ops::Neg,
-	rc::Rc,
-};
-use serde::Serialize;
-use crate::primitive_types::boolean::Boolean;
-use crate::primitive_types::integer::Integer;
-use crate::stack_item::StackItem;
+use num_bigint::BigInt;
+use std::{cell::RefCell, collections::HashSet, rc::Rc};
+
+/// A callback invoked when the VM halts, receiving the GAS left of the limit.
+pub type HaltHook = Rc<dyn Fn(&ExecutionEngine, i64)>;
+
+/// How a script run by `ExecutionEngine::dry_run` ended.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DryRunOutcome {
+	/// The script halted within the step budget.
+	Halted,
+
+	/// The script faulted within the step budget.
+	Faulted(VMError),
+
+	/// The step budget ran out before the script halted or faulted.
+	StepsExhausted,
+}
+
+/// Everything `ExecutionEngine::execute_detailed` observed while running a script.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExecutionResult {
+	/// The state the VM stopped in.
+	pub state: VMState,
+
+	/// The items on the result stack, from the bottom up.
+	pub results: Vec<StackItem>,
+
+	/// The GAS consumed by the executed instructions.
+	pub gas_consumed: i64,
+
+	/// The number of instructions executed.
+	pub steps: u64,
+
+	/// The error that faulted the VM, if it faulted.
+	pub fault_reason: Option<VMError>,
+
+	/// The events raised by `System.Runtime.Notify`.
+	pub notifications: Vec<Notification>,
+
+	/// The messages written by `System.Runtime.Log`.
+	pub logs: Vec<String>,
+}
 
 /// Represents the VM used to execute the script.
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
 pub struct ExecutionEngine {
 	/// Restrictions on the VM.
 	pub limits: ExecutionEngineLimits,
@@ -55,1566 +85,997 @@ pub struct ExecutionEngine {
 	pub result_stack: Rc<RefCell<EvaluationStack>>,
 
 	/// The VM object representing the uncaught exception.
-	pub uncaught_exception: Option<Rc<RefCell<dyn StackItem>>>,
+	pub uncaught_exception: Option<Rc<RefCell<StackItem>>>,
 
 	/// The current state of the VM.
 	pub state: VMState,
 
 	pub is_jumping: bool,
-}
 
-/// Interface implemented by objects that can be reference counted.
-pub trait ReferenceCounted {
-	/// Returns a unique ID for the object.
-	fn id(&self) -> usize;
+	/// The handlers used to execute each instruction.
+	pub jump_table: Rc<JumpTable>,
+
+	/// The pool the splice instructions draw buffers from, when enabled.
+	pub buffer_pool: Option<BufferPool>,
+
+	/// The interop services available through `OpCode::Syscall`.
+	pub interop_service: InteropService,
 
-	/// Free any resources used by the object.
-	fn free(&mut self);
+	/// The methods that can be called with `OpCode::CallT`, by index.
+	pub tokens: Vec<MethodToken>,
+
+	/// Loads the methods called with `OpCode::CallT`.
+	pub token_resolver: Option<TokenResolver>,
+
+	/// Callbacks observing the exception handling flow.
+	pub exception_hooks: ExceptionHooks,
+
+	/// The positions, by script, at which `execute` breaks before running the instruction.
+	pub breakpoints: HashSet<(ScriptHash, usize)>,
+
+	/// Whether `execute` stopped at a breakpoint, in which case the next call runs the
+	/// instruction there instead of breaking again.
+	pub(crate) stopped_at_breakpoint: bool,
+
+	/// The price charged for each executed instruction.
+	pub gas_table: GasTable,
+
+	/// The GAS consumed by the executed instructions.
+	pub gas_consumed: i64,
+
+	/// The GAS the VM may consume before it faults.
+	pub gas_limit: i64,
+
+	/// Called when the VM halts, e.g. so that the host can refund the unused GAS.
+	pub on_halt: Option<HaltHook>,
+
+	/// The number of instructions executed.
+	pub steps: u64,
+
+	/// The error that faulted the VM, if it faulted, as a `VMError::FaultAt` when the faulting
+	/// instruction is known.
+	pub last_error: Option<VMError>,
+
+	/// The messages written by `System.Runtime.Log`.
+	pub logs: Vec<String>,
+
+	/// The events raised by `System.Runtime.Notify`.
+	pub notifications: Vec<Notification>,
+
+	/// Notified of the executed instructions and the state changes.
+	pub observer: Option<Box<dyn EngineObserver>>,
 }
 
-impl<T> ReferenceCounted for T
-where
-	T: Sized + PartialEq,
-{
-	fn id(&self) -> usize {
-		self as *const T as usize
+impl Default for ExecutionEngine {
+	fn default() -> Self {
+		Self::new()
 	}
-
-	fn free(&mut self) {}
 }
 
 impl ExecutionEngine {
-	/// Constructs a new VM engine with default options.
+	/// Constructs a new `ExecutionEngine` with the default limits.
 	pub fn new() -> Self {
-		Self::with_options(ExecutionEngineLimits::default())
+		Self::new_with_limits(ExecutionEngineLimits::default())
 	}
 
-	/// Constructs a VM engine with the given options.
-	pub fn with_options(limits: ExecutionEngineLimits) -> Self {
+	/// Constructs a new `ExecutionEngine` with the specified limits.
+	pub fn new_with_limits(limits: ExecutionEngineLimits) -> Self {
+		let reference_counter = Rc::new(RefCell::new(ReferenceCounter::new()));
 		Self {
 			limits,
-			reference_counter: Ref::new(RefCell::new(ReferenceCounter::new())),
+			result_stack: Rc::new(RefCell::new(EvaluationStack::new(reference_counter.clone()))),
+			reference_counter,
 			invocation_stack: Vec::new(),
 			current_context: None,
 			entry_context: None,
-			result_stack: Ref::new(RefCell::new(EvaluationStack::new(Rc::new(RefCell::new(
-				ReferenceCounter::new(),
-			))))),
 			uncaught_exception: None,
 			state: VMState::Break,
 			is_jumping: false,
+			jump_table: Rc::new(JumpTable::default()),
+			buffer_pool: None,
+			interop_service: InteropService::new(),
+			tokens: Vec::new(),
+			token_resolver: None,
+			exception_hooks: ExceptionHooks::default(),
+			breakpoints: HashSet::new(),
+			stopped_at_breakpoint: false,
+			gas_table: GasTable::default(),
+			gas_consumed: 0,
+			gas_limit: i64::MAX,
+			on_halt: None,
+			steps: 0,
+			last_error: None,
+			logs: Vec::new(),
+			notifications: Vec::new(),
+			observer: None,
+		}
+	}
+
+	/// Registers `handler` as the interop service invoked by `OpCode::Syscall` with `hash`.
+	pub fn register_syscall<F>(&mut self, hash: u32, handler: F) -> &mut Self
+	where
+		F: Fn(&mut ExecutionEngine) -> Result<(), VMError> + 'static,
+	{
+		self.interop_service.register(hash, Rc::new(handler));
+		self
+	}
+
+	/// Sets the function loading the methods called with `OpCode::CallT`.
+	pub fn set_token_resolver<F>(&mut self, resolver: F) -> &mut Self
+	where
+		F: Fn(&mut ExecutionEngine, &MethodToken) -> Result<(), VMError> + 'static,
+	{
+		self.token_resolver = Some(Rc::new(resolver));
+		self
+	}
+
+	/// Sets the function called with the remaining GAS when the VM halts.
+	pub fn set_on_halt<F>(&mut self, hook: F) -> &mut Self
+	where
+		F: Fn(&ExecutionEngine, i64) + 'static,
+	{
+		self.on_halt = Some(Rc::new(hook));
+		self
+	}
+
+	/// Sets the observer notified of the executed instructions and the state changes.
+	pub fn set_observer<O: EngineObserver + 'static>(&mut self, observer: O) -> &mut Self {
+		self.observer = Some(Box::new(observer));
+		self
+	}
+
+	/// Throws `exception` as `OpCode::Throw` does, e.g. from an interop service, moving to the
+	/// nearest `catch` or `finally` block. Fails when no handler is left to take it, which
+	/// faults the VM when returned from an instruction or interop service.
+	pub fn throw(&mut self, exception: Rc<RefCell<StackItem>>) -> Result<(), VMError> {
+		let jump_table = self.jump_table.clone();
+		jump_table.execute_throw(self, exception)
+	}
+
+	/// The exception being thrown, or the one that faulted the VM, if any.
+	pub fn uncaught_exception(&self) -> Option<&Rc<RefCell<StackItem>>> {
+		self.uncaught_exception.as_ref()
+	}
+
+	/// Loads the method described by the token at `index` through the token resolver.
+	pub(crate) fn load_token(&mut self, index: u16) -> Result<(), VMError> {
+		let token = self.tokens.get(index as usize).cloned().ok_or_else(|| {
+			VMError::InvalidToken(format!("The token index {index} is out of range."))
+		})?;
+		let resolver = self
+			.token_resolver
+			.clone()
+			.ok_or_else(|| VMError::InvalidToken("No token resolver is set.".to_string()))?;
+		resolver(self, &token)
+	}
+
+	/// The hashes of the registered interop services, in ascending order.
+	pub fn registered_syscalls(&self) -> Vec<u32> {
+		self.interop_service.hashes()
+	}
+
+	/// The names of the registered interop services that were given one, in ascending order.
+	pub fn registered_syscall_names(&self) -> Vec<String> {
+		self.interop_service.names()
+	}
+
+	/// Enables pooling of the buffers created by the splice instructions, retaining at most
+	/// `max_buffers` released buffers for reuse.
+	///
+	/// A buffer is only released to the pool when DROP, CAT, SUBSTR, LEFT or RIGHT pops the last
+	/// reference to it. Buffers freed any other way, e.g. with the evaluation stack of a
+	/// returning context or as the element of a compound item, are deallocated as usual.
+	pub fn enable_buffer_pool(&mut self, max_buffers: usize) {
+		self.buffer_pool = Some(BufferPool::new(max_buffers));
+	}
+
+	/// Returns a zeroed buffer of `length` bytes, drawn from the buffer pool when it is enabled.
+	pub(crate) fn rent_buffer(&mut self, length: usize) -> Vec<u8> {
+		match &mut self.buffer_pool {
+			Some(pool) => pool.rent(length),
+			None => vec![0; length],
+		}
+	}
+
+	/// Fails when a newly produced primitive or buffer exceeds the size limits: `MaxItemSize`
+	/// for byte strings and buffers and the integer size limit for integers. Compound items are
+	/// bounded by `MaxStackSize` instead.
+	pub fn track_item_size(&self, item: &StackItem) -> Result<(), VMError> {
+		match item {
+			StackItem::Integer(value) => self.limits.assert_integer_limit(value),
+			StackItem::ByteString(_) | StackItem::Buffer(_) =>
+				self.limits.assert_max_item_size(item.size()?),
+			_ => Ok(()),
+		}
+	}
+
+	/// Returns the memory of a `Buffer` item to the buffer pool if `item` holds its last
+	/// reference. Only the instructions listed by `enable_buffer_pool` call this.
+	pub(crate) fn release_item(&mut self, item: Rc<RefCell<StackItem>>) {
+		let Some(pool) = &mut self.buffer_pool else { return };
+		if let Ok(item) = Rc::try_unwrap(item) {
+			if let StackItem::Buffer(buffer) = item.into_inner() {
+				pool.release(buffer);
+			}
 		}
 	}
 
-	/// Starts executing the loaded script.
+	/// Starts execution of the VM, returning the state it stopped in.
+	///
+	/// Execution stops with `VMState::Break` before an instruction at a breakpoint; calling
+	/// `execute` again runs that instruction and continues.
 	pub fn execute(&mut self) -> VMState {
+		let mut resuming = std::mem::take(&mut self.stopped_at_breakpoint);
 		if self.state == VMState::Break {
-			self.state = VMState::None;
+			self.set_state(VMState::None);
 		}
-
 		while self.state != VMState::Halt && self.state != VMState::Fault {
-			self.execute_next();
+			if !resuming && self.is_at_breakpoint() {
+				self.stopped_at_breakpoint = true;
+				self.set_state(VMState::Break);
+				break
+			}
+			resuming = false;
+			if let Err(err) = self.execute_next() {
+				self.on_fault(err);
+			}
 		}
-
 		self.state
 	}
 
-	/// Steps through executing a single instr.
-	///
-	fn execute_next(&mut self) {
-		if self.invocation_stack.is_empty() {
-			self.state = VMState::Halt;
-		} else {
-			let context = self.current_context?.borrow();
+	/// Starts execution of the VM like `execute`, and collects what it observed into an
+	/// `ExecutionResult`.
+	pub fn execute_detailed(&mut self) -> ExecutionResult {
+		let state = self.execute();
+		ExecutionResult {
+			state,
+			results: self.results().iter().map(|item| item.borrow().clone()).collect(),
+			gas_consumed: self.gas_consumed,
+			steps: self.steps,
+			fault_reason: self.last_error.clone(),
+			notifications: self.notifications.clone(),
+			logs: self.logs.clone(),
+		}
+	}
 
-			let instruction = context.current_instruction().unwrap_or(Instruction::RET);
+	/// Runs `script` on a fresh engine with the default limits, and returns the state it stopped
+	/// in and the items on its result stack, from the bottom up.
+	pub fn run(script: Vec<u8>) -> (VMState, Vec<Rc<RefCell<StackItem>>>) {
+		let mut engine = Self::new();
+		let state = match engine.load_script(Script::new(script), -1, 0) {
+			Ok(_) => engine.execute(),
+			Err(_) => VMState::Fault,
+		};
+		(state, engine.results())
+	}
 
-			self.pre_execute_instruction(instruction);
+	/// The items on the result stack, from the bottom up.
+	fn results(&self) -> Vec<Rc<RefCell<StackItem>>> {
+		let result_stack = self.result_stack.borrow();
+		(0..result_stack.count() as i32)
+			.rev()
+			.filter_map(|index| result_stack.peek(index).ok())
+			.collect()
+	}
 
-			match self.execute_instruction(instruction) {
-				Ok(_) => (),
-				Err(e) => Err(VMException::InvalidOpcode("{e}".parse().unwrap())), // self.on_fault(e),
+	/// Executes at most `max_steps` instructions and returns the state of the VM after them,
+	/// which is `VMState::None` if the budget ran out first. An error faults the VM and is
+	/// returned to the caller.
+	pub fn execute_with_limit(&mut self, max_steps: usize) -> Result<VMState, VMError> {
+		for _ in 0..max_steps {
+			if self.step()? == VMState::Halt {
+				break
 			}
+		}
+		Ok(self.state)
+	}
 
-			self.post_execute_instruction(instruction);
-			if !self.is_jumping {
-				self.current_context.unwrap().move_next();
-			}
+	/// Runs `script` on a fresh engine with the default limits for at most `max_steps`
+	/// instructions, e.g. to screen out scripts that don't finish quickly.
+	pub fn dry_run(script: Script, max_steps: usize) -> DryRunOutcome {
+		let mut engine = Self::new();
+		let state = engine
+			.load_script(script, -1, 0)
+			.and_then(|_| engine.execute_with_limit(max_steps));
+		match state {
+			Ok(VMState::Halt) => DryRunOutcome::Halted,
+			Ok(_) => DryRunOutcome::StepsExhausted,
+			Err(err) => DryRunOutcome::Faulted(err),
+		}
+	}
 
-			self.is_jumping = false;
+	/// Adds a breakpoint at `position` in `script`, returning `false` if it already exists.
+	pub fn add_breakpoint(&mut self, script: &Script, position: usize) -> bool {
+		self.breakpoints.insert((script.hash(), position))
+	}
+
+	/// Removes the breakpoint at `position` in `script`, returning `false` if there was none.
+	pub fn remove_breakpoint(&mut self, script: &Script, position: usize) -> bool {
+		self.breakpoints.remove(&(script.hash(), position))
+	}
+
+	/// Indicates whether the next instruction to execute is at a breakpoint.
+	fn is_at_breakpoint(&self) -> bool {
+		if self.breakpoints.is_empty() {
+			return false
 		}
+		let Some(context) = &self.current_context else { return false };
+		let context = context.borrow();
+		let hash = context.script().borrow().hash();
+		self.breakpoints.contains(&(hash, context.instruction_pointer))
 	}
 
-	fn pop(&mut self) -> Rc<RefCell<dyn StackItem>> {
-		self.current_context
-			.unwrap()
-			.get_mut()
-			.evaluation_stack()
-			.get_mut()
-			.pop()
+	/// Executes a single instruction and returns the state of the VM after it.
+	///
+	/// Nothing is executed once the VM has halted or faulted. An error faults the VM and is
+	/// returned to the caller.
+	pub fn step(&mut self) -> Result<VMState, VMError> {
+		if self.state == VMState::Halt || self.state == VMState::Fault {
+			return Ok(self.state)
+		}
+		if self.state == VMState::Break {
+			self.set_state(VMState::None);
+		}
+		self.stopped_at_breakpoint = false;
+		if let Err(err) = self.execute_next() {
+			self.on_fault(err.clone());
+			return Err(err)
+		}
+		Ok(self.state)
 	}
 
-	fn push(&mut self, item: Rc<RefCell<dyn StackItem>>) {
-		self.current_context
-			.unwrap()
-			.get_mut()
-			.evaluation_stack()
-			.get_mut()
-			.push(item);
+	/// Executes the next instruction.
+	pub(crate) fn execute_next(&mut self) -> Result<(), VMError> {
+		if self.invocation_stack.is_empty() {
+			self.set_state(VMState::Halt);
+			return Ok(())
+		}
+		let context = self.context()?;
+		let instruction = context.borrow().current_instruction()?;
+		let instruction = instruction.unwrap_or_else(|| Rc::new(Instruction::RET));
+
+		self.pre_execute_instruction(&instruction)?;
+		self.steps += 1;
+		let jump_table = self.jump_table.clone();
+		jump_table.execute(self, &instruction)?;
+		self.post_execute_instruction(&instruction)?;
+
+		if !self.is_jumping {
+			context.borrow_mut().move_next()?;
+		}
+		self.is_jumping = false;
+		Ok(())
 	}
 
-	fn peek(&self, index: usize) -> Rc<RefCell<dyn StackItem>> {
-		self.current_context
-			.unwrap()
-			.borrow()
-			.evaluation_stack()
-			.get_mut()
-			.peek(index as i32)
-			.unwrap()
-	}
-
-	fn execute_instr(&mut self, instr: Instruction) -> Result<VMState, VMException> {
-		match instr.opcode {
-			//Push
-			OpCode::PushInt8
-			| OpCode::PushInt16
-			| OpCode::PushInt32
-			| OpCode::PushInt64
-			| OpCode::PushInt128
-			| OpCode::PushInt256 => self.push(Rc::new(RefCell::new(Integer::new(&BigInt::from_be_bytes(instr.operand.as_slice()))))),
-			OpCode::PushTrue => self.push(Rc::new(RefCell::new(Boolean::new(true)))),
-			OpCode::PushFalse => self.push(Rc::new(RefCell::new(Boolean::new(false)))),
-			OpCode::PushA => {
-				let position = (self.current_context?.get_mut().instruction_pointer as i32)
-					.checked_add(instr.token_i32())
-					.unwrap();
-				if position < 0
-					|| position > self.current_context?.get_mut().script().len() as i32
-				{
-					// return Err(VMException::InvalidOpcode("Bad pointer address: {position}");
-					return Err(VMException::new(Error::new("Bad pointer address")))
-				}
-
-				self.push(
-					StackItem::VMPointer(Pointer::new(
-						&self.current_context?.get_mut().script(),
-						position as usize,
-					))
-					.into(),
-				)
-			},
-			OpCode::PushNull => self.push(StackItem::VMNull(Null::default()).into()),
-			OpCode::PushData1 | OpCode::PushData2 | OpCode::PushData4 => {
-				self.limits.assert_max_item_size(instr.operand.len() as u32);
-				self.push(StackItem::from(instr.operand).into())
-			},
-			OpCode::PushM1
-			| OpCode::Push0
-			| OpCode::Push1
-			| OpCode::Push2
-			| OpCode::Push3
-			| OpCode::Push4
-			| OpCode::Push5
-			| OpCode::Push6
-			| OpCode::Push7
-			| OpCode::Push8
-			| OpCode::Push9
-			| OpCode::Push10
-			| OpCode::Push11
-			| OpCode::Push12
-			| OpCode::Push13
-			| OpCode::Push14
-			| OpCode::Push15
-			| OpCode::Push16 => self.push(StackItem::VMInteger(instr.opcode - OpCode::Push0).into()),
-
-			// Control
-			OpCode::Nop => Ok(VMState::None),
-			OpCode::Jmp => self.execute_jump_offset(instr.token_i8() as i32),
-			OpCode::JmpL => self.execute_jump_offset(instr.token_i32()),
-			OpCode::JmpIf =>
-				if self.pop().get_bool() {
-					self.execute_jump_offset(instr.token_i8() as i32)
-				},
-			OpCode::JmpIfL =>
-				if self.pop().get_bool() {
-					self.execute_jump_offset(instr.token_i32())
-				},
-			OpCode::JmpIfNot =>
-				if !self.pop().get_bool() {
-					self.execute_jump_offset(instr.token_i8() as i32)
-				},
-			OpCode::JmpIfNotL =>
-				if !self.pop().get_bool() {
-					self.execute_jump_offset(instr.token_i32())
-				},
-			OpCode::JmpEq => {
-				let x2 = self.pop().get_integer();
-				let x1 = self.pop().get_integer();
-				if x1 == x2 {
-					self.execute_jump_offset(instr.token_i8() as i32)
-				}
-			},
-			OpCode::JmpEqL => {
-				let x2 = self.pop().get_integer();
-				let x1 = self.pop().get_integer();
-				if x1 == x2 {
-					self.execute_jump_offset(instr.token_i32())
-				}
-			},
-			OpCode::JmpNe => {
-				let x2 = self.pop().get_integer();
-				let x1 = self.pop().get_integer();
-				if x1 != x2 {
-					self.execute_jump_offset(instr.token_i8() as i32)
-				}
-			},
-			OpCode::JmpNeL => {
-				let x2 = self.pop().get_integer();
-				let x1 = self.pop().get_integer();
-				if x1 != x2 {
-					self.execute_jump_offset(instr.token_i32())
-				}
-			},
-			OpCode::JmpGt => {
-				let x2 = self.pop().get_integer();
-				let x1 = self.pop().get_integer();
-				if x1 > x2 {
-					self.execute_jump_offset(instr.token_i8() as i32)
-				}
-			},
-			OpCode::JmpGtL => {
-				let x2 = self.pop().get_integer();
-				let x1 = self.pop().get_integer();
-				if x1 > x2 {
-					self.execute_jump_offset(instr.token_i32())
-				}
-			},
-			OpCode::JmpGe => {
-				let x2 = self.pop().get_integer();
-				let x1 = self.pop().get_integer();
-				if x1 >= x2 {
-					self.execute_jump_offset(instr.token_i8() as i32)
-				}
-			},
-			OpCode::JmpGeL => {
-				let x2 = self.pop().get_integer();
-				let x1 = self.pop().get_integer();
-				if x1 >= x2 {
-					self.execute_jump_offset(instr.token_i32())
-				}
-			},
-			OpCode::JmpLt => {
-				let x2 = self.pop().get_integer();
-				let x1 = self.pop().get_integer();
-				if x1 < x2 {
-					self.execute_jump_offset(instr.token_i8() as i32)
-				}
-			},
-			OpCode::JmpLtL => {
-				let x2 = self.pop().get_integer();
-				let x1 = self.pop().get_integer();
-				if x1 < x2 {
-					self.execute_jump_offset(instr.token_i32())
-				}
-			},
-			OpCode::JmpLe => {
-				let x2 = self.pop().get_integer();
-				let x1 = self.pop().get_integer();
-				if x1 <= x2 {
-					self.execute_jump_offset(instr.token_i8() as i32)
-				}
-			},
-			OpCode::JmpLeL => {
-				let x2 = self.pop().get_integer();
-				let x1 = self.pop().get_integer();
-				if x1 <= x2 {
-					self.execute_jump_offset(instr.token_i32())
-				}
-			},
-			OpCode::Call => self.execute_call(
-				(self.current_context?.get_mut().instruction_pointer + instr.token_i8()) as i32,
-			),
-			OpCode::CallL => self
-				.execute_call((self.current_context?.get_mut().instruction_pointer + instr.token_i32()) as i32),
-			OpCode::CallA => {
-				let x: Pointer = self.pop().into();
-				if x.script() != self.current_context?.get_mut().script() {
-					return Err(VMException::InvalidOpcode(
-						"Pointers can't be shared between scripts".parse().unwrap(),
-					))
-				}
-				self.execute_call(x.position() as i32)
-			},
-			OpCode::CallT => self.load_token(instr.token_u16()),
-			OpCode::Abort =>
-				Err(VMException::InvalidOpcode("{OpCode::ABORT} is executed.".parse().unwrap())),
-			OpCode::Assert => {
-				let x = self.pop().get_bool();
-				if !x {
-					Err(VMException::InvalidOpcode(
-						"{OpCode::ASSERT} is executed with false result.".parse().unwrap(),
-					))
-				}
-				// break;
-			},
-			OpCode::Throw => self.execute_throw(self.pop()),
-			OpCode::Try => {
-				let catch_offset = instr.token_i8();
-				let finally_offset = instr.token_i8_1();
-				self.execute_try(catch_offset as usize, finally_offset as usize)
-				// break;
-			},
-			OpCode::TryL => {
-				let catch_offset = instr.token_i32();
-				let finally_offset = instr.token_i32_1();
-				self.execute_try(catch_offset as usize, finally_offset as usize)
-			},
-			OpCode::EndTry => {
-				let end_offset = instr.token_i8();
-				self.execute_end_try(end_offset as usize)
-			},
-			OpCode::EndTryL => {
-				let end_offset = instr.token_i32();
-				self.execute_end_try(end_offset as usize)
-			},
-			OpCode::EndFinally => {
-				if self.current_context?.get_mut().try_stack.is_none() {
-					return Err(VMException::InvalidOpcode(
-						"The corresponding TRY block cannot be found.".parse().unwrap(),
-					))
-				}
-				let current_try = match self.current_context?.get_mut().try_stack {
-					Some(ref mut x) => x,
-					None =>
-						return Err(VMException::InvalidOpcode(
-							"The corresponding TRY block cannot be found.".parse().unwrap(),
-						)),
-				};
-
-				if self.uncaught_exception.is_none() {
-					self.current_context?.get_mut().instruction_pointer = current_try.pop().EndPointer;
-				} else {
-					self.handle_exception();
-				}
-
-				self.is_jumping = true
-			},
-			OpCode::Ret => {
-				let mut context_pop = self.invocation_stack.pop().unwrap();
-				let stack_eval = match self.invocation_stack.len() == 0 {
-					true => self.result_stack.clone(),
-					false => self
-						.invocation_stack
-						.last()
-						.unwrap()
-						.borrow()
-						.evaluation_stack()
-						.clone(),
-				};
-				// }
-				// ? self.result_stack.clone() : self.invocation_stack.self.peek().EvaluationStack;
-				if context_pop.borrow().evaluation_stack() != stack_eval {
-					if context_pop.borrow().rv_count >= 0
-						&& context_pop.get_mut().evaluation_stack().get_mut().len() != context_pop.borrow().rv_count as usize
-					{
-						return Err(VMException::InvalidOpcode(
-							"RVCount doesn't match with EvaluationStack".parse().unwrap(),
-						))
-					}
-					context_pop.get_mut().evaluation_stack().CopyTo(stack_eval);
-				}
-				if self.invocation_stack.len() == 0 {
-					self.state = VMState::Halt;
-				}
-
-				self.unload_context(context_pop);
-				self.is_jumping = true
-				// break;
-			},
-			OpCode::Syscall => self.on_syscall(instr.token_u32()),
-
-			// Stack ops
-			OpCode::Depth => self.push(self.current_context?.get_mut().evaluation_stack().borrow().len()),
-			OpCode::Drop => self.pop(),
-			OpCode::Nip => self.current_context.unwrap().evaluation_stack().remove(1),
-			OpCode::Xdrop => {
-				let n = self.pop().get_integer().to_i32().unwrap();
-				if n < 0 {
-					return Err(VMException::InvalidOpcode(
-						"The negative value {n} is invalid for OpCode::{instr.OpCode}."
-							.parse()
-							.unwrap(),
-					))
-				}
-				self.current_context.unwrap().evaluation_stack().remove(n as i64)
-			},
-			OpCode::Clear => self.current_context.unwrap().evaluation_stack().Clear(),
-			OpCode::Dup => self.push(self.peek(0).clone()),
-			OpCode::Over => self.push(self.peek(1).clone()),
-			OpCode::Pick => {
-				let n = self.pop().get_integer();
-				if n < BigInt::zero() {
-					return Err(VMException::InvalidOpcode(
-						"The negative value {n} is invalid for OpCode::{instr.OpCode}."
-							.parse()
-							.unwrap(),
-					))
-				}
-				self.push(self.peek(n.to_i32().unwrap() as usize).clone())
-				// break;
-			},
-			OpCode::Tuck => self
-				.current_context?
-				.get_mut()
-				.evaluation_stack()
-				.get_mut()
-				.Insert(2, self.peek(0)),
-			OpCode::Swap => {
-				let x = self.current_context.unwrap().evaluation_stack().remove(1);
-				self.push(StackItem::from(x).into())
-				// break;
-			},
-			OpCode::Rot => {
-				let x = self.current_context.unwrap().evaluation_stack().remove(2);
-				self.push(StackItem::from(x).into())
-			},
-			OpCode::Roll => {
-				let n = self.pop().get_integer().to_i64().unwrap();
-				if n < 0 {
-					return Err(VMException::InvalidOpcode(
-						"The negative value {n} is invalid for OpCode::{instr.OpCode}."
-							.parse()
-							.unwrap(),
-					))
-				}
-				if n == 0 {
-					return Ok(VMState::None)
-				}
-				let x = self.current_context?.get_mut().evaluation_stack().remove(n);
-				self.push(StackItem::from(x).into())
-			},
-			OpCode::Reverse3 => self.current_context?.get_mut().evaluation_stack().Reverse(3),
-			OpCode::Reverse4 => self.current_context?.get_mut().evaluation_stack().Reverse(4),
-			OpCode::ReverseN => {
-				let n = self.pop().get_integer();
-				self.current_context?.get_mut().evaluation_stack().Reverse(n)
-			},
+	/// Called when an error is raised while executing an instruction.
+	fn on_fault(&mut self, err: VMError) {
+		let err = match (&err, self.current_position()) {
+			(VMError::FaultAt { .. }, _) | (_, None) => err,
+			(_, Some((ip, opcode))) => VMError::FaultAt { ip, opcode, reason: Box::new(err) },
+		};
+		self.last_error = Some(err);
+		self.set_state(VMState::Fault);
+	}
 
-			//Slot
-			OpCode::InitSSLot => {
-				if self.current_context?.get_mut().static_fields().is_some() {
-					return Err(VMException::InvalidOpcode(
-						"{instr.OpCode} cannot be executed twice.".parse().unwrap(),
-					))
-				}
-				if instr.token_u8() == 0 {
-					return Err(VMException::InvalidOpcode(
-						"The operand {instr.token_u8()} is invalid for OpCode::{instr.OpCode}."
-							.parse()
-							.unwrap(),
-					))
-				}
-				self.current_context?.get_mut().set_fields() = Some(
-					&Slot::new_with_count(instr.token_u8() as i32, self.reference_counter.clone()),
-				)
-				// break;
-			},
-			OpCode::InitSlot => {
-				if self.current_context?.get_mut().local_variables.is_some()
-					|| self.current_context?.get_mut().arguments.is_some()
-				{
-					return Err(VMException::InvalidOpcode(
-						"{instr.OpCode} cannot be executed twice.".parse().unwrap(),
-					))
-				}
-				if instr.token_u16() == 0 {
-					return Err(VMException::InvalidOpcode(
-						"The operand {instr.token_u16()} is invalid for OpCode::{instr.OpCode}."
-							.parse()
-							.unwrap(),
-					))
-				}
-				if instr.token_u8() > 0 {
-					self.current_context?.get_mut().local_variables = Some(Slot::new_with_count(
-						instr.token_u8() as i32,
-						self.reference_counter.clone(),
-					));
-				}
-				if instr.token_u8_1() > 0 {
-					// generate a vector of instr.token_u8_1() StackItems
-					let mut items = Vec::new();
-					let size = instr.token_u8_1() as usize;
-
-					// for _ in 0..size{
-					//     items.push(StackItem::default());
-					// }
-					//
-					for i in 0..size {
-						items[i] = self.pop();
-					}
-
-					self.current_context?.get_mut().arguments =
-						Some(Slot::new(items, self.reference_counter.clone()))
-				}
-			},
-			OpCode::LdSFLd0
-			| OpCode::LdSFLd1
-			| OpCode::LdSFLd2
-			| OpCode::LdSFLd3
-			| OpCode::LdSFLd4
-			| OpCode::LdSFLd5
-			| OpCode::LdSFLd6 => self.execute_load_from_slot(
-				&mut self.current_context?.get_mut().fields().unwrap(),
-				instr.opcode - OpCode::LdSFLd0,
-			),
-			OpCode::LdSFLd => self.execute_load_from_slot(
-				&mut self.current_context?.get_mut().fields().unwrap(),
-				instr.token_u8() as usize,
-			),
-			OpCode::StSFLd0
-			| OpCode::StSFLd1
-			| OpCode::StSFLd2
-			| OpCode::StSFLd3
-			| OpCode::StSFLd4
-			| OpCode::StSFLd5
-			| OpCode::StSFLd6 => self.execute_store_to_slot(
-				&mut self.current_context?.get_mut().fields()?,
-				instr.opcode - OpCode::StSFLd0,
-			),
-			OpCode::StSFLd => self.execute_store_to_slot(
-				&mut self.current_context?.get_mut().fields()?,
-				instr.token_u8() as usize,
-			),
-			OpCode::LdLoc0
-			| OpCode::LdLoc1
-			| OpCode::LdLoc2
-			| OpCode::LdLoc3
-			| OpCode::LdLoc4
-			| OpCode::LdLoc5
-			| OpCode::LdLoc6 => self.execute_load_from_slot(
-				& mut self.current_context?.get_mut().local_variables?,
-				instr.opcode - OpCode::LdLoc0,
-			),
-			OpCode::LdLoc => self.execute_load_from_slot(
-				&mut self.current_context?.get_mut().local_variables?,
-				instr.token_u8() as usize,
-			),
-			OpCode::StLoc0
-			| OpCode::StLoc1
-			| OpCode::StLoc2
-			| OpCode::StLoc3
-			| OpCode::StLoc4
-			| OpCode::StLoc5
-			| OpCode::StLoc6 => self.execute_store_to_slot(
-				&mut self.current_context?.get_mut().local_variables?,
-				instr.opcode - OpCode::StLoc0,
-			),
-			OpCode::StLoc => self.execute_store_to_slot(
-				&mut self.current_context?.get_mut().local_variables?,
-				instr.token_u8() as usize,
-			),
-			OpCode::LdArg0
-			| OpCode::LdArg1
-			| OpCode::LdArg2
-			| OpCode::LdArg3
-			| OpCode::LdArg4
-			| OpCode::LdArg5
-			| OpCode::LdArg6 => self.execute_load_from_slot(
-				&mut self.current_context?.get_mut().arguments.unwrap(),
-				instr.opcode - OpCode::LdArg0,
-			),
-			OpCode::LdArg => self.execute_load_from_slot(
-				&mut self.current_context?.get_mut().arguments.unwrap(),
-				instr.token_u8() as usize,
-			),
-			OpCode::StArg0
-			| OpCode::StArg1
-			| OpCode::StArg2
-			| OpCode::StArg3
-			| OpCode::StArg4
-			| OpCode::StArg5
-			| OpCode::StArg6 => self.execute_store_to_slot(
-				&mut self.current_context?.get_mut().arguments?,
-				instr.opcode - OpCode::StArg0,
-			),
-			OpCode::StArg => self.execute_store_to_slot(
-				&mut self.current_context?.get_mut().arguments?,
-				instr.token_u8() as usize,
-			),
-
-			// Splice
-			OpCode::NewBuffer => {
-				let length = self.pop().get_integer();
-				self.limits.assert_max_item_size(length.to_u32().unwrap());
-				self.push(StackItem::from(Buffer::new(length.to_usize().unwrap())).into())
-			},
-			OpCode::MemCpy => {
-				let count = self.pop().get_integer().to_i64().unwrap();
-				if count < 0 {
-					return Err(VMException::InvalidOpcode(
-						"The value {count} is out of range.".parse().unwrap(),
-					))
-				}
-				let si = self.pop().get_integer().to_i64().unwrap();
-				if si < 0 {
-					return Err(VMException::InvalidOpcode(
-						"The value {si} is out of range.".parse().unwrap(),
-					))
-				}
-				let src = self.pop().get_mut().get_slice();
-				if si.checked_add(count).unwrap() > src.len() as i64 {
-					return Err(VMException::InvalidOpcode(
-						"The value {count} is out of range.".parse().unwrap(),
-					))
-				}
-				let di = self.pop().get_mut().get_integer().to_i64().unwrap();
-				if di < 0 {
-					return Err(VMException::InvalidOpcode(
-						"The value {di} is out of range.".parse().unwrap(),
-					))
-				}
-				let dst: Buffer = self.pop().into();
-				if di.checked_add(count)? > dst.size() as i64 {
-					return Err(VMException::InvalidOpcode(
-						"The value {count} is out of range.".parse().unwrap(),
-					))
-				}
-				src.Slice(si, count).CopyTo(dst.get_slice()[di..])
-			},
-			OpCode::Cat => {
-				let x2 = self.pop().GetSpan();
-				let x1 = self.pop().GetSpan();
-				let length = x1.Length + x2.Length;
-				self.limits.assert_max_item_size(length);
-				let result = Buffer::new(length); //, false);
-				x1.CopyTo(result.get_slice());
-				x2.CopyTo(result.get_slice()[x1.Length..]);
-				self.push(StackItem::from(result).into())
-				// break;
-			},
-			OpCode::Substr => {
-				let count = self.pop().get_integer().to_usize().unwrap();
-				if count < 0 {
-					return Err(VMException::InvalidOpcode(
-						"The value {count} is out of range.".parse().unwrap(),
-					))
-				}
-				let index = self.pop().get_integer().to_usize().unwrap();
-				if index < 0 {
-					return Err(VMException::InvalidOpcode(
-						"The value {index} is out of range.".parse().unwrap(),
-					))
-				}
-				let x = self.pop().GetSpan();
-				if index + count > x.Length {
-					return Err(VMException::InvalidOpcode(
-						"The value {count} is out of range.".parse().unwrap(),
-					))
-				}
-				let result = Buffer::new(count); //, false);
-				x.Slice(index, count).CopyTo(result.get_slice());
-				self.push(StackItem::from(result).into())
-			},
-			OpCode::Left => {
-				let count = self.pop().get_integer().to_i32().unwrap();
-				if count < 0 {
-					return Err(VMException::InvalidOpcode(
-						"The value {count} is out of range.".parse().unwrap(),
-					))
-				}
-				let x = self.pop().GetSpan();
-				if count > x.Length {
-					return Err(VMException::InvalidOpcode(
-						"The value {count} is out of range.".parse().unwrap(),
-					))
-				}
-				let result = Buffer::new(count as usize); //, false);
-				x[..count].CopyTo(result.get_slice());
-				self.push(StackItem::from(result).into())
-			},
-			OpCode::Right => {
-				let count = self.pop().get_integer().to_i32().unwrap();
-				if count < 0 {
-					return Err(VMException::InvalidOpcode(
-						"The value {count} is out of range.".parse().unwrap(),
-					))
-				}
-				let x = self.pop().get_slice();
-				if count > x.Length {
-					return Err(VMException::InvalidOpcode(
-						"The value {count} is out of range.".parse().unwrap(),
-					))
-				}
-				let result = Buffer::from(x); //, false);
-							  // x[^count.. ^ 0].CopyTo(result.InnerBuffer.Span);
-				self.push(StackItem::from(result).into())
-				// break;
-			},
+	/// The position and opcode of the next instruction to execute, if there is a context.
+	fn current_position(&self) -> Option<(usize, OpCode)> {
+		let context = self.current_context.as_ref()?.borrow();
+		let opcode = match context.current_instruction() {
+			Ok(Some(instruction)) => instruction.opcode,
+			Ok(None) => OpCode::Ret,
+			Err(_) => return None,
+		};
+		Some((context.instruction_pointer, opcode))
+	}
 
-			// Bitwise logic
-			OpCode::Invert => {
-				let x = self.pop().get_integer();
-				self.push(StackItem::from(BigInt::neg(x)).into())
-			},
-			OpCode::And => {
-				let x2 = self.pop().get_integer();
-				let x1 = self.pop().get_integer();
-				self.push(StackItem::from(x1 & x2).into())
-			},
-			OpCode::Or => {
-				let x2 = self.pop().get_integer();
-				let x1 = self.pop().get_integer();
-				self.push(StackItem::from(x1 | x2).into())
-			},
-			OpCode::Xor => {
-				let x2 = self.pop().get_integer();
-				let x1 = self.pop().get_integer();
-				self.push(StackItem::from(x1 ^ x2).into())
-			},
-			OpCode::Equal => {
-				let x2 = self.pop();
-				let x1 = self.pop();
-				self.push(x1.Equal(x2, self.limits))
-			},
-			OpCode::NotEqual => {
-				let x2 = self.pop();
-				let x1 = self.pop();
-				self.push(!x1.Equals(x2, self.limits))
-			},
+	pub(crate) fn set_state(&mut self, state: VMState) {
+		if self.state != state {
+			let old = std::mem::replace(&mut self.state, state);
+			self.on_state_changed(old);
+		}
+	}
 
-			// Numeric
-			OpCode::Sign => {
-				let x = self.pop().get_integer();
-				self.push(StackItem::from(x.Sign).into())
-			},
-			OpCode::Abs => {
-				let x = self.pop().get_integer();
-				self.push(StackItem::from(BigInt::abs(&x)).into())
-			},
-			OpCode::Negate => {
-				let x = self.pop().get_integer();
-				self.push(StackItem::from(-x).into())
-			},
-			OpCode::Inc => {
-				let x = self.pop().get_integer();
-				self.push(StackItem::from(x + 1).into())
-			},
-			OpCode::Dec => {
-				let x = self.pop().get_integer();
-				self.push(StackItem::from(x - 1).into())
-			},
-			OpCode::Add => {
-				let x2 = self.pop().get_integer();
-				let x1 = self.pop().get_integer();
-				self.push(StackItem::from(x1 + x2).into())
-			},
-			OpCode::Sub => {
-				let x2 = self.pop().get_integer();
-				let x1 = self.pop().get_integer();
-				self.push(StackItem::from(x1 - x2).into())
-			},
-			OpCode::Mul => {
-				let x2 = self.pop().get_integer();
-				let x1 = self.pop().get_integer();
-				self.push(StackItem::from(x1 * x2).into())
-			},
-			OpCode::Div => {
-				let x2 = self.pop().get_integer();
-				let x1 = self.pop().get_integer();
-				self.push(StackItem::from(x1 / x2).into())
-			},
-			OpCode::Mod => {
-				let x2 = self.pop().get_integer();
-				let x1 = self.pop().get_integer();
-				self.push(StackItem::from(x1 % x2).into())
-			},
-			OpCode::Pow => {
-				let exponent = self.pop().get_integer().to_i32().unwrap();
-				self.limits.assert_shift(exponent);
-				let value = self.pop().get_integer();
-				self.push(StackItem::from(value.pow(exponent as u32)).into())
-			},
-			OpCode::Sqrt => self.push(self.pop().get_integer().Sqrt()),
-			OpCode::ModMul => {
-				let modulus = self.pop().get_integer();
-				let x2 = self.pop().get_integer();
-				let x1 = self.pop().get_integer();
-				self.push(StackItem::from(x1 * x2 % modulus).into())
-			},
-			OpCode::ModPow => {
-				let modulus = self.pop().get_integer();
-				let exponent = self.pop().get_integer().to_i32().unwrap();
-				let value = self.pop().get_integer();
-				let result = match exponent == -1 {
-					true => value.ModInverse(modulus),
-					false => value.ModPow(exponent, modulus),
-				};
-				// } value.ModInverse(modulus) :  BigInteger.ModPow(value, exponent, modulus);
-				self.push(StackItem::from(result).into())
-			},
-			OpCode::Shl => {
-				let shift = self.pop().get_integer().to_i32().unwrap();
-				self.limits.assert_shift(shift);
-				if shift == 0 {
-					return Ok(VMState::None)
-				}
-				let x = self.pop().get_integer();
-				self.push(StackItem::from(x << shift).into())
-			},
-			OpCode::Shr => {
-				let shift = self.pop().get_integer().to_i32().unwrap();
-				self.limits.assert_shift(shift);
-				if shift == 0 {
-					return Ok(VMState::None) // break;
-				}
-				let x = self.pop().get_integer();
-				self.push(StackItem::from(x >> shift).into())
-			},
-			OpCode::Not => {
-				let x = self.pop().get_bool();
-				self.push(StackItem::from(!x).into())
-			},
-			OpCode::BoolAnd => {
-				let x2 = self.pop().get_bool();
-				let x1 = self.pop().get_bool();
-				self.push(StackItem::from(x1 && x2).into())
-			},
-			OpCode::BoolOr => {
-				let x2 = self.pop().get_bool();
-				let x1 = self.pop().get_bool();
-				self.push(StackItem::from(x1 || x2).into())
-			},
-			OpCode::Nz => {
-				let x = self.pop().get_integer();
-				self.push(StackItem::from(!x.is_zero()).into())
-			},
-			OpCode::NumEqual => {
-				let x2 = self.pop().get_integer();
-				let x1 = self.pop().get_integer();
-				self.push(StackItem::from(x1 == x2).into())
-			},
-			OpCode::NumNotEqual => {
-				let x2 = self.pop().get_integer();
-				let x1 = self.pop().get_integer();
-				self.push(StackItem::from(x1 != x2).into())
-			},
-			OpCode::Lt => {
-				let x2 = self.pop();
-				let x1 = self.pop();
-				if x1.get_item_type() == StackItemType::Any
-					|| x2.get_item_type() == StackItemType::Any
-				{
-					self.push(StackItem::from(false).into())
-				} else {
-					self.push(StackItem::from(x1.get_integer() < x2.get_integer()).into())
-				}
-			},
-			OpCode::Le => {
-				let x2 = self.pop().borrow();
-				let x1 = self.pop().borrow();
-				if x1.get_type() == StackItemType::Any
-					|| x2.get_item_type() == StackItemType::Any
-				{
-					self.push(StackItem::from(false).into())
-				} else {
-					self.push(StackItem::from(x1.get_integer() <= x2.get_integer()).into())
-				}
-				// break;
-			},
-			OpCode::Gt => {
-				let x2 = self.pop().borrow();
-				let x1 = self.pop().borrow();
-				if x1.get_type() == StackItemType::Any
-					|| x2.get_item_type() == StackItemType::Any
-				{
-					self.push(StackItem::from(false).into())
-				} else {
-					self.push(StackItem::from(x1.get_integer() > x2.get_integer()).into())
-				}
-				// break;
-			},
-			OpCode::Ge => {
-				let x2 = self.pop();
-				let x1 = self.pop();
-				if x1.get_item_type() == StackItemType::Any
-					|| x2.get_item_type() == StackItemType::Any
-				{
-					self.push(StackItem::from(false).into())
-				} else {
-					self.push(StackItem::from(x1.get_integer() >= x2.get_integer()).into())
-				}
-				// break;
-			},
-			OpCode::Min => {
-				let x2 = self.pop().get_integer();
-				let x1 = self.pop().get_integer();
-				self.push(StackItem::from(BigInt::min(x1, x2)).into())
-			},
-			OpCode::Max => {
-				let x2 = self.pop().get_integer();
-				let x1 = self.pop().get_integer();
-				self.push(StackItem::from(BigInt::max(x1, x2)).into())
-				// break;
-			},
-			OpCode::Within => {
-				let b = self.pop().get_integer();
-				let a = self.pop().get_integer();
-				let x = self.pop().get_integer();
-				self.push(StackItem::from(a <= x && x < b).into())
-			},
+	/// Called when the state of the VM changes from `old`.
+	fn on_state_changed(&mut self, old: VMState) {
+		if let Some(observer) = &mut self.observer {
+			observer.on_state_changed(old, self.state);
+		}
+		if self.state == VMState::Halt {
+			if let Some(hook) = self.on_halt.clone() {
+				hook(self, self.gas_limit.saturating_sub(self.gas_consumed));
+			}
+		}
+	}
 
-			// Compound-type
-			OpCode::PackMap => {
-				let size = self.pop().get_integer().to_usize().unwrap();
-				if size < 0 || size * 2 > self.current_context?.get_mut().evaluation_stack().borrow().size() {
-					return Err(VMException::InvalidOpcode(
-						"The value {size} is out of range.".parse().unwrap(),
-					))
-				}
-				let map = Map::new(Some(self.reference_counter.clone()));
-				for i in 0..size {
-					let key: dyn PrimitiveType = self.pop().into();
-					let value = self.pop();
-					map[key] = value;
-				}
-				self.push(StackItem::VMMap(map).into())
-			},
-			OpCode::PackStruct => {
-				let size = self.pop().get_integer().to_i64().unwrap();
-				if size < 0 || size > self.current_context?.get_mut().evaluation_stack().borrow().size() {
-					return Err(VMException::InvalidOpcode(
-						"The value {size} is out of range.".parse().unwrap(),
-					))
-				}
-				let _struct = Struct::new(None, Some(self.reference_counter.clone()));
-				for i in 0..size {
-					let item = self.pop();
-					_struct.Add(item);
-				}
-				self.push(StackItem::VMStruct(_struct).into())
-				// break;
-			},
-			OpCode::Pack => {
-				let size = self.pop().get_integer().to_usize().unwrap();
-				if size < 0
-					|| size > self.current_context.unwrap().evaluation_stack().len()
-				{
-					return Err(VMException::InvalidOpcode(
-						"The value {size} is out of range.".parse().unwrap(),
-					))
-				}
-				let array = Array::new(None, Some(self.reference_counter.clone()));
-				for i in 0..size {
-					let item = self.pop();
-					array.Add(item);
-				}
-				self.push(StackItem::from(array).into())
-			},
-			OpCode::Unpack => {
-				let compound: dyn CompoundType = self.pop().into();
-				match compound {
-					CompoundType::VMMap(map) =>
-						for (key, value) in map.values().rev() {
-							self.push((value).into().into());
-							self.push((key).into());
-						},
-
-					// break;
-					CompoundType::VMArray(array) =>
-						for i in (0..=array.array.len()).rev() {
-							self.push(array[i].into());
-						},
-					// break;
-					_ =>
-						return Err(VMException::InvalidOpcode(
-							"Invalid type for {instr.OpCode}: {compound.Type}".parse().unwrap(),
-						)),
-				}
-				self.push(StackItem::from(compound.count()).into())
-			},
-			OpCode::NewArray0 => self.push(
-				StackItem::from(Array::new(None, Some(self.reference_counter.clone()))).into(),
-			),
-			OpCode::NewArray | OpCode::NewArrayT => {
-				let n = self.pop().get_integer().to_i64().unwrap();
-				if n < 0 || n > self.limits.max_stack_size {
-					return Err(VMException::InvalidOpcode(
-						"MaxStackSize exceed: {n}".parse().unwrap(),
-					))
-				}
-				let item: dyn StackItem;
-				if instr.opcode == OpCode::NewArrayT {
-					let _type = instr.token_u8();
-					if !StackItemType::is_valid(_type) {
-						return Err(VMException::InvalidOpcode(
-							"Invalid type for {instr.OpCode}: {instr.token_u8()}".parse().unwrap(),
-						))
-					}
-					item = match _type as StackItemType {
-						StackItemType::Boolean => StackItem::from(false),
-						StackItemType::Integer => StackItem::from(BigInt::zero()),
-						StackItemType::ByteString => StackItem::from(ByteString::new(Vec::new())),
-						_ => StackItem::from(Null::default()),
-					};
-				} else {
-					item = StackItem::VMNull(Null::default());
-				}
-				self.push(
-					StackItem::from(Array::new(
-						std::iter::repeat(item).take(n as usize).collect(),
-						Some(self.reference_counter.clone()),
-					))
-					.into(),
-				)
-			},
-			OpCode::NewStruct0 => self.push(
-				StackItem::from(Struct::new(None, Some(self.reference_counter.clone()))).into(),
-			),
-			OpCode::NewStruct => {
-				let n = self.pop().get_integer() as usize;
-				if n < 0 || n > self.limits.max_stack_size {
-					return Err(VMException::InvalidOpcode(
-						"MaxStackSize exceed: {n}".parse().unwrap(),
-					))
-				}
-				let result = Struct::new(None, Some(self.reference_counter.clone()));
-				for i in 0..n {
-					result.Add(StackItem::from(Null::default()));
-				}
-				self.push(StackItem::from(result).into())
-				// break;
-			},
-			OpCode::NewMap =>
-				self.push(StackItem::from(Map::new(Some(self.reference_counter.clone()))).into()),
-			OpCode::Size => {
-				let x = self.pop();
-				match x {
-					StackItem::VMArray(array) => self.push(StackItem::from(array.Count).into()),
-					StackItem::VMMap(map) => self.push(StackItem::from(map.Count).into()),
-					StackItem::VMStruct(_struct) =>
-						self.push(StackItem::from(_struct.Count).into()),
-					StackItem::VMByteString(array) => self.push(StackItem::from(array.Size).into()),
-					StackItem::VMBuffer(buffer) => self.push(StackItem::from(buffer.Size).into()),
-					StackItem::VMInteger(integer) =>
-						self.push(StackItem::from(integer.size()).into()),
-					_ =>
-						return Err(VMException::InvalidOpcode(
-							"Invalid type for {instr.OpCode}: {x.Type}".parse().unwrap(),
-						)),
-				}
-			},
-			OpCode::HasKey => {
-				let key: Rc<RefCell<dyn PrimitiveType>> = self.pop().into();
-				let x = self.pop();
-				match x {
-					StackItem::VMMap(map) =>
-						self.push(StackItem::from(map.contains_key(key)).into()),
-					StackItem::VMByteString(array) => {
-						let index = key.get_integer().to_u32().unwrap();
-						if index < 0 {
-							return Err(VMException::InvalidOpcode(
-								"The negative value {index} is invalid for OpCode::{instr.OpCode}."
-									.parse()
-									.unwrap(),
-							))
-						}
-						self.push(StackItem::from(index < array.size() as u32).into())
-					},
-					StackItem::VMBuffer(buffer) => {
-						let index = key.get_integer().to_u32().unwrap();
-						if index < 0 {
-							return Err(VMException::InvalidOpcode(
-								"The negative value {index} is invalid for OpCode::{instr.OpCode}."
-									.parse()
-									.unwrap(),
-							))
-						}
-						self.push(StackItem::from(index < buffer.size() as u32).into())
-					},
-					StackItem::VMArray(array) => {
-						let index = key.get_integer().to_u32().unwrap();
-						if index < 0 {
-							return Err(VMException::InvalidOpcode(
-								"The negative value {index} is invalid for OpCode::{instr.OpCode}."
-									.parse()
-									.unwrap(),
-							))
-						}
-
-						self.push(StackItem::from(index < array.count() as u32).into())
-					},
-					_ =>
-						return Err(VMException::InvalidOpcode(
-							"Invalid type for {instr.OpCode}: {x.Type}".parse().unwrap(),
-						)),
-				}
-				// break;
-			},
-			OpCode::Keys => {
-				let map: Map = self.pop().into();
-				self.push(
-					StackItem::from(Array::new(
-						Some(map.keys()),
-						Some(self.reference_counter.clone()),
-					))
-					.into(),
-				)
-			},
-			OpCode::Values => {
-				let x = self.pop();
-				let values = match x {
-					StackItem::VMArray(array) => array,
-					StackItem::VMMap(map) => map.values(),
-					_ => panic!(), //return Err(VMException::InvalidOpcode("Invalid type for {instr.OpCode}: {x.Type}".parse().unwrap())),
-				};
-				let mut new_array = Array::new(None, Some(self.reference_counter.clone()));
-				for item in values.array {
-					if item.get_item_type() == StackItemType::Struct {
-						let s: Struct = item.into();
-						new_array.add(s.clone(&self.limits).try_into().unwrap());
-
-					// new_array.Add(s.Clone(self.limits));
-					} else {
-						new_array.Add(item);
-					}
-				}
-
-				self.push(StackItem::from(new_array).into())
-			},
-			OpCode::PickItem => {
-				let key: Rc<RefCell<dyn PrimitiveType>> = self.pop().into();
-				let x = self.pop();
-				match x {
-					StackItem::VMArray(array) => {
-						let index = key.get_integer().to_i64().unwrap();
-						if index < 0 || index >= array.Count {
-							return Err(VMException::InvalidOpcode(
-								"The value {index} is out of range.".parse().unwrap(),
-							))
-						}
-						self.push(array[index])
-					},
-					StackItem::VMMap(map) => {
-						let value = match map.get(key) {
-							Some(v) => v,
-							None =>
-								return Err(VMException::InvalidOpcode(
-									"Key not found in {nameof(Map)}".parse().unwrap(),
-								)),
-						};
-						self.push(StackItem::from(value).into())
-					},
-					StackItem::VMByteString(byte_string) => {},
-					StackItem::VMBoolean(boolean) => {},
-					StackItem::VMInteger(integer) => {
-						let byte_array = integer.get_slice();
-						let index = key.get_integer().to_i64().unwrap();
-						if index < 0 || index >= byte_array.Length {
-							return Err(VMException::InvalidOpcode(
-								"The value {index} is out of range.".parse().unwrap(),
-							))
-						}
-						self.push(
-							StackItem::from(BigInt::from_bytes_le(
-								Sign::NoSign,
-								byte_array.get(index).unwrap(),
-							))
-							.into(),
-						)
-					},
-					StackItem::VMBuffer(buffer) => {
-						let index = key.get_integer().to_i64().unwrap();
-						if index < 0 || index >= buffer.Size {
-							return Err(VMException::InvalidOpcode(
-								"The value {index} is out of range.".parse().unwrap(),
-							))
-						}
-						self.push(
-							StackItem::from(BigInt::from_bytes_le(
-								Sign::NoSign,
-								buffer.get_slice().get(index).unwrap(),
-							))
-							.into(),
-						)
-					},
-					_ =>
-						return Err(VMException::InvalidOpcode(
-							"Invalid type for {instr.OpCode}: {x.Type}".parse().unwrap(),
-						)),
-				}
-				// break;
-			},
-			OpCode::Append => {
-				let mut new_item = self.pop();
-				let array: Array = self.pop().into();
-				if new_item.get_item_type() == StackItemType::Struct {
-					let s: Struct = new_item.into();
-					new_item = s.clone(&self.limits).try_into().unwrap();
-					// new_item = s.Clone(self.limits);
-				}
-				array.Add(new_item)
-			},
-			OpCode::SetItem => {
-				let mut value = self.pop();
-				if value.get_item_type() == StackItemType::Struct {
-					let s: Struct = value.into();
-					value = s.clone(&self.limits).try_into().unwrap();
-				}
-				let key: dyn PrimitiveType = self.pop().into();
-				let x = self.pop();
-				match x {
-					VMArray(array) => {
-						let index = key.get_integer().to_i32().unwrap();
-						if index < 0 || index >= array.Count {
-							return Err(VMException::InvalidOpcode(
-								"The value {index} is out of range.".parse().unwrap(),
-							))
-						}
-						array[index] = value
-					},
-					StackItem::VMMap(map) => map[key] = value,
-					StackItem::VMBuffer(buffer) => {
-						let index = key.get_integer().to_i32().unwrap();
-						if index < 0 || index >= buffer.Size {
-							return Err(VMException::InvalidOpcode(
-								"The value {index} is out of range.".parse().unwrap(),
-							))
-						}
-						if !StackItemType::is_primitive(value.get_item_type() as u8) {
-							return Err(VMException::InvalidOpcode(
-								"Value must be a primitive type in {instr.OpCode}".parse().unwrap(),
-							))
-						}
-						let b = value.get_integer().to_i64().unwrap();
-						if b < i8::min as i64 || b > i8::max as i64 {
-							return Err(VMException::InvalidOpcode(
-								"Overflow in {instr.OpCode}, {b} is not a byte type."
-									.parse()
-									.unwrap(),
-							))
-						}
-						buffer.InnerBuffer.Span[index] = b
-					},
-					_ => Err(VMException::InvalidOpcode(
-						"Invalid type for {instr.OpCode}: {x.Type}".parse().unwrap(),
-					)),
-				}
-				// break;
-			},
-			OpCode::ReverseItems => {
-				let x = self.pop();
-				match x {
-					StackItem::VMArray(array) => array.Reverse(),
-					StackItem::VMBuffer(buffer) => buffer.InnerBuffer.Span.Reverse(),
-					_ => Err(VMException::InvalidOpcode(
-						"Invalid type for {instr.OpCode}: {x.Type}".parse().unwrap(),
-					)),
-				}
-			},
-			OpCode::Remove => {
-				let key: Rc<RefCell<dyn PrimitiveType>> = self.pop().into();
-				let x = self.pop();
-				match x {
-					StackItem::VMArray(mut array) => {
-						let index = key.get_integer().to_i32().unwrap();
-						if index < 0 || index >= array.Count {
-							return Err(VMException::InvalidOpcode(
-								"The value {index} is out of range.".parse().unwrap(),
-							))
-						}
-						array.remove_at(index as usize)
-					},
-					StackItem::VMMap(mut map) => map.remove(key),
-					_ =>
-						return Err(VMException::InvalidOpcode(
-							"Invalid type for {instr.OpCode}: {x.Type}".parse().unwrap(),
-						)),
-				}
-			},
-			OpCode::ClearItems => {
-				let x: dyn CompoundType = self.pop().into();
-				x.Clear()
-			},
-			OpCode::PopItem => {
-				let mut x: Rc<RefCell<dyn CompoundType>> = self.pop();
-				let index = x.count() - 1;
-				self.push(x[index].clone());
-				x.remove_at(index)
-			},
+	/// Called when a `try` block is entered.
+	pub(crate) fn on_try_enter(&self, try_context: &ExceptionHandlingContext) {
+		if let Some(hook) = &self.exception_hooks.on_try_enter {
+			hook(self, try_context);
+		}
+	}
 
-			//Types
-			OpCode::IsNull => {
-				let x = self.pop();
-				self.push(StackItem::from(x.get_item_type() == StackItemType::Any).into())
-			},
-			OpCode::IsType => {
-				let x = self.pop();
-				let _type: StackItemType = instr.token_u8() as StackItemType;
-				if _type == StackItemType::Any || !StackItemType::is_valid(instr.token_u8()) {
-					return Err(VMException::InvalidOpcode("Invalid type: {type}".parse().unwrap()))
-				}
-				self.push(StackItem::from(x.get_item_type() == _type).into())
-			},
-			OpCode::Convert => {
-				let x = self.pop();
-				self.push(x.ConvertTo(instr.token_u8()))
-			},
-			OpCode::AbortMsg => {
-				let msg = self.pop().GetString();
-				Err(VMException::InvalidOpcode(
-					"{OpCode::ABORTMSG} is executed. Reason: {msg}".parse().unwrap(),
-				))
-			},
-			OpCode::AssertMsg => {
-				let msg = self.pop().GetString();
-				let x = self.pop().get_bool();
-				if !x {
-					return Err(VMException::InvalidOpcode(
-						"{OpCode::ASSERTMSG} is executed with false result. Reason: {msg}"
-							.parse()
-							.unwrap(),
-					))
-				}
-				// break;
-			},
-			_ => panic!("Opcode {instr} is undefined."),
+	/// Called when a `catch` block is entered.
+	pub(crate) fn on_catch_enter(&self, try_context: &ExceptionHandlingContext) {
+		if let Some(hook) = &self.exception_hooks.on_catch_enter {
+			hook(self, try_context);
 		}
+	}
 
-		Ok(VMState::Halt)
+	/// Called when a `finally` block is entered.
+	pub(crate) fn on_finally_enter(&self, try_context: &ExceptionHandlingContext) {
+		if let Some(hook) = &self.exception_hooks.on_finally_enter {
+			hook(self, try_context);
+		}
 	}
 
-	fn execute_call(&mut self, offset: i32) {
-		let new_context = self.current_context.unwrap().clone_at_offset(offset);
-		self.load_context(new_context);
+	/// Called before an instruction is executed.
+	fn pre_execute_instruction(&mut self, instruction: &Instruction) -> Result<(), VMError> {
+		if let Some(observer) = &mut self.observer {
+			observer.on_pre_execute(instruction);
+		}
+		self.add_gas(self.gas_table.price(instruction.opcode))
 	}
 
-	fn execute_jump_offset(&mut self, offset: i32) {
-		self.execute_jump(
-			(self.current_context?.borrow().instruction_pointer as i32)
-				.checked_add(offset)
-				.unwrap(),
-		)
+	/// Charges `gas`, failing when the total exceeds the GAS limit.
+	pub fn add_gas(&mut self, gas: i64) -> Result<(), VMError> {
+		self.gas_consumed = self.gas_consumed.saturating_add(gas);
+		if self.gas_consumed > self.gas_limit {
+			return Err(VMError::InsufficientGas(format!(
+				"{}/{}",
+				self.gas_consumed, self.gas_limit
+			)))
+		}
+		Ok(())
 	}
-	fn execute_jump(&mut self, offset: i32) {
-		let new_ip = (self.current_context?.borrow().instruction_pointer as i32 + offset) as usize;
-		if new_ip >= self.current_context?.borrow().script.0.len() {
-			return self.handle_error(Error::InvalidJump)
+
+	/// Called after an instruction is executed.
+	fn post_execute_instruction(&mut self, instruction: &Instruction) -> Result<(), VMError> {
+		if let Some(observer) = &mut self.observer {
+			observer.on_post_execute(instruction);
+		}
+		let mut count = self.reference_counter.borrow().count();
+		if count > self.limits.max_stack_size {
+			count = self.reference_counter.borrow_mut().check_zero_referred();
 		}
-		self.current_context?.borrow().instruction_pointer = new_ip;
+		if count > self.limits.max_stack_size {
+			return Err(VMError::StackOverflow(format!("MaxStackSize exceed: {count}")))
+		}
+		Ok(())
+	}
+
+	/// Creates a new context with the specified script without loading it.
+	fn create_context(
+		&self,
+		script: Script,
+		rv_count: i32,
+		initial_position: usize,
+	) -> ExecutionContext {
+		let mut context = ExecutionContext::new(script, rv_count, self.reference_counter.clone());
+		context.instruction_pointer = initial_position;
+		context
 	}
 
-	fn handle_error(&mut self, err: Error) {
-		self.state = VMState::Fault;
-		self.uncaught_exception = Some(StackItem::from(Null::default()).into());
+	/// Loads the specified script onto the invocation stack.
+	pub fn load_script(
+		&mut self,
+		script: Script,
+		rv_count: i32,
+		initial_position: usize,
+	) -> Result<Rc<RefCell<ExecutionContext>>, VMError> {
+		self.limits.assert_max_script_size(script.len())?;
+		let context =
+			Rc::new(RefCell::new(self.create_context(script, rv_count, initial_position)));
+		self.load_context(context.clone())?;
+		Ok(context)
 	}
 
-	fn load_context(&mut self, context: &Rc<RefCell<ExecutionContext>>) {
+	/// Loads the specified context into the invocation stack.
+	pub fn load_context(&mut self, context: Rc<RefCell<ExecutionContext>>) -> Result<(), VMError> {
+		if self.invocation_stack.len() >= self.limits.max_invocation_stack_size {
+			return Err(VMError::InvocationStackOverflow(format!(
+				"MaxInvocationStackSize exceed: {}",
+				self.invocation_stack.len()
+			)))
+		}
 		self.invocation_stack.push(context.clone());
-		self.current_context = Some(self.invocation_stack.last().unwrap().clone());
 		if self.entry_context.is_none() {
-			self.entry_context = self.current_context.clone();
+			self.entry_context = Some(context.clone());
 		}
+		self.current_context = Some(context);
+		Ok(())
 	}
 
-	fn unload_context(&mut self, mut context: Rc<RefCell<ExecutionContext>>) {
+	/// Called when a context is unloaded from the invocation stack.
+	pub(crate) fn unload_context(&mut self, context: Rc<RefCell<ExecutionContext>>) {
 		if self.invocation_stack.is_empty() {
 			self.current_context = None;
 			self.entry_context = None;
 		} else {
-			self.current_context = Some(self.invocation_stack.last().unwrap().clone());
+			self.current_context = self.invocation_stack.last().cloned();
 		}
-
-		if let Some(current) = &mut self.current_context {
-			if current.borrow().fields()
-				!= context.borrow().fields()
-			{
-				context.borrow().fields()?.clear_references();
+		let context = context.borrow();
+		// Items left on the stack of a frame unwound by a throw are released here.
+		let evaluation_stack = context.evaluation_stack();
+		let shared = self.current_context.as_ref().is_some_and(|current| {
+			Rc::ptr_eq(&current.borrow().evaluation_stack(), &evaluation_stack)
+		});
+		if !shared {
+			evaluation_stack.borrow_mut().clear();
+		}
+		if let Some(static_fields) = context.static_fields() {
+			let shared = self
+				.current_context
+				.as_ref()
+				.and_then(|current| current.borrow().static_fields())
+				.is_some_and(|current| Rc::ptr_eq(&current, &static_fields));
+			if !shared {
+				static_fields.borrow_mut().clear_references();
 			}
 		}
-		context.borrow().local_variables.unwrap().clear_references();
-		context.borrow().arguments.unwrap().clear_references();
+		if let Some(local_variables) = &context.local_variables {
+			local_variables.borrow_mut().clear_references();
+		}
+		if let Some(arguments) = &context.arguments {
+			arguments.borrow_mut().clear_references();
+		}
 	}
 
-	fn create_context(
-		&self,
-		script: Script,
-		rvcount: i32,
-		initial_position: usize,
-	) -> ExecutionContext {
-		let share = SharedStates {
-			script,
-			evaluation_stack: Default::default(),
-			static_fields: None,
-			states: Default::default(),
-		};
+	/// Returns the current context, failing when the invocation stack is empty.
+	pub(crate) fn context(&self) -> Result<Rc<RefCell<ExecutionContext>>, VMError> {
+		self.current_context
+			.clone()
+			.ok_or_else(|| VMError::Custom("The invocation stack is empty.".to_string()))
+	}
 
-		ExecutionContext {
-			shared_states,
-			instruction_pointer: initial_position,
-			rv_count: rvcount,
-			local_variables: None,
-			try_stack: None,
-			arguments: None,
-		}
+	/// The evaluation stack of the current context.
+	pub(crate) fn evaluation_stack(&self) -> Result<Rc<RefCell<EvaluationStack>>, VMError> {
+		Ok(self.context()?.borrow().evaluation_stack())
 	}
 
-	fn load_script(
-		&mut self,
-		script: Script,
-		rvcount: i32,
-		initial_position: usize,
-	) -> Rc<RefCell<ExecutionContext>> {
-		let context = Rc::new(RefCell::new(self.create_context(script, rvcount, initial_position)));
+	/// Returns the item at the specified index from the top of the current stack without
+	/// removing it.
+	pub fn peek(&self, index: usize) -> Result<Rc<RefCell<StackItem>>, VMError> {
+		self.evaluation_stack()?.borrow().peek(index as i32)
+	}
 
-		self.load_context(&context);
+	/// Removes and returns the item at the top of the current stack.
+	pub fn pop(&mut self) -> Result<Rc<RefCell<StackItem>>, VMError> {
+		self.evaluation_stack()?.borrow_mut().pop()
+	}
 
-		context
+	/// Pops the item at the top of the current stack and converts it to an integer.
+	pub fn pop_int(&mut self) -> Result<BigInt, VMError> {
+		self.pop()?.borrow().get_integer()
 	}
 
-	fn pre_execute_instruction(&mut self, instruction: Instruction) {
-		if self.reference_counter.borrow().count() > self.limits.max_stack_size {
-			panic!("Max stack size exceeded");
-		}
+	/// Pops the item at the top of the current stack and converts it to a boolean.
+	pub fn pop_bool(&mut self) -> Result<bool, VMError> {
+		self.pop()?.borrow().get_boolean()
+	}
 
-		match instruction {
-			Instruction::JMP(offset) => {
-				self.is_jumping = true;
-			},
-			Instruction::CALL(context) => {
-				self.load_context(context);
-			},
-			_ => (),
-		}
+	/// Pops the item at the top of the current stack and gets its memory.
+	pub fn pop_bytes(&mut self) -> Result<Vec<u8>, VMError> {
+		self.pop()?.borrow().get_span()
 	}
 
-	fn post_execute_instruction(&mut self, instruction: Instruction) {
-		let count = self.reference_counter.borrow().count();
-		if count > self.limits.max_stack_size {
-			panic!("Max stack size exceeded: {}", count);
-		}
+	/// Removes and returns the item at the top of the current stack, or `None` if there is no
+	/// current context or its stack is empty.
+	pub fn try_pop(&mut self) -> Option<Rc<RefCell<StackItem>>> {
+		self.pop().ok()
+	}
 
-		match instruction {
-			Instruction::RET => {
-				let context = self.invocation_stack.pop().unwrap();
-				// do something with returned context
-			},
-			Instruction::THROW => {
-				self.handle_exception();
-			},
-			_ => (),
-		}
+	/// Pushes an item onto the top of the current stack.
+	pub fn push(&mut self, item: Rc<RefCell<StackItem>>) -> Result<(), VMError> {
+		self.evaluation_stack()?.borrow_mut().push(item);
+		Ok(())
 	}
-	fn handle_exception(&mut self) {
-		// loop through contexts
-		// set instruction pointer to catch or finally
-		// pop contexts
-		if let Some(exception) = self.uncaught_exception.take() {
-			panic!("Unhandled exception: {:?}", exception.borrow().get_slice());
-		}
+
+	/// Inserts an item into the current stack so that it ends up at `index` from the top.
+	pub fn insert(&mut self, index: usize, item: Rc<RefCell<StackItem>>) -> Result<(), VMError> {
+		self.evaluation_stack()?.borrow_mut().insert(index, item)
 	}
+}
 
-	fn execute_try(&mut self, catch_offset: usize, finally_offset: usize) {
-		let mut context = self.current_context.as_mut().unwrap().borrow_mut();
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::script_builder::ScriptBuilder;
+
+	#[test]
+	fn test_empty_script_halts() {
+		let mut engine = ExecutionEngine::new();
+		engine.load_script(Script::new(vec![]), -1, 0).unwrap();
+		assert_eq!(engine.execute(), VMState::Halt);
+		assert_eq!(engine.result_stack.borrow().count(), 0);
+	}
 
-		if catch_offset == 0 && finally_offset == 0 {
-			panic!("Invalid try block offsets");
+	#[test]
+	fn test_typed_pop() {
+		let mut engine = ExecutionEngine::new();
+		engine.load_script(Script::new(vec![]), -1, 0).unwrap();
+		for item in [StackItem::ByteString(vec![0x01]), StackItem::from(2), StackItem::from(3)] {
+			engine.push(item.into()).unwrap();
 		}
+		assert_eq!(engine.pop_int().unwrap(), BigInt::from(3));
+		assert!(engine.pop_bool().unwrap());
+		assert_eq!(engine.pop_bytes().unwrap(), [0x01]);
 
-		if context.try_stack.is_none() {
-			context.try_stack = Some(Vec::new());
-		}
+		let error = |result: Result<_, VMError>| match result {
+			Err(VMError::InvalidType(message)) => message,
+			result => panic!("unexpected result: {result:?}"),
+		};
+		let array = || StackItem::Array(Vec::new().into()).into();
+		engine.push(array()).unwrap();
+		assert_eq!(error(engine.pop_int().map(drop)), "Can't convert Array to Integer");
+		engine.push(array()).unwrap();
+		assert_eq!(error(engine.pop_bytes().map(drop)), "Array has no span");
+		engine.push(StackItem::ByteString(vec![0x01; 33]).into()).unwrap();
+		assert_eq!(
+			error(engine.pop_bool().map(drop)),
+			"Can't convert a ByteString of size 33 to Boolean"
+		);
+		assert!(engine.pop_int().is_err());
+	}
 
-		if context.try_stack.as_ref().unwrap().len() >= self.limits.max_try_nesting_depth {
-			panic!("Max try nesting depth exceeded");
+	#[test]
+	fn test_pop_underflow() {
+		let mut engine = ExecutionEngine::new();
+		assert!(engine.pop().is_err());
+		assert!(engine.try_pop().is_none());
+
+		engine.load_script(Script::new(vec![]), -1, 0).unwrap();
+		assert!(engine.pop().is_err());
+		assert!(engine.try_pop().is_none());
+		engine.push(StackItem::from(1).into()).unwrap();
+		assert_eq!(*engine.try_pop().unwrap().borrow(), StackItem::from(1));
+		assert!(engine.try_pop().is_none());
+
+		// Every instruction popping more items than the stack holds faults the VM.
+		for opcode in [OpCode::Drop, OpCode::Add, OpCode::Swap, OpCode::Within, OpCode::Pack] {
+			let mut engine = ExecutionEngine::new();
+			engine.load_script(Script::new(vec![opcode as u8]), -1, 0).unwrap();
+			assert_eq!(engine.execute(), VMState::Fault, "{opcode:?}");
 		}
+	}
+
+	#[test]
+	fn test_push_without_context() {
+		let mut engine = ExecutionEngine::new();
+		assert!(engine.push(StackItem::from(1).into()).is_err());
+		assert!(engine.insert(0, StackItem::from(1).into()).is_err());
+		assert_eq!(engine.reference_counter.borrow().count(), 0);
+
+		// Once RET empties the invocation stack there is nothing to push onto.
+		engine.load_script(Script::new(vec![OpCode::Ret as u8]), -1, 0).unwrap();
+		assert_eq!(engine.execute(), VMState::Halt);
+		assert!(engine.push(StackItem::from(1).into()).is_err());
+	}
 
-		let catch_pointer =
-			if catch_offset > 0 { Some(context.instruction_pointer + catch_offset) } else { None };
+	#[test]
+	fn test_invalid_opcode_faults() {
+		let mut engine = ExecutionEngine::new();
+		engine.load_script(Script::new(vec![0xFF]), -1, 0).unwrap();
+		assert_eq!(engine.execute(), VMState::Fault);
+	}
 
-		let finally_pointer = if finally_offset > 0 {
-			Some(context.instruction_pointer + finally_offset)
-		} else {
-			None
+	#[test]
+	fn test_results_move_to_result_stack() {
+		let mut engine = ExecutionEngine::new();
+		let script = vec![OpCode::Push1 as u8, OpCode::Push2 as u8, OpCode::Add as u8];
+		engine.load_script(Script::new(script), -1, 0).unwrap();
+		assert_eq!(engine.execute(), VMState::Halt);
+		let result = engine.result_stack.borrow().peek(0).unwrap();
+		assert_eq!(*result.borrow(), StackItem::from(3));
+	}
+
+	#[test]
+	fn test_step() {
+		let script = vec![OpCode::Push1 as u8, OpCode::Push2 as u8, OpCode::Add as u8];
+		let mut engine = ExecutionEngine::new();
+		engine.load_script(Script::new(script), -1, 0).unwrap();
+		let top = |engine: &ExecutionEngine| {
+			let stack = engine.evaluation_stack().unwrap();
+			let stack = stack.borrow();
+			(stack.count(), stack.peek(0).unwrap().borrow().clone())
 		};
 
-		context.try_stack.as_mut().unwrap().push(ExceptionHandlingContext {
-			state: ExceptionHandlingState::Try,
-			catch_pointer: catch_pointer.unwrap() as i32,
-			finally_pointer: finally_pointer.unwrap() as i32,
-			end_pointer: 0,
-		});
+		assert_eq!(engine.step(), Ok(VMState::None));
+		assert_eq!(top(&engine), (1, StackItem::from(1)));
+		assert_eq!(engine.step(), Ok(VMState::None));
+		assert_eq!(top(&engine), (2, StackItem::from(2)));
+		assert_eq!(engine.step(), Ok(VMState::None));
+		assert_eq!(top(&engine), (1, StackItem::from(3)));
+
+		// Stepping past the end of the script runs the implicit RET.
+		assert_eq!(engine.step(), Ok(VMState::Halt));
+		assert_eq!(engine.step(), Ok(VMState::Halt));
+		assert_eq!(engine.result_stack.borrow().count(), 1);
+	}
 
-		self.is_jumping = true;
+	#[test]
+	fn test_step_fault() {
+		let mut engine = ExecutionEngine::new();
+		engine.load_script(Script::new(vec![OpCode::Add as u8]), -1, 0).unwrap();
+		assert!(engine.step().is_err());
+		assert_eq!(engine.state, VMState::Fault);
+		assert_eq!(engine.step(), Ok(VMState::Fault));
 	}
 
-	fn execute_throw(&mut self, exception: Rc<RefCell<dyn StackItem>>) {
-		self.uncaught_exception = Some(exception);
-		self.handle_exception();
+	#[test]
+	fn test_breakpoint() {
+		let script = Script::new(vec![
+			OpCode::Push1 as u8,
+			OpCode::Push2 as u8,
+			OpCode::Add as u8,
+			OpCode::Push3 as u8,
+		]);
+		let mut engine = ExecutionEngine::new();
+		assert!(engine.add_breakpoint(&script, 2));
+		assert!(!engine.add_breakpoint(&script, 2));
+		engine.load_script(script.clone(), -1, 0).unwrap();
+
+		assert_eq!(engine.execute(), VMState::Break);
+		let stack = engine.evaluation_stack().unwrap();
+		assert_eq!(stack.borrow().count(), 2);
+		assert_eq!(*stack.borrow().peek(0).unwrap().borrow(), StackItem::from(2));
+		assert_eq!(engine.context().unwrap().borrow().instruction_pointer, 2);
+
+		assert_eq!(engine.execute(), VMState::Halt);
+		let result = engine.result_stack.borrow();
+		assert_eq!(result.count(), 2);
+		assert_eq!(*result.peek(0).unwrap().borrow(), StackItem::from(3));
+		assert_eq!(*result.peek(1).unwrap().borrow(), StackItem::from(3));
+		drop(result);
+
+		assert!(engine.remove_breakpoint(&script, 2));
+		assert!(!engine.remove_breakpoint(&script, 2));
 	}
 
-	fn execute_end_try(&mut self, end_offset: usize) {
-		let context = self.current_context.as_mut().unwrap().borrow_mut();
+	#[test]
+	fn test_breakpoint_at_first_instruction() {
+		let script = Script::new(vec![OpCode::Push1 as u8, OpCode::Push2 as u8]);
+		let mut engine = ExecutionEngine::new();
+		assert_eq!(engine.state, VMState::Break);
+		engine.add_breakpoint(&script, 0);
+		engine.load_script(script, -1, 0).unwrap();
+
+		// A new engine starts in `VMState::Break`, which doesn't skip the breakpoint.
+		assert_eq!(engine.execute(), VMState::Break);
+		assert_eq!(engine.steps, 0);
+		assert_eq!(engine.context().unwrap().borrow().instruction_pointer, 0);
+		assert_eq!(engine.execute(), VMState::Halt);
+		assert_eq!(engine.result_stack.borrow().count(), 2);
+	}
 
-		let mut current_try = match context.try_stack.as_mut().unwrap().pop() {
-			Some(try_context) => try_context,
-			None => panic!("No matching try block found"),
-		};
+	#[test]
+	fn test_max_stack_size() {
+		let mut engine = ExecutionEngine::new();
+		engine.limits.max_stack_size = 2;
+		let script = vec![OpCode::Push1 as u8, OpCode::Push1 as u8, OpCode::Push1 as u8];
+		engine.load_script(Script::new(script), -1, 0).unwrap();
+		engine.step().unwrap();
+		engine.step().unwrap();
+		assert!(matches!(engine.step(), Err(VMError::StackOverflow(_))));
+		assert_eq!(engine.state, VMState::Fault);
+	}
 
-		if let ExceptionHandlingState::Finally = current_try.state() {
-			panic!("EndTry cannot be called in finally block");
+	#[test]
+	fn test_max_stack_size_releases_unreachable_items() {
+		// The elements of the dropped array still count until the limit is hit, at which point
+		// they are released.
+		let mut engine = ExecutionEngine::new();
+		engine.limits.max_stack_size = 3;
+		let script = vec![
+			OpCode::Push2 as u8,
+			OpCode::NewArray as u8,
+			OpCode::Drop as u8,
+			OpCode::Push1 as u8,
+			OpCode::Push1 as u8,
+		];
+		engine.load_script(Script::new(script), -1, 0).unwrap();
+		for _ in 0..4 {
+			engine.step().unwrap();
 		}
+		assert_eq!(engine.reference_counter.borrow().count(), 3);
+		engine.step().unwrap();
+		assert_eq!(engine.reference_counter.borrow().count(), 2);
+		assert_eq!(engine.execute(), VMState::Halt);
+	}
 
-		let end_pointer = context.instruction_pointer + end_offset;
+	#[test]
+	fn test_dry_run() {
+		let script = vec![OpCode::Push1 as u8, OpCode::Push2 as u8, OpCode::Add as u8];
+		assert_eq!(ExecutionEngine::dry_run(Script::new(script), 10), DryRunOutcome::Halted);
+
+		let script = vec![OpCode::Push1 as u8, OpCode::Push0 as u8, OpCode::Div as u8];
+		assert!(matches!(
+			ExecutionEngine::dry_run(Script::new(script), 10),
+			DryRunOutcome::Faulted(VMError::DivisionByZero(_))
+		));
+
+		let script = vec![OpCode::Jmp as u8, 0x00];
+		assert_eq!(
+			ExecutionEngine::dry_run(Script::new(script), 1000),
+			DryRunOutcome::StepsExhausted
+		);
+	}
 
-		if let Some(handler) = current_try.finally_pointer() {
-			current_try.set_state(ExceptionHandlingState::Finally);
-			current_try.set_end_pointer(end_pointer as i32);
-			context.instruction_pointer = handler;
-		} else {
-			context.instruction_pointer = end_pointer;
-		}
+	#[test]
+	fn test_gas_limit() {
+		// NOP and JMP back to it forever, costing 1 + 2 per iteration.
+		let script = vec![OpCode::Nop as u8, OpCode::Jmp as u8, 0xFF];
+		let mut engine = ExecutionEngine::new();
+		engine.gas_limit = 100;
+		engine.load_script(Script::new(script.clone()), -1, 0).unwrap();
+		assert_eq!(engine.execute(), VMState::Fault);
+		assert_eq!(engine.gas_consumed, 102);
+
+		let mut engine = ExecutionEngine::new();
+		engine.gas_table = GasTable::default().with_price(OpCode::Jmp, 9);
+		engine.gas_limit = 100;
+		engine.load_script(Script::new(script), -1, 0).unwrap();
+		assert_eq!(engine.execute(), VMState::Fault);
+		assert_eq!(engine.gas_consumed, 101);
+	}
 
-		self.is_jumping = true;
+	#[test]
+	fn test_on_halt() {
+		let remaining = Rc::new(RefCell::new(None));
+		let mut engine = ExecutionEngine::new();
+		engine.gas_limit = 100;
+		let captured = remaining.clone();
+		engine.set_on_halt(move |_, gas| *captured.borrow_mut() = Some(gas));
+		let script = vec![OpCode::Push1 as u8, OpCode::Push2 as u8, OpCode::Add as u8];
+		engine.load_script(Script::new(script), -1, 0).unwrap();
+		assert_eq!(engine.execute(), VMState::Halt);
+		assert!(engine.gas_consumed > 0);
+		assert_eq!(*remaining.borrow(), Some(100 - engine.gas_consumed));
 	}
 
-	fn execute_load_from_slot(&mut self, slot: &mut Slot, index: usize) {
-		if let Some(values) = slot {
-			if index < values.len() {
-				let value = values[index].clone();
-				self.push(value);
-			} else {
-				panic!("Invalid slot index: {}", index);
-			}
-		} else {
-			panic!("Slot not initialized");
+	#[test]
+	fn test_execute_detailed() {
+		let mut builder = ScriptBuilder::new();
+		builder.emit_push_bytes(b"hello".to_vec());
+		builder.emit_syscall_by_name("System.Runtime.Log").unwrap();
+		builder.emit(OpCode::Push1, vec![]);
+		builder.emit(OpCode::Push1, vec![]);
+		builder.emit(OpCode::Pack, vec![]);
+		builder.emit_push_bytes(b"event".to_vec());
+		builder.emit_syscall_by_name("System.Runtime.Notify").unwrap();
+		builder.emit(OpCode::Push7, vec![]);
+		let mut engine = ExecutionEngine::new();
+		engine.register_runtime();
+		engine.load_script(builder.to_script(), -1, 0).unwrap();
+		let result = engine.execute_detailed();
+		assert_eq!(result.state, VMState::Halt);
+		assert_eq!(result.results, vec![StackItem::from(7)]);
+		assert!(result.gas_consumed > 0);
+		// Eight instructions and the implicit RET.
+		assert_eq!(result.steps, 9);
+		assert_eq!(result.fault_reason, None);
+		assert_eq!(result.logs, vec!["hello".to_string()]);
+		assert_eq!(
+			result.notifications,
+			vec![Notification { event_name: "event".to_string(), state: vec![StackItem::from(1)] }]
+		);
+
+		let mut engine = ExecutionEngine::new();
+		engine
+			.load_script(Script::new(vec![OpCode::Push0 as u8, OpCode::Throw as u8]), -1, 0)
+			.unwrap();
+		let result = engine.execute_detailed();
+		assert_eq!(result.state, VMState::Fault);
+		assert!(result.fault_reason.is_some());
+	}
+
+	#[test]
+	fn test_fault_records_position() {
+		let script = vec![OpCode::Push1 as u8, OpCode::Push0 as u8, OpCode::Div as u8];
+		let mut engine = ExecutionEngine::new();
+		engine.load_script(Script::new(script), -1, 0).unwrap();
+		assert_eq!(engine.execute(), VMState::Fault);
+		match engine.last_error {
+			Some(VMError::FaultAt { ip, opcode, reason }) => {
+				assert_eq!((ip, opcode), (2, OpCode::Div));
+				assert!(matches!(*reason, VMError::DivisionByZero(_)));
+			},
+			error => panic!("unexpected error: {error:?}"),
 		}
 	}
 
-	fn execute_store_to_slot(&mut self, slot: &mut Slot, index: usize) {
-		if let Some(slot) = slot {
-			if index >= slot.len() {
-				panic!("Index out of range when storing to slot: {}", index);
+	#[test]
+	fn test_observer() {
+		#[derive(Default)]
+		struct Counter {
+			executed: Rc<RefCell<usize>>,
+			states: Rc<RefCell<Vec<(VMState, VMState)>>>,
+		}
+		impl EngineObserver for Counter {
+			fn on_post_execute(&mut self, _instruction: &Instruction) {
+				*self.executed.borrow_mut() += 1;
 			}
 
-			let value = self.result_stack.get_mut().pop();
-			slot[index] = value;
-		} else {
-			panic!("Slot has not been initialized.");
+			fn on_state_changed(&mut self, old: VMState, new: VMState) {
+				self.states.borrow_mut().push((old, new));
+			}
 		}
+
+		// for (i = 3; i != 0; i--) {}
+		let script = vec![
+			OpCode::Push3 as u8,
+			OpCode::Dec as u8,
+			OpCode::Dup as u8,
+			OpCode::JmpIf as u8,
+			(-2i8) as u8,
+			OpCode::Drop as u8,
+		];
+		let counter = Counter::default();
+		let executed = counter.executed.clone();
+		let states = counter.states.clone();
+		let mut engine = ExecutionEngine::new();
+		engine.set_observer(counter);
+		engine.load_script(Script::new(script), -1, 0).unwrap();
+		assert_eq!(engine.execute(), VMState::Halt);
+		// PUSH3, three rounds of DEC DUP JMPIF, DROP and the implicit RET.
+		assert_eq!(*executed.borrow(), 12);
+		assert_eq!(
+			*states.borrow(),
+			[(VMState::Break, VMState::None), (VMState::None, VMState::Halt)]
+		);
+	}
+
+	#[test]
+	fn test_registered_syscalls() {
+		let mut engine = ExecutionEngine::new();
+		assert!(engine.registered_syscalls().is_empty());
+		engine.interop_service.register_named(
+			"System.Runtime.Log",
+			0x9647e7cf,
+			Rc::new(|_| Ok(())),
+		);
+		engine.interop_service.register_named(
+			"System.Runtime.Notify",
+			0x616f0195,
+			Rc::new(|_| Ok(())),
+		);
+		engine.interop_service.register(0x01020304, Rc::new(|_| Ok(())));
+		assert_eq!(engine.registered_syscalls(), vec![0x01020304, 0x616f0195, 0x9647e7cf]);
+		assert_eq!(
+			engine.registered_syscall_names(),
+			vec!["System.Runtime.Log".to_string(), "System.Runtime.Notify".to_string()]
+		);
+	}
+
+	#[test]
+	fn test_max_script_size() {
+		let limits = ExecutionEngineLimits { max_script_size: 4, ..Default::default() };
+		let mut engine = ExecutionEngine::new_with_limits(limits);
+		engine.load_script(Script::new(vec![OpCode::Nop as u8; 4]), -1, 0).unwrap();
+
+		let mut engine = ExecutionEngine::new_with_limits(limits);
+		let err = engine.load_script(Script::new(vec![OpCode::Nop as u8; 5]), -1, 0).unwrap_err();
+		assert!(matches!(err, VMError::InvalidParameter(_)));
+		assert!(engine.invocation_stack.is_empty());
+		assert_eq!(engine.state, VMState::Break);
 	}
 
-	fn load_token(&mut self, token: u16) -> Result<ExecutionContext, &'static str> {
-		panic!("Not implemented");
+	#[test]
+	fn test_default_max_script_size() {
+		let max_script_size = ExecutionEngineLimits::default().max_script_size;
+		let mut engine = ExecutionEngine::new();
+		engine
+			.load_script(Script::new(vec![OpCode::Nop as u8; max_script_size]), -1, 0)
+			.unwrap();
+
+		let script = Script::new(vec![OpCode::Nop as u8; max_script_size + 1]);
+		let mut engine = ExecutionEngine::new();
+		assert!(matches!(engine.load_script(script, -1, 0), Err(VMError::InvalidParameter(_))));
 	}
 
-	fn on_syscall(&mut self, method: u32) {
-		panic!("Not implemented")
-		// let syscall = match method {
-		//     0 => Syscall::Syscall0,
-		//     1 => Syscall::Syscall1,
-		//     _ => panic!("Invalid syscall: {}", method),
-		// };
-		//
-		// syscall.invoke(self);
+	#[test]
+	fn test_rv_count_mismatch_faults() {
+		let mut engine = ExecutionEngine::new();
+		engine.load_script(Script::new(vec![OpCode::Push1 as u8]), 0, 0).unwrap();
+		assert_eq!(engine.execute(), VMState::Fault);
 	}
 }
@@ -0,0 +1,95 @@
+use crate::op_code::OpCode;
+
+/// The price of executing each `OpCode`, in the smallest fee unit.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GasTable {
+	/// The prices, indexed by the byte value of their opcode.
+	prices: [i64; 256],
+}
+
+impl Default for GasTable {
+	/// Creates a table with the prices charged by NEO's `ApplicationEngine`.
+	fn default() -> Self {
+		let mut prices = [0; 256];
+		for (code, price) in prices.iter_mut().enumerate() {
+			if let Some(opcode) = num_traits::FromPrimitive::from_usize(code) {
+				*price = default_price(opcode);
+			}
+		}
+		Self { prices }
+	}
+}
+
+impl GasTable {
+	/// Creates a table in which every opcode is free.
+	pub fn free() -> Self {
+		Self { prices: [0; 256] }
+	}
+
+	/// Overrides the price of `opcode`.
+	pub fn with_price(mut self, opcode: OpCode, price: i64) -> Self {
+		self.prices[opcode as usize] = price;
+		self
+	}
+
+	/// The price of executing `opcode`.
+	pub fn price(&self, opcode: OpCode) -> i64 {
+		self.prices[opcode as usize]
+	}
+}
+
+/// The price of `opcode` in NEO's `ApplicationEngine`.
+fn default_price(opcode: OpCode) -> i64 {
+	use OpCode::*;
+	match opcode {
+		PushInt8 | PushInt16 | PushInt32 | PushInt64 | PushTrue | PushFalse | PushNull | PushM1
+		| Push0 | Push1 | Push2 | Push3 | Push4 | Push5 | Push6 | Push7 | Push8 | Push9
+		| Push10 | Push11 | Push12 | Push13 | Push14 | Push15 | Push16 | Nop | Assert
+		| AssertMsg => 1,
+		PushInt128 | PushInt256 | PushA | Try | TryL | EndTry | EndTryL | EndFinally | Invert
+		| Sign | Abs | Negate | Inc | Dec | Not | Nz | Size => 1 << 2,
+		PushData1 | And | Or | Xor | Add | Sub | Mul | Div | Mod | Shl | Shr | BoolAnd | BoolOr
+		| NumEqual | NumNotEqual | Lt | Le | Gt | Ge | Min | Max | Within | NewMap => 1 << 3,
+		PushData2 | Call | CallL | CallA | Throw | NewArray | NewArrayT | NewStruct => 1 << 9,
+		PushData4 => 1 << 12,
+		Jmp | JmpL | JmpIf | JmpIfL | JmpIfNot | JmpIfNotL | JmpEq | JmpEqL | JmpNe | JmpNeL
+		| JmpGt | JmpGtL | JmpGe | JmpGeL | JmpLt | JmpLtL | JmpLe | JmpLeL | Depth | Drop
+		| Nip | Dup | Over | Pick | Tuck | Swap | Rot | Reverse3 | Reverse4 | LdSFLd0 | LdSFLd1
+		| LdSFLd2 | LdSFLd3 | LdSFLd4 | LdSFLd5 | LdSFLd6 | LdSFLd | StSFLd0 | StSFLd1
+		| StSFLd2 | StSFLd3 | StSFLd4 | StSFLd5 | StSFLd6 | StSFLd | LdLoc0 | LdLoc1 | LdLoc2
+		| LdLoc3 | LdLoc4 | LdLoc5 | LdLoc6 | LdLoc | StLoc0 | StLoc1 | StLoc2 | StLoc3
+		| StLoc4 | StLoc5 | StLoc6 | StLoc | LdArg0 | LdArg1 | LdArg2 | LdArg3 | LdArg4
+		| LdArg5 | LdArg6 | LdArg | StArg0 | StArg1 | StArg2 | StArg3 | StArg4 | StArg5
+		| StArg6 | StArg | IsNull | IsType => 1 << 1,
+		CallT => 1 << 15,
+		Abort | AbortMsg | Ret | Syscall => 0,
+		Xdrop | Clear | Roll | ReverseN | InitSSLot | NewArray0 | NewStruct0 | Keys | Remove
+		| ClearItems | PopItem => 1 << 4,
+		InitSlot | Pow | Sqrt | HasKey | PickItem => 1 << 6,
+		NewBuffer => 1 << 8,
+		MemCpy | Cat | Substr | Left | Right | ModPow | PackMap | PackStruct | Pack | Unpack =>
+			1 << 11,
+		Equal | NotEqual | ModMul => 1 << 5,
+		Values | Append | SetItem | ReverseItems | Convert => 1 << 13,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_default_prices() {
+		let table = GasTable::default();
+		assert_eq!(table.price(OpCode::Push1), 1);
+		assert_eq!(table.price(OpCode::Jmp), 2);
+		assert_eq!(table.price(OpCode::Add), 8);
+		assert_eq!(table.price(OpCode::CallT), 1 << 15);
+		assert_eq!(table.price(OpCode::Ret), 0);
+
+		let table = table.with_price(OpCode::Add, 100);
+		assert_eq!(table.price(OpCode::Add), 100);
+		assert_eq!(table.price(OpCode::Sub), 8);
+		assert_eq!(GasTable::free().price(OpCode::CallT), 0);
+	}
+}
@@ -1,32 +1,77 @@
-use crate::op_code::OpCode;
-use std::convert::TryFrom;
+use crate::{op_code::OpCode, utility::to_hex, vm::script::ScriptError};
+use num_traits::FromPrimitive;
 
-#[derive(Debug)]
+/// Represents instructions in the VM script.
+///
+/// An instruction decoded by `new` always holds the whole operand of its opcode, so the token
+/// getters can't read past its end.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Instruction {
 	pub opcode: OpCode,
 	pub operand: Vec<u8>,
 }
 
-#[derive(Debug)]
-enum Error {
-	InvalidOpcode,
-	InvalidOperandSize,
-	InvalidPrefixSize(usize),
-	OperandOutOfBounds { instruction_pointer: usize, operand_size: usize, script_length: usize },
-}
 impl Instruction {
+	/// Represents the instruction with `OpCode::Ret`.
 	pub const RET: Self = Self { opcode: OpCode::Ret, operand: Vec::new() };
 
+	/// Decodes the instruction starting at `ip` in `script`, failing with
+	/// `ScriptError::OperandOutOfBounds` when the script ends before the operand does.
+	pub fn new(script: &[u8], ip: usize) -> Result<Self, ScriptError> {
+		let code = *script.get(ip).ok_or(ScriptError::InvalidInstrPointer(ip))?;
+		let opcode = OpCode::from_u8(code).ok_or(ScriptError::InvalidOpcode(code))?;
+		let mut ip = ip + 1;
+
+		let prefix_size = opcode.operand_prefix().unwrap_or(0) as usize;
+		let prefix = script.get(ip..ip + prefix_size).ok_or(ScriptError::OperandOutOfBounds {
+			instruction_pointer: ip,
+			operand_size: prefix_size,
+			script_length: script.len(),
+		})?;
+		let operand_size = match prefix_size {
+			0 => opcode.operand_size().unwrap_or(0) as usize,
+			1 => prefix[0] as usize,
+			2 => u16::from_le_bytes([prefix[0], prefix[1]]) as usize,
+			4 => {
+				let size = i32::from_le_bytes([prefix[0], prefix[1], prefix[2], prefix[3]]);
+				usize::try_from(size).map_err(|_| ScriptError::InvalidPrefixSize(prefix_size))?
+			},
+			_ => return Err(ScriptError::InvalidPrefixSize(prefix_size)),
+		};
+		ip += prefix_size;
+
+		let operand = script
+			.get(ip..ip.saturating_add(operand_size))
+			.ok_or(ScriptError::OperandOutOfBounds {
+				instruction_pointer: ip,
+				operand_size,
+				script_length: script.len(),
+			})?
+			.to_vec();
+		Ok(Self { opcode, operand })
+	}
+
+	/// Gets the size of the instruction, including the opcode and the operand prefix.
 	pub fn size(&self) -> usize {
-		let prefix_size = self.opcode.operand_prefix().unwrap_or(0); //  OPERAND_SIZE_PREFIX[self.opcode as usize];
+		let prefix_size = self.opcode.operand_prefix().unwrap_or(0) as usize;
 		if prefix_size > 0 {
-			(1 + prefix_size + self.operand.len()) as usize
+			1 + prefix_size + self.operand.len()
 		} else {
-			(1 + self.opcode.operand_size().unwrap_or(0)) as usize
+			1 + self.opcode.operand_size().unwrap_or(0) as usize
 		}
 	}
 
-	// Token getters
+	/// The number of operand bytes, excluding the operand prefix.
+	pub fn operand_len(&self) -> usize {
+		self.operand.len()
+	}
+
+	/// The operand bytes as lowercase hex, excluding the operand prefix.
+	pub fn operand_as_hex(&self) -> String {
+		to_hex(&self.operand)
+	}
+
+	// Token getters, which expect the operand to be as long as `OpCode::operand_size`.
 	pub fn token_i8(&self) -> i8 {
 		self.operand[0] as i8
 	}
@@ -35,6 +80,10 @@ impl Instruction {
 		self.operand[1] as i8
 	}
 
+	pub fn token_i16(&self) -> i16 {
+		i16::from_le_bytes(self.operand[..2].try_into().unwrap())
+	}
+
 	pub fn token_i32(&self) -> i32 {
 		i32::from_le_bytes(self.operand[..4].try_into().unwrap())
 	}
@@ -43,6 +92,10 @@ impl Instruction {
 		i32::from_le_bytes(self.operand[4..8].try_into().unwrap())
 	}
 
+	pub fn token_i64(&self) -> i64 {
+		i64::from_le_bytes(self.operand[..8].try_into().unwrap())
+	}
+
 	// Other token methods
 	pub fn token_u8(&self) -> u8 {
 		self.operand[0]
@@ -63,37 +116,50 @@ impl Instruction {
 	pub fn token_string(&self) -> String {
 		String::from_utf8(self.operand.clone()).unwrap()
 	}
-	pub fn from_script(script: &[u8], ip: usize) -> Result<Self, Error> {
-		let opcode = OpCode::try_from(script[ip])?;
-		let mut ip = ip + 1;
+}
 
-		let mut operand_size = 0;
-		let prefix_size = opcode.operand_prefix().unwrap_or(0) as usize;
-		match prefix_size {
-			0 => {
-				operand_size = opcode.operand_size().unwrap_or(0) as usize;
-			},
-			1 => {
-				operand_size = script[ip] as usize;
-				ip += 1;
-			},
-			2 => {
-				operand_size = u16::from_le_bytes([script[ip], script[ip + 1]]) as usize;
-				ip += 2;
-			},
-			4 => {
-				operand_size = i32::from_le_bytes([
-					script[ip],
-					script[ip + 1],
-					script[ip + 2],
-					script[ip + 3],
-				]) as usize;
-				ip += 4;
-			},
-			_ => return Err(Error::InvalidPrefixSize(prefix_size)),
-		}
+#[cfg(test)]
+mod tests {
+	use super::*;
 
-		let operand = script[ip..ip + operand_size].to_vec();
-		Ok(Self { opcode, operand })
+	#[test]
+	fn test_operand_accessors() {
+		let script = [OpCode::PushData1 as u8, 0x03, 0x0A, 0xBC, 0xFF];
+		let instruction = Instruction::new(&script, 0).unwrap();
+		assert_eq!(instruction.operand_len(), 3);
+		assert_eq!(instruction.operand_as_hex(), "0abcff");
+		assert_eq!(instruction.size(), 5);
+
+		assert_eq!(Instruction::RET.operand_len(), 0);
+		assert_eq!(Instruction::RET.operand_as_hex(), "");
+	}
+
+	#[test]
+	fn test_token_i16_and_i64() {
+		let script = [OpCode::PushInt16 as u8, 0x34, 0x12];
+		assert_eq!(Instruction::new(&script, 0).unwrap().token_i16(), 0x1234);
+		let script = [OpCode::PushInt16 as u8, 0xfe, 0xff];
+		assert_eq!(Instruction::new(&script, 0).unwrap().token_i16(), -2);
+
+		let script = [OpCode::PushInt64 as u8, 0x08, 0x07, 0x06, 0x05, 0x04, 0x03, 0x02, 0x01];
+		assert_eq!(Instruction::new(&script, 0).unwrap().token_i64(), 0x0102030405060708);
+		let script = [OpCode::PushInt64 as u8, 0x00, 0, 0, 0, 0, 0, 0, 0x80];
+		assert_eq!(Instruction::new(&script, 0).unwrap().token_i64(), i64::MIN);
+	}
+
+	#[test]
+	fn test_truncated_operand() {
+		for script in [
+			vec![OpCode::PushInt32 as u8, 0x01, 0x02],
+			vec![OpCode::PushInt64 as u8, 0x01, 0x02, 0x03],
+			vec![OpCode::Jmp as u8],
+			vec![OpCode::InitSlot as u8, 0x01],
+			vec![OpCode::PushData2 as u8, 0x01],
+		] {
+			assert!(matches!(
+				Instruction::new(&script, 0),
+				Err(ScriptError::OperandOutOfBounds { script_length, .. }) if script_length == script.len()
+			));
+		}
 	}
 }
@@ -0,0 +1,138 @@
+use crate::{execution_engine::ExecutionEngine, vm_error::VMError};
+use std::{collections::HashMap, fmt, rc::Rc};
+
+/// A function implementing an interop service invoked through `OpCode::Syscall`.
+pub type SyscallHandler = Rc<dyn Fn(&mut ExecutionEngine) -> Result<(), VMError>>;
+
+/// The well-known NEO N3 interop services and their hashes, the first 4 bytes of the SHA-256
+/// of the name read as a little-endian `u32`. Sorted by name.
+pub const INTEROP_HASHES: &[(&str, u32)] = &[
+	("System.Contract.Call", 0x525b7d62),
+	("System.Contract.CallNative", 0x677bf71a),
+	("System.Contract.CreateMultisigAccount", 0x09e9336a),
+	("System.Contract.CreateStandardAccount", 0x028799cf),
+	("System.Contract.GetCallFlags", 0x813ada95),
+	("System.Contract.NativeOnPersist", 0x93bcdb2e),
+	("System.Contract.NativePostPersist", 0x165da144),
+	("System.Crypto.CheckMultisig", 0x3adcd09e),
+	("System.Crypto.CheckSig", 0x27b3e756),
+	("System.Iterator.Next", 0x9ced089c),
+	("System.Iterator.Value", 0x1dbf54f3),
+	("System.Runtime.BurnGas", 0xbc8c5ac3),
+	("System.Runtime.CheckWitness", 0x8cec27f8),
+	("System.Runtime.CurrentSigners", 0x8b18f1ac),
+	("System.Runtime.GasLeft", 0xced88814),
+	("System.Runtime.GetAddressVersion", 0xdc92494c),
+	("System.Runtime.GetCallingScriptHash", 0x3c6e5339),
+	("System.Runtime.GetEntryScriptHash", 0x38e2b4f9),
+	("System.Runtime.GetExecutingScriptHash", 0x74a8fedb),
+	("System.Runtime.GetInvocationCounter", 0x43112784),
+	("System.Runtime.GetNetwork", 0xe0a0fbc5),
+	("System.Runtime.GetNotifications", 0xf1354327),
+	("System.Runtime.GetRandom", 0x28a9de6b),
+	("System.Runtime.GetScriptContainer", 0x3008512d),
+	("System.Runtime.GetTime", 0x0388c3b7),
+	("System.Runtime.GetTrigger", 0xa0387de9),
+	("System.Runtime.LoadScript", 0x8f800cb3),
+	("System.Runtime.Log", 0x9647e7cf),
+	("System.Runtime.Notify", 0x616f0195),
+	("System.Runtime.Platform", 0xf6fc79b2),
+	("System.Storage.AsReadOnly", 0xe9bf4c76),
+	("System.Storage.Delete", 0xedc5582f),
+	("System.Storage.Find", 0x9ab830df),
+	("System.Storage.Get", 0x31e85d92),
+	("System.Storage.GetContext", 0xce67f69b),
+	("System.Storage.GetReadOnlyContext", 0xe26bb4f6),
+	("System.Storage.Put", 0x84183fe6),
+];
+
+/// Looks up the hash of the well-known interop service called `name`.
+pub fn interop_hash(name: &str) -> Option<u32> {
+	INTEROP_HASHES
+		.binary_search_by_key(&name, |&(name, _)| name)
+		.ok()
+		.map(|index| INTEROP_HASHES[index].1)
+}
+
+/// Describes an interop service registered with the engine.
+#[derive(Clone)]
+pub struct InteropDescriptor {
+	/// The name of the service, e.g. `System.Runtime.Log`, if one was provided.
+	pub name: Option<String>,
+
+	/// The 4-byte hash identifying the service in the operand of `OpCode::Syscall`.
+	pub hash: u32,
+
+	/// The function implementing the service.
+	pub handler: SyscallHandler,
+}
+
+impl fmt::Debug for InteropDescriptor {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("InteropDescriptor")
+			.field("name", &self.name)
+			.field("hash", &format_args!("{:#010x}", self.hash))
+			.finish()
+	}
+}
+
+/// The interop services available to the scripts executed by an engine, keyed by hash.
+#[derive(Clone, Debug, Default)]
+pub struct InteropService {
+	services: HashMap<u32, InteropDescriptor>,
+}
+
+impl InteropService {
+	/// Creates an empty registry.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Registers `handler` under `hash`, replacing any existing service with that hash.
+	pub fn register(&mut self, hash: u32, handler: SyscallHandler) {
+		self.services.insert(hash, InteropDescriptor { name: None, hash, handler });
+	}
+
+	/// Registers `handler` under `hash` and records `name` for introspection.
+	pub fn register_named(&mut self, name: &str, hash: u32, handler: SyscallHandler) {
+		self.services
+			.insert(hash, InteropDescriptor { name: Some(name.to_string()), hash, handler });
+	}
+
+	/// Returns the service registered under `hash`.
+	pub fn get(&self, hash: u32) -> Option<&InteropDescriptor> {
+		self.services.get(&hash)
+	}
+
+	/// The hashes of all the registered services, in ascending order.
+	pub fn hashes(&self) -> Vec<u32> {
+		let mut hashes: Vec<u32> = self.services.keys().copied().collect();
+		hashes.sort_unstable();
+		hashes
+	}
+
+	/// The names of the registered services that were given one, in ascending order.
+	pub fn names(&self) -> Vec<String> {
+		let mut names: Vec<String> =
+			self.services.values().filter_map(|service| service.name.clone()).collect();
+		names.sort_unstable();
+		names
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::storage::{STORAGE_DELETE, STORAGE_GET, STORAGE_GET_CONTEXT, STORAGE_PUT};
+
+	#[test]
+	fn test_interop_hash() {
+		assert!(INTEROP_HASHES.windows(2).all(|pair| pair[0].0 < pair[1].0));
+		assert_eq!(interop_hash("System.Runtime.Log"), Some(0x9647e7cf));
+		assert_eq!(interop_hash("System.Storage.GetContext"), Some(STORAGE_GET_CONTEXT));
+		assert_eq!(interop_hash("System.Storage.Get"), Some(STORAGE_GET));
+		assert_eq!(interop_hash("System.Storage.Put"), Some(STORAGE_PUT));
+		assert_eq!(interop_hash("System.Storage.Delete"), Some(STORAGE_DELETE));
+		assert_eq!(interop_hash("System.Runtime.Unknown"), None);
+	}
+}
@@ -0,0 +1,29 @@
+use crate::{execution_engine::ExecutionEngine, vm_error::VMError};
+use std::rc::Rc;
+
+/// A method of another contract that can be called with `OpCode::CallT`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MethodToken {
+	/// The hash of the contract to be called.
+	pub hash: [u8; 20],
+
+	/// The name of the method to be called.
+	pub method: String,
+
+	/// The number of parameters of the method.
+	pub parameters_count: u16,
+
+	/// Indicates whether the method has a return value.
+	pub has_return_value: bool,
+
+	/// The flags restricting what the called method can do.
+	pub call_flags: u8,
+}
+
+/// Loads and calls the method described by a `MethodToken`.
+///
+/// The resolver is invoked by `OpCode::CallT` after the token index has been validated. It is
+/// expected to pop the `parameters_count` arguments from the current evaluation stack and load
+/// the context running the method, e.g. with `ExecutionEngine::load_script`; any error it
+/// returns faults the engine.
+pub type TokenResolver = Rc<dyn Fn(&mut ExecutionEngine, &MethodToken) -> Result<(), VMError>>;
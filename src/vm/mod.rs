@@ -1,17 +1,27 @@
-// pub mod instruction;
+pub mod instruction;
 pub mod op_code;
 
-// pub mod script;
+pub mod script;
 
-// pub mod evaluation_stack;
+pub mod buffer_pool;
+pub mod engine_observer;
+pub mod engine_snapshot;
+pub mod evaluation_stack;
 
-// pub mod execution_context;
+pub mod execution_context;
 
-// pub mod slot;
+pub mod reference_counter;
+pub mod runtime;
+pub mod slot;
+pub mod storage;
+pub mod tarjan;
 
-// mod execution_engine;
-// mod vm_exception;
-// pub mod vm_state;
+pub mod execution_engine;
+pub mod gas_table;
+pub mod interop_service;
+pub mod method_token;
+pub mod vm_error;
+pub mod vm_state;
 
 pub fn add(left: usize, right: usize) -> usize {
 	left + right
@@ -1,7 +1,9 @@
 use lazy_static::lazy_static;
 use num_derive::FromPrimitive;
-use num_traits::FromPrimitive;
-use std::{collections::HashMap, fmt::Error};
+use std::{
+	collections::HashMap,
+	fmt::{self, Display, Formatter},
+};
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, FromPrimitive, Hash)]
 pub enum OpCode {
@@ -212,6 +214,56 @@ pub enum OpCode {
 	AssertMsg = 0xE1,
 }
 
+/// Describes how the operand of an opcode is laid out, for disassemblers and other tooling.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum OperandLayout {
+	/// The opcode has no operand.
+	None,
+	/// A signed integer of the given number of bytes, pushed by `OpCode::PushInt8` to
+	/// `OpCode::PushInt256`.
+	Integer(u8),
+	/// A signed byte, e.g. the offset of a short jump.
+	I8,
+	/// A signed 32-bit integer, e.g. the offset of a long jump.
+	I32,
+	/// The catch and finally offsets of `OpCode::Try`.
+	TwoI8,
+	/// The catch and finally offsets of `OpCode::TryL`.
+	TwoI32,
+	/// An unsigned byte, e.g. a slot index or a type code.
+	U8,
+	/// The local variable and argument counts of `OpCode::InitSlot`.
+	TwoU8,
+	/// An unsigned 16-bit integer, the method token of `OpCode::CallT`.
+	U16,
+	/// An unsigned 32-bit integer, the interop service hash of `OpCode::Syscall`.
+	U32,
+	/// Data preceded by its length, stored in the given number of bytes.
+	PrefixedData(u8),
+}
+
+impl OperandLayout {
+	/// The number of operand bytes, excluding the length prefix of `PrefixedData`.
+	pub fn size(&self) -> u8 {
+		match self {
+			Self::None | Self::PrefixedData(_) => 0,
+			Self::Integer(size) => *size,
+			Self::I8 | Self::U8 => 1,
+			Self::TwoI8 | Self::TwoU8 | Self::U16 => 2,
+			Self::I32 | Self::U32 => 4,
+			Self::TwoI32 => 8,
+		}
+	}
+
+	/// The number of bytes of the length prefix.
+	pub fn prefix(&self) -> u8 {
+		match self {
+			Self::PrefixedData(prefix) => *prefix,
+			_ => 0,
+		}
+	}
+}
+
 struct OperandSize {
 	prefix: u8,
 	size: u8,
@@ -286,6 +338,269 @@ impl OpCode {
 			None => Err(self),
 		}
 	}
+
+	/// The canonical NEO mnemonic of the opcode, e.g. `"PUSHINT8"` or `"JMP_L"`.
+	pub fn name(&self) -> &'static str {
+		match self {
+			OpCode::PushInt8 => "PUSHINT8",
+			OpCode::PushInt16 => "PUSHINT16",
+			OpCode::PushInt32 => "PUSHINT32",
+			OpCode::PushInt64 => "PUSHINT64",
+			OpCode::PushInt128 => "PUSHINT128",
+			OpCode::PushInt256 => "PUSHINT256",
+			OpCode::PushTrue => "PUSHT",
+			OpCode::PushFalse => "PUSHF",
+			OpCode::PushA => "PUSHA",
+			OpCode::PushNull => "PUSHNULL",
+			OpCode::PushData1 => "PUSHDATA1",
+			OpCode::PushData2 => "PUSHDATA2",
+			OpCode::PushData4 => "PUSHDATA4",
+			OpCode::PushM1 => "PUSHM1",
+			OpCode::Push0 => "PUSH0",
+			OpCode::Push1 => "PUSH1",
+			OpCode::Push2 => "PUSH2",
+			OpCode::Push3 => "PUSH3",
+			OpCode::Push4 => "PUSH4",
+			OpCode::Push5 => "PUSH5",
+			OpCode::Push6 => "PUSH6",
+			OpCode::Push7 => "PUSH7",
+			OpCode::Push8 => "PUSH8",
+			OpCode::Push9 => "PUSH9",
+			OpCode::Push10 => "PUSH10",
+			OpCode::Push11 => "PUSH11",
+			OpCode::Push12 => "PUSH12",
+			OpCode::Push13 => "PUSH13",
+			OpCode::Push14 => "PUSH14",
+			OpCode::Push15 => "PUSH15",
+			OpCode::Push16 => "PUSH16",
+			OpCode::Nop => "NOP",
+			OpCode::Jmp => "JMP",
+			OpCode::JmpL => "JMP_L",
+			OpCode::JmpIf => "JMPIF",
+			OpCode::JmpIfL => "JMPIF_L",
+			OpCode::JmpIfNot => "JMPIFNOT",
+			OpCode::JmpIfNotL => "JMPIFNOT_L",
+			OpCode::JmpEq => "JMPEQ",
+			OpCode::JmpEqL => "JMPEQ_L",
+			OpCode::JmpNe => "JMPNE",
+			OpCode::JmpNeL => "JMPNE_L",
+			OpCode::JmpGt => "JMPGT",
+			OpCode::JmpGtL => "JMPGT_L",
+			OpCode::JmpGe => "JMPGE",
+			OpCode::JmpGeL => "JMPGE_L",
+			OpCode::JmpLt => "JMPLT",
+			OpCode::JmpLtL => "JMPLT_L",
+			OpCode::JmpLe => "JMPLE",
+			OpCode::JmpLeL => "JMPLE_L",
+			OpCode::Call => "CALL",
+			OpCode::CallL => "CALL_L",
+			OpCode::CallA => "CALLA",
+			OpCode::CallT => "CALLT",
+			OpCode::Abort => "ABORT",
+			OpCode::Assert => "ASSERT",
+			OpCode::Throw => "THROW",
+			OpCode::Try => "TRY",
+			OpCode::TryL => "TRY_L",
+			OpCode::EndTry => "ENDTRY",
+			OpCode::EndTryL => "ENDTRY_L",
+			OpCode::EndFinally => "ENDFINALLY",
+			OpCode::Ret => "RET",
+			OpCode::Syscall => "SYSCALL",
+			OpCode::Depth => "DEPTH",
+			OpCode::Drop => "DROP",
+			OpCode::Nip => "NIP",
+			OpCode::Xdrop => "XDROP",
+			OpCode::Clear => "CLEAR",
+			OpCode::Dup => "DUP",
+			OpCode::Over => "OVER",
+			OpCode::Pick => "PICK",
+			OpCode::Tuck => "TUCK",
+			OpCode::Swap => "SWAP",
+			OpCode::Rot => "ROT",
+			OpCode::Roll => "ROLL",
+			OpCode::Reverse3 => "REVERSE3",
+			OpCode::Reverse4 => "REVERSE4",
+			OpCode::ReverseN => "REVERSEN",
+			OpCode::InitSSLot => "INITSSLOT",
+			OpCode::InitSlot => "INITSLOT",
+			OpCode::LdSFLd0 => "LDSFLD0",
+			OpCode::LdSFLd1 => "LDSFLD1",
+			OpCode::LdSFLd2 => "LDSFLD2",
+			OpCode::LdSFLd3 => "LDSFLD3",
+			OpCode::LdSFLd4 => "LDSFLD4",
+			OpCode::LdSFLd5 => "LDSFLD5",
+			OpCode::LdSFLd6 => "LDSFLD6",
+			OpCode::LdSFLd => "LDSFLD",
+			OpCode::StSFLd0 => "STSFLD0",
+			OpCode::StSFLd1 => "STSFLD1",
+			OpCode::StSFLd2 => "STSFLD2",
+			OpCode::StSFLd3 => "STSFLD3",
+			OpCode::StSFLd4 => "STSFLD4",
+			OpCode::StSFLd5 => "STSFLD5",
+			OpCode::StSFLd6 => "STSFLD6",
+			OpCode::StSFLd => "STSFLD",
+			OpCode::LdLoc0 => "LDLOC0",
+			OpCode::LdLoc1 => "LDLOC1",
+			OpCode::LdLoc2 => "LDLOC2",
+			OpCode::LdLoc3 => "LDLOC3",
+			OpCode::LdLoc4 => "LDLOC4",
+			OpCode::LdLoc5 => "LDLOC5",
+			OpCode::LdLoc6 => "LDLOC6",
+			OpCode::LdLoc => "LDLOC",
+			OpCode::StLoc0 => "STLOC0",
+			OpCode::StLoc1 => "STLOC1",
+			OpCode::StLoc2 => "STLOC2",
+			OpCode::StLoc3 => "STLOC3",
+			OpCode::StLoc4 => "STLOC4",
+			OpCode::StLoc5 => "STLOC5",
+			OpCode::StLoc6 => "STLOC6",
+			OpCode::StLoc => "STLOC",
+			OpCode::LdArg0 => "LDARG0",
+			OpCode::LdArg1 => "LDARG1",
+			OpCode::LdArg2 => "LDARG2",
+			OpCode::LdArg3 => "LDARG3",
+			OpCode::LdArg4 => "LDARG4",
+			OpCode::LdArg5 => "LDARG5",
+			OpCode::LdArg6 => "LDARG6",
+			OpCode::LdArg => "LDARG",
+			OpCode::StArg0 => "STARG0",
+			OpCode::StArg1 => "STARG1",
+			OpCode::StArg2 => "STARG2",
+			OpCode::StArg3 => "STARG3",
+			OpCode::StArg4 => "STARG4",
+			OpCode::StArg5 => "STARG5",
+			OpCode::StArg6 => "STARG6",
+			OpCode::StArg => "STARG",
+			OpCode::NewBuffer => "NEWBUFFER",
+			OpCode::MemCpy => "MEMCPY",
+			OpCode::Cat => "CAT",
+			OpCode::Substr => "SUBSTR",
+			OpCode::Left => "LEFT",
+			OpCode::Right => "RIGHT",
+			OpCode::Invert => "INVERT",
+			OpCode::And => "AND",
+			OpCode::Or => "OR",
+			OpCode::Xor => "XOR",
+			OpCode::Equal => "EQUAL",
+			OpCode::NotEqual => "NOTEQUAL",
+			OpCode::Sign => "SIGN",
+			OpCode::Abs => "ABS",
+			OpCode::Negate => "NEGATE",
+			OpCode::Inc => "INC",
+			OpCode::Dec => "DEC",
+			OpCode::Add => "ADD",
+			OpCode::Sub => "SUB",
+			OpCode::Mul => "MUL",
+			OpCode::Div => "DIV",
+			OpCode::Mod => "MOD",
+			OpCode::Pow => "POW",
+			OpCode::Sqrt => "SQRT",
+			OpCode::ModMul => "MODMUL",
+			OpCode::ModPow => "MODPOW",
+			OpCode::Shl => "SHL",
+			OpCode::Shr => "SHR",
+			OpCode::Not => "NOT",
+			OpCode::BoolAnd => "BOOLAND",
+			OpCode::BoolOr => "BOOLOR",
+			OpCode::Nz => "NZ",
+			OpCode::NumEqual => "NUMEQUAL",
+			OpCode::NumNotEqual => "NUMNOTEQUAL",
+			OpCode::Lt => "LT",
+			OpCode::Le => "LE",
+			OpCode::Gt => "GT",
+			OpCode::Ge => "GE",
+			OpCode::Min => "MIN",
+			OpCode::Max => "MAX",
+			OpCode::Within => "WITHIN",
+			OpCode::PackMap => "PACKMAP",
+			OpCode::PackStruct => "PACKSTRUCT",
+			OpCode::Pack => "PACK",
+			OpCode::Unpack => "UNPACK",
+			OpCode::NewArray0 => "NEWARRAY0",
+			OpCode::NewArray => "NEWARRAY",
+			OpCode::NewArrayT => "NEWARRAY_T",
+			OpCode::NewStruct0 => "NEWSTRUCT0",
+			OpCode::NewStruct => "NEWSTRUCT",
+			OpCode::NewMap => "NEWMAP",
+			OpCode::Size => "SIZE",
+			OpCode::HasKey => "HASKEY",
+			OpCode::Keys => "KEYS",
+			OpCode::Values => "VALUES",
+			OpCode::PickItem => "PICKITEM",
+			OpCode::Append => "APPEND",
+			OpCode::SetItem => "SETITEM",
+			OpCode::ReverseItems => "REVERSEITEMS",
+			OpCode::Remove => "REMOVE",
+			OpCode::ClearItems => "CLEARITEMS",
+			OpCode::PopItem => "POPITEM",
+			OpCode::IsNull => "ISNULL",
+			OpCode::IsType => "ISTYPE",
+			OpCode::Convert => "CONVERT",
+			OpCode::AbortMsg => "ABORTMSG",
+			OpCode::AssertMsg => "ASSERTMSG",
+		}
+	}
+
+	/// Describes how the operand of the opcode is laid out.
+	pub fn operand_layout(&self) -> OperandLayout {
+		match self {
+			OpCode::PushInt8
+			| OpCode::PushInt16
+			| OpCode::PushInt32
+			| OpCode::PushInt64
+			| OpCode::PushInt128
+			| OpCode::PushInt256 => OperandLayout::Integer(self.operand_size().unwrap_or(0)),
+			OpCode::PushData1 => OperandLayout::PrefixedData(1),
+			OpCode::PushData2 => OperandLayout::PrefixedData(2),
+			OpCode::PushData4 => OperandLayout::PrefixedData(4),
+			OpCode::Jmp
+			| OpCode::JmpIf
+			| OpCode::JmpIfNot
+			| OpCode::JmpEq
+			| OpCode::JmpNe
+			| OpCode::JmpGt
+			| OpCode::JmpGe
+			| OpCode::JmpLt
+			| OpCode::JmpLe
+			| OpCode::Call
+			| OpCode::EndTry => OperandLayout::I8,
+			OpCode::PushA
+			| OpCode::JmpL
+			| OpCode::JmpIfL
+			| OpCode::JmpIfNotL
+			| OpCode::JmpEqL
+			| OpCode::JmpNeL
+			| OpCode::JmpGtL
+			| OpCode::JmpGeL
+			| OpCode::JmpLtL
+			| OpCode::JmpLeL
+			| OpCode::CallL
+			| OpCode::EndTryL => OperandLayout::I32,
+			OpCode::Try => OperandLayout::TwoI8,
+			OpCode::TryL => OperandLayout::TwoI32,
+			OpCode::InitSSLot
+			| OpCode::LdSFLd
+			| OpCode::StSFLd
+			| OpCode::LdLoc
+			| OpCode::StLoc
+			| OpCode::LdArg
+			| OpCode::StArg
+			| OpCode::NewArrayT
+			| OpCode::IsType
+			| OpCode::Convert => OperandLayout::U8,
+			OpCode::InitSlot => OperandLayout::TwoU8,
+			OpCode::CallT => OperandLayout::U16,
+			OpCode::Syscall => OperandLayout::U32,
+			_ => OperandLayout::None,
+		}
+	}
+}
+
+impl Display for OpCode {
+	/// Writes the NEO mnemonic returned by `name`.
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		f.write_str(self.name())
+	}
 }
 
 // let opcode_sizes = {
@@ -338,3 +653,39 @@ impl OpCode {
 // OpCode::ABORTMSG => 0,
 // OpCode::ASSERTMSG => 0,
 // };
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use num_traits::FromPrimitive;
+
+	#[test]
+	fn test_name() {
+		assert_eq!(OpCode::PushInt8.name(), "PUSHINT8");
+		assert_eq!(OpCode::PushTrue.name(), "PUSHT");
+		assert_eq!(OpCode::JmpIfNotL.name(), "JMPIFNOT_L");
+		assert_eq!(OpCode::InitSSLot.name(), "INITSSLOT");
+		assert_eq!(OpCode::LdSFLd0.name(), "LDSFLD0");
+		assert_eq!(OpCode::NewArrayT.name(), "NEWARRAY_T");
+		assert_eq!(OpCode::Add.name(), "ADD");
+		assert_eq!(OpCode::AssertMsg.name(), "ASSERTMSG");
+	}
+
+	#[test]
+	fn test_display() {
+		assert_eq!(format!("{}", OpCode::Add), "ADD");
+		assert_eq!(OpCode::JmpL.to_string(), OpCode::JmpL.name());
+	}
+
+	#[test]
+	fn test_operand_layout_matches_operand_size() {
+		for opcode in (0..=u8::MAX).filter_map(OpCode::from_u8) {
+			let layout = opcode.operand_layout();
+			assert_eq!(layout.size(), opcode.operand_size().unwrap_or(0), "{opcode:?}");
+			assert_eq!(layout.prefix(), opcode.operand_prefix().unwrap_or(0), "{opcode:?}");
+		}
+		assert_eq!(OpCode::PushInt64.operand_layout(), OperandLayout::Integer(8));
+		assert_eq!(OpCode::PushData2.operand_layout(), OperandLayout::PrefixedData(2));
+		assert_eq!(OpCode::TryL.operand_layout(), OperandLayout::TwoI32);
+	}
+}
@@ -0,0 +1,236 @@
+use crate::{ordered_map::OrderedMap, stack_item::StackItem, tarjan::Tarjan};
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+/// Used for reference counting of objects in the VM.
+///
+/// The counter only tracks how many references exist. Compound items that leave a stack or a
+/// parent are remembered as zero-referred, and `check_zero_referred` releases the references
+/// held by those that are no longer reachable, including ones that only reference each other.
+#[derive(Debug, Default, Clone)]
+pub struct ReferenceCounter {
+	references_count: usize,
+	zero_referred: OrderedMap<usize, Rc<RefCell<StackItem>>>,
+}
+
+impl ReferenceCounter {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Adds a reference from a compound item to one of its sub-items.
+	pub fn add_reference(&mut self) {
+		self.references_count += 1;
+	}
+
+	/// Adds `count` references from the evaluation stacks or slots.
+	pub fn add_stack_reference(&mut self, count: usize) {
+		self.references_count += count;
+	}
+
+	/// Removes a reference from a compound item to one of its sub-items.
+	pub fn remove_reference(&mut self) {
+		self.references_count -= 1;
+	}
+
+	/// Removes the reference from the evaluation stacks or slots to `item`.
+	pub fn remove_stack_reference(&mut self, item: &Rc<RefCell<StackItem>>) {
+		self.references_count -= 1;
+		self.add_zero_referred(item);
+	}
+
+	/// Remembers that `item` lost a reference, so that `check_zero_referred` releases its
+	/// sub-items if it is no longer reachable. Only compound items are remembered.
+	pub fn add_zero_referred(&mut self, item: &Rc<RefCell<StackItem>>) {
+		if matches!(*item.borrow(), StackItem::Array(_) | StackItem::Struct(_) | StackItem::Map(_))
+		{
+			self.zero_referred.insert(Rc::as_ptr(item) as usize, item.clone());
+		}
+	}
+
+	/// Gets the number of references currently counted. The engine checks it against
+	/// `MaxStackSize` after each instruction, running `check_zero_referred` first when it is
+	/// exceeded.
+	pub fn count(&self) -> usize {
+		self.references_count
+	}
+
+	/// Releases the references held by the zero-referred items that are no longer reachable,
+	/// and returns the number of references left.
+	///
+	/// An item is reachable when something other than the unreachable items holds it. The
+	/// items reachable from the zero-referred ones are split into strongly connected components
+	/// with `Tarjan`, so that a cycle is released as a whole. Released items are emptied, which
+	/// also frees cycles that would otherwise keep each other alive.
+	pub fn check_zero_referred(&mut self) -> usize {
+		if self.zero_referred.is_empty() {
+			return self.references_count
+		}
+		let mut nodes: Vec<Rc<RefCell<StackItem>>> = Vec::new();
+		let mut indexes = HashMap::new();
+		for (pointer, item) in std::mem::take(&mut self.zero_referred) {
+			indexes.insert(pointer, nodes.len());
+			nodes.push(item);
+		}
+		let mut successors = Vec::new();
+		while successors.len() < nodes.len() {
+			let sub_items = compound_sub_items(&nodes[successors.len()].borrow());
+			let mut edges = Vec::with_capacity(sub_items.len());
+			for sub_item in sub_items {
+				let index = *indexes.entry(Rc::as_ptr(&sub_item) as usize).or_insert_with(|| {
+					nodes.push(sub_item);
+					nodes.len() - 1
+				});
+				edges.push(index);
+			}
+			successors.push(edges);
+		}
+
+		// Besides `nodes`, an item is held by its parents among the nodes and by anything
+		// outside of them, which keeps it reachable.
+		let mut internal = vec![0; nodes.len()];
+		for &index in successors.iter().flatten() {
+			internal[index] += 1;
+		}
+		let mut reachable: Vec<bool> = nodes
+			.iter()
+			.zip(&internal)
+			.map(|(node, internal)| Rc::strong_count(node) > 1 + internal)
+			.collect();
+
+		// Parents come before their sub-items in the reversed components, so the reachability
+		// of a component is known before it is checked.
+		let mut unreachable = Vec::new();
+		for component in Tarjan::new(&successors).invoke().into_iter().rev() {
+			if component.iter().any(|&index| reachable[index]) {
+				for &index in &component {
+					for &successor in &successors[index] {
+						reachable[successor] = true;
+					}
+				}
+			} else {
+				unreachable.extend(component);
+			}
+		}
+		for index in unreachable {
+			let mut item = nodes[index].borrow_mut();
+			let count = match &mut *item {
+				StackItem::Array(items) | StackItem::Struct(items) => {
+					let count = items.len();
+					items.clear();
+					count
+				},
+				StackItem::Map(map) => {
+					let count = map.len() * 2;
+					map.clear();
+					count
+				},
+				_ => 0,
+			};
+			self.references_count = self.references_count.saturating_sub(count);
+		}
+		self.references_count
+	}
+}
+
+/// The compound sub-items of `item`, once for each time it holds them.
+fn compound_sub_items(item: &StackItem) -> Vec<Rc<RefCell<StackItem>>> {
+	let is_compound = |item: &&Rc<RefCell<StackItem>>| {
+		matches!(*item.borrow(), StackItem::Array(_) | StackItem::Struct(_) | StackItem::Map(_))
+	};
+	match item {
+		StackItem::Array(items) | StackItem::Struct(items) =>
+			items.iter().filter(is_compound).cloned().collect(),
+		StackItem::Map(map) => map.values().filter(is_compound).cloned().collect(),
+		_ => Vec::new(),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		execution_engine::ExecutionEngine, op_code::OpCode, vm::script::Script, vm_state::VMState,
+	};
+
+	fn array(items: Vec<Rc<RefCell<StackItem>>>) -> Rc<RefCell<StackItem>> {
+		StackItem::Array(items.into()).into()
+	}
+
+	#[test]
+	fn test_self_reference_is_collected() {
+		let mut counter = ReferenceCounter::new();
+		let a = array(Vec::new());
+		counter.add_stack_reference(1);
+		if let StackItem::Array(items) = &mut *a.borrow_mut() {
+			items.push(a.clone());
+		}
+		counter.add_reference();
+		assert_eq!(counter.check_zero_referred(), 2);
+
+		counter.remove_stack_reference(&a);
+		drop(a);
+		assert_eq!(counter.count(), 1);
+		assert_eq!(counter.check_zero_referred(), 0);
+	}
+
+	#[test]
+	fn test_dropped_cycle_is_collected() {
+		// a = []; a.append(a); drop(a)
+		let script = vec![
+			OpCode::NewArray0 as u8,
+			OpCode::Dup as u8,
+			OpCode::Dup as u8,
+			OpCode::Append as u8,
+			OpCode::Drop as u8,
+		];
+		let mut engine = ExecutionEngine::new();
+		engine.load_script(Script::new(script), -1, 0).unwrap();
+		assert_eq!(engine.execute(), VMState::Halt);
+		let mut counter = engine.reference_counter.borrow_mut();
+		assert_eq!(counter.count(), 1);
+		assert_eq!(counter.check_zero_referred(), 0);
+	}
+
+	#[test]
+	fn test_nested_array_counts() {
+		// outer = [inner], inner = [null]
+		let script = vec![
+			OpCode::Push1 as u8,
+			OpCode::NewArray as u8,
+			OpCode::Push1 as u8,
+			OpCode::Pack as u8,
+			OpCode::Dup as u8,
+			OpCode::Drop as u8,
+			OpCode::Drop as u8,
+		];
+		let mut engine = ExecutionEngine::new();
+		engine.load_script(Script::new(script), -1, 0).unwrap();
+		// One reference from the stack to each item on it, plus one from each compound item to
+		// each of its elements.
+		for expected in [1, 2, 3, 3, 4, 3, 2] {
+			engine.step().unwrap();
+			assert_eq!(engine.reference_counter.borrow().count(), expected);
+		}
+		// Only `outer -> inner` and `inner -> null` are left, and nothing reaches `outer`.
+		assert_eq!(engine.reference_counter.borrow_mut().check_zero_referred(), 0);
+	}
+
+	#[test]
+	fn test_reachable_items_are_kept() {
+		let mut counter = ReferenceCounter::new();
+		let inner = array(vec![StackItem::Null.into()]);
+		let outer = array(vec![inner.clone()]);
+		counter.add_reference();
+		counter.add_reference();
+		counter.add_stack_reference(2);
+
+		// `inner` leaves the stack but `outer` still holds it.
+		counter.remove_stack_reference(&inner);
+		drop(inner);
+		assert_eq!(counter.check_zero_referred(), 3);
+
+		counter.remove_stack_reference(&outer);
+		drop(outer);
+		assert_eq!(counter.check_zero_referred(), 0);
+	}
+}
@@ -0,0 +1,88 @@
+use crate::{execution_engine::ExecutionEngine, stack_item::StackItem, vm_error::VMError};
+use std::rc::Rc;
+
+/// The hash of `System.Runtime.Log`.
+pub const RUNTIME_LOG: u32 = 0x9647e7cf;
+
+/// The hash of `System.Runtime.Notify`.
+pub const RUNTIME_NOTIFY: u32 = 0x616f0195;
+
+/// The maximum size of a log message in bytes.
+pub const MAX_LOG_SIZE: usize = 1024;
+
+/// The maximum size of a notification event name in bytes.
+pub const MAX_EVENT_NAME_SIZE: usize = 32;
+
+/// An event raised by `System.Runtime.Notify`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Notification {
+	pub event_name: String,
+	pub state: Vec<StackItem>,
+}
+
+impl ExecutionEngine {
+	/// Registers the `System.Runtime.Log` and `System.Runtime.Notify` interop services.
+	///
+	/// `Log` pops a UTF-8 message and appends it to `logs`. `Notify` pops a UTF-8 event name
+	/// and an `Array` of state, and appends them to `notifications`.
+	pub fn register_runtime(&mut self) -> &mut Self {
+		self.interop_service.register_named(
+			"System.Runtime.Log",
+			RUNTIME_LOG,
+			Rc::new(|engine: &mut ExecutionEngine| {
+				let message = pop_string(engine, MAX_LOG_SIZE)?;
+				engine.logs.push(message);
+				Ok(())
+			}),
+		);
+		self.interop_service.register_named(
+			"System.Runtime.Notify",
+			RUNTIME_NOTIFY,
+			Rc::new(|engine: &mut ExecutionEngine| {
+				let event_name = pop_string(engine, MAX_EVENT_NAME_SIZE)?;
+				let state = match &*engine.pop()?.borrow() {
+					StackItem::Array(items) =>
+						items.iter().map(|item| item.borrow().clone()).collect(),
+					item =>
+						return Err(VMError::InvalidType(format!(
+							"Expected an array of state, found {:?}",
+							item.get_type()
+						))),
+				};
+				engine.notifications.push(Notification { event_name, state });
+				Ok(())
+			}),
+		);
+		self
+	}
+}
+
+/// Pops a UTF-8 string of at most `max_size` bytes.
+fn pop_string(engine: &mut ExecutionEngine, max_size: usize) -> Result<String, VMError> {
+	let bytes = engine.pop()?.borrow().get_span()?;
+	if bytes.len() > max_size {
+		return Err(VMError::InvalidParameter(format!("String length too big: {}", bytes.len())))
+	}
+	String::from_utf8(bytes)
+		.map_err(|_| VMError::InvalidParameter("Invalid UTF-8 string.".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{op_code::OpCode, vm::script::Script, vm_state::VMState};
+
+	#[test]
+	fn test_log_rejects_long_messages() {
+		let mut script = vec![OpCode::PushData2 as u8];
+		script.extend(((MAX_LOG_SIZE + 1) as u16).to_le_bytes());
+		script.extend(vec![b'a'; MAX_LOG_SIZE + 1]);
+		script.push(OpCode::Syscall as u8);
+		script.extend(RUNTIME_LOG.to_le_bytes());
+		let mut engine = ExecutionEngine::new();
+		engine.register_runtime();
+		engine.load_script(Script::new(script), -1, 0).unwrap();
+		assert_eq!(engine.execute(), VMState::Fault);
+		assert!(engine.logs.is_empty());
+	}
+}
@@ -1,40 +1,123 @@
 use crate::{instruction::Instruction, op_code::OpCode, stack_item_type::StackItemType};
-use std::{collections::HashMap, convert::TryFrom, ops::Index};
+use num_traits::FromPrimitive;
+use std::{
+	cell::OnceCell,
+	collections::HashMap,
+	fmt,
+	fmt::{Display, Formatter},
+	rc::Rc,
+};
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+/// Identifies a script by its content.
+pub type ScriptHash = u128;
+
+/// Represents the script executed in the VM.
+#[derive(Debug, Clone, Default)]
 pub struct Script {
 	value: Vec<u8>,
 	strict_mode: bool,
-	instructions: HashMap<usize, Instruction>,
+	instructions: HashMap<usize, Rc<Instruction>>,
+	hash: OnceCell<ScriptHash>,
 }
 
 impl Script {
+	/// Creates a script without validating it.
+	pub fn new(bytes: Vec<u8>) -> Self {
+		Self {
+			value: bytes,
+			strict_mode: false,
+			instructions: HashMap::new(),
+			hash: OnceCell::new(),
+		}
+	}
+
+	/// Creates a script, validating every instruction and jump target up front when
+	/// `strict_mode` is set.
+	pub fn new_with_mode(bytes: Vec<u8>, strict_mode: bool) -> Result<Self, ScriptError> {
+		if strict_mode {
+			Self::new_strict(bytes)
+		} else {
+			Ok(Self::new(bytes))
+		}
+	}
+
+	/// Creates a script in strict mode, validating every instruction and jump target up front.
+	pub fn new_strict(bytes: Vec<u8>) -> Result<Self, ScriptError> {
+		let mut script = Self::new(bytes);
+		script.validate_script()?;
+		script.strict_mode = true;
+		Ok(script)
+	}
+
 	pub fn len(&self) -> usize {
 		self.value.len()
 	}
 
-	pub fn get(&self, index: usize) -> OpCode {
-		OpCode::try_from(self.value[index]).unwrap()
+	pub fn is_empty(&self) -> bool {
+		self.value.is_empty()
 	}
 
-	pub fn new(bytes: Vec<u8>, strict_mode: bool) -> Result<Self, ScriptError> {
-		let mut script = Self { value: bytes, strict_mode, instructions: HashMap::new() };
+	/// Indicates whether the script was validated up front.
+	pub fn strict_mode(&self) -> bool {
+		self.strict_mode
+	}
 
-		if strict_mode {
-			script.validate()?;
-		}
+	/// The raw bytes of the script.
+	pub fn value(&self) -> &[u8] {
+		&self.value
+	}
 
-		Ok(script)
+	/// The 128-bit murmur3 hash of the script bytes, computed on first use.
+	pub fn hash(&self) -> ScriptHash {
+		*self.hash.get_or_init(|| {
+			murmur3::murmur3_x64_128(&mut self.value.as_slice(), 0)
+				.expect("reading from a slice can't fail")
+		})
+	}
+
+	/// Decodes the instructions from the start of the script, yielding each with its offset.
+	/// Iteration stops at the first instruction that can't be decoded, such as one whose
+	/// operand is truncated.
+	pub fn instructions_iter(&self) -> impl Iterator<Item = (usize, Instruction)> + '_ {
+		let mut ip = 0;
+		std::iter::from_fn(move || {
+			let instruction = Instruction::new(&self.value, ip).ok()?;
+			let offset = ip;
+			ip += instruction.size();
+			Some((offset, instruction))
+		})
+	}
+
+	/// Disassembles the script, one `OFFSET  OPCODE  operand_hex` line per instruction.
+	pub fn to_asm(&self) -> String {
+		self.to_string()
 	}
 
-	pub fn validate(&mut self) -> Result<(), ScriptError> {
+	/// Gets the opcode of the byte at `index`, or `None` if it is out of range or not an opcode.
+	pub fn get(&self, index: usize) -> Option<OpCode> {
+		self.value.get(index).and_then(|&b| OpCode::from_u8(b))
+	}
+
+	/// Decodes every instruction of the script into the cache, then checks the jump, call and
+	/// try targets and the type codes.
+	///
+	/// Once it succeeds, every instruction of the script is known to decode, so a strict
+	/// script only serves cached instructions and rejects any other instruction pointer.
+	pub fn validate_script(&mut self) -> Result<(), ScriptError> {
 		let mut ip = 0;
 		while ip < self.len() {
-			let instruction = self.get_instruction(ip).unwrap();
+			let instruction = self.get_instruction(ip)?;
 			ip += instruction.size();
 		}
 
-		for (ip, instruction) in &self.instructions {
+		for (&ip, instruction) in &self.instructions {
+			let check_target = |offset: i32| {
+				let target = ip as i64 + offset as i64;
+				match usize::try_from(target) {
+					Ok(target) if self.instructions.contains_key(&target) => Ok(()),
+					_ => Err(ScriptError::InvalidJumpTarget { instruction_pointer: ip, target }),
+				}
+			};
 			match instruction.opcode {
 				OpCode::Jmp
 				| OpCode::JmpIf
@@ -46,10 +129,7 @@ impl Script {
 				| OpCode::JmpLt
 				| OpCode::JmpLe
 				| OpCode::Call
-				| OpCode::EndTry =>
-					if !self.instructions.contains_key(&(ip + instruction.token_i8())) {
-						panic!("ip: {}, opcode: {:?}", ip, instruction.opcode);
-					},
+				| OpCode::EndTry => check_target(instruction.token_i8() as i32)?,
 				OpCode::PushA
 				| OpCode::JmpL
 				| OpCode::JmpIfL
@@ -61,35 +141,25 @@ impl Script {
 				| OpCode::JmpLtL
 				| OpCode::JmpLeL
 				| OpCode::CallL
-				| OpCode::EndTryL =>
-					if !self.instructions.contains_key(&(ip + instruction.token_i32())) {
-						panic!("ip: {}, opcode: {:?}", ip, instruction.opcode);
-					},
+				| OpCode::EndTryL => check_target(instruction.token_i32())?,
 				OpCode::Try => {
-					if !self.instructions.contains_key(&(ip + instruction.token_i8())) {
-						panic!("ip: {}, opcode: {:?}", ip, instruction.opcode);
-					}
-					if !self.instructions.contains_key(&(ip + instruction.token_i8_1())) {
-						panic!("ip: {}, opcode: {:?}", ip, instruction.opcode);
-					}
+					check_target(instruction.token_i8() as i32)?;
+					check_target(instruction.token_i8_1() as i32)?;
 				},
 				OpCode::TryL => {
-					if !self.instructions.contains_key(&(ip + instruction.token_i32())) {
-						panic!("ip: {}, opcode: {:?}", ip, instruction.opcode);
-					}
-					if !self.instructions.contains_key(&(ip + instruction.token_i32_1())) {
-						panic!("ip: {}, opcode: {:?}", ip, instruction.opcode);
-					}
+					check_target(instruction.token_i32())?;
+					check_target(instruction.token_i32_1())?;
 				},
 				OpCode::NewArrayT | OpCode::IsType | OpCode::Convert => {
 					let type_code = instruction.token_u8();
-					if !StackItemType::is_valid(type_code) {
-						panic!("Invalid type code: {}", type_code);
-					}
-					if instruction.opcode != OpCode::NewArrayT
-						&& type_code == StackItemType::Any as u8
+					if !StackItemType::is_valid(type_code)
+						|| (instruction.opcode != OpCode::NewArrayT
+							&& type_code == StackItemType::Any as u8)
 					{
-						panic!("ip: {}, opcode: {:?} with Any type", ip, instruction.opcode);
+						return Err(ScriptError::InvalidTypeCode {
+							instruction_pointer: ip,
+							type_code,
+						})
 					}
 				},
 				_ => {},
@@ -99,37 +169,157 @@ impl Script {
 		Ok(())
 	}
 
-	pub fn get_instruction(&mut self, ip: usize) -> Result<&Instruction, ScriptError> {
+	pub fn get_instruction(&mut self, ip: usize) -> Result<Rc<Instruction>, ScriptError> {
 		if !self.instructions.contains_key(&ip) {
-			if self.strict_mode {
+			if ip >= self.len() || self.strict_mode {
 				return Err(ScriptError::InvalidInstrPointer(ip))
 			}
 
-			let instr = Instruction::parse(&self.value, ip)?;
-			self.instructions.insert(ip, instr);
+			let instr = Instruction::new(&self.value, ip)?;
+			self.instructions.insert(ip, Rc::new(instr));
 		}
 
-		Ok(self.instructions.get(&ip).unwrap())
+		Ok(self.instructions[&ip].clone())
 	}
 }
 
-impl Index<usize> for Script {
-	type Output = OpCode;
+impl Display for Script {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		for (index, (offset, instruction)) in self.instructions_iter().enumerate() {
+			if index > 0 {
+				writeln!(f)?;
+			}
+			write!(f, "{offset:04x}  {}", instruction.opcode)?;
+			if instruction.operand_len() > 0 {
+				write!(f, "  {}", instruction.operand_as_hex())?;
+			}
+		}
+		Ok(())
+	}
+}
 
-	fn index(&self, index: usize) -> &Self::Output {
-		&self.get(index)
+impl From<Vec<u8>> for Script {
+	fn from(script: Vec<u8>) -> Self {
+		Self::new(script)
 	}
 }
 
-impl TryFrom<Vec<u8>> for Script {
-	type Error = ScriptError;
+/// Represents errors raised while decoding a script.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScriptError {
+	/// The instruction pointer does not point to an instruction of the script.
+	InvalidInstrPointer(usize),
+
+	/// The byte at the instruction pointer is not a known opcode.
+	InvalidOpcode(u8),
 
-	fn try_from(script: Vec<u8>) -> Result<Self, Self::Error> {
-		Self::new(script, false)
+	/// The operand size prefix of the instruction is invalid.
+	InvalidPrefixSize(usize),
+
+	/// The operand of the instruction runs past the end of the script.
+	OperandOutOfBounds { instruction_pointer: usize, operand_size: usize, script_length: usize },
+
+	/// The jump, call or try instruction targets an offset that is not the start of an
+	/// instruction.
+	InvalidJumpTarget { instruction_pointer: usize, target: i64 },
+
+	/// The instruction has a stack item type operand that it doesn't accept.
+	InvalidTypeCode { instruction_pointer: usize, type_code: u8 },
+}
+
+impl Display for ScriptError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::InvalidInstrPointer(ip) => write!(f, "invalid instruction pointer: {ip}"),
+			Self::InvalidOpcode(code) => write!(f, "invalid opcode: {code:#04x}"),
+			Self::InvalidPrefixSize(size) => write!(f, "invalid operand prefix size: {size}"),
+			Self::OperandOutOfBounds { instruction_pointer, operand_size, script_length } =>
+				write!(
+				f,
+				"operand of size {operand_size} at {instruction_pointer} exceeds script length {script_length}"
+			),
+			Self::InvalidJumpTarget { instruction_pointer, target } =>
+				write!(f, "invalid jump target {target} at {instruction_pointer}"),
+			Self::InvalidTypeCode { instruction_pointer, type_code } =>
+				write!(f, "invalid type code {type_code:#04x} at {instruction_pointer}"),
+		}
 	}
 }
 
-enum ScriptError {
-	InvalidInstrPointer(usize),
-	// other errors
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_disassemble() {
+		let script = Script::new(vec![
+			OpCode::Push1 as u8,
+			OpCode::Push2 as u8,
+			OpCode::Add as u8,
+			OpCode::Ret as u8,
+		]);
+		let instructions: Vec<_> = script.instructions_iter().collect();
+		assert_eq!(instructions.len(), 4);
+		assert_eq!(instructions[2], (2, Instruction { opcode: OpCode::Add, operand: vec![] }));
+		assert_eq!(script.to_asm(), "0000  PUSH1\n0001  PUSH2\n0002  ADD\n0003  RET");
+	}
+
+	#[test]
+	fn test_disassemble_operands() {
+		let script =
+			Script::new(vec![OpCode::PushData1 as u8, 0x02, 0xab, 0xcd, OpCode::Jmp as u8]);
+		// The JMP is missing its offset, so only the PUSHDATA1 is decoded.
+		assert_eq!(script.to_asm(), "0000  PUSHDATA1  abcd");
+	}
+
+	#[test]
+	fn test_get() {
+		let script = Script::new(vec![OpCode::Push1 as u8, 0xff]);
+		assert_eq!(script.get(0), Some(OpCode::Push1));
+		assert_eq!(script.get(1), None);
+		assert_eq!(script.get(2), None);
+	}
+
+	#[test]
+	fn test_strict_mode_rejects_truncated_operands() {
+		// The PUSHDATA1 announces 4 bytes but only 2 follow.
+		let script = vec![OpCode::Push1 as u8, OpCode::PushData1 as u8, 0x04, 0x01, 0x02];
+		assert_eq!(
+			Script::new_strict(script.clone()).unwrap_err(),
+			ScriptError::OperandOutOfBounds {
+				instruction_pointer: 3,
+				operand_size: 4,
+				script_length: 5
+			}
+		);
+		// 0xff isn't an opcode.
+		let script = vec![OpCode::Push1 as u8, 0xff];
+		assert_eq!(Script::new_strict(script).unwrap_err(), ScriptError::InvalidOpcode(0xff));
+
+		let mut script =
+			Script::new_strict(vec![OpCode::PushData1 as u8, 0x01, 0x01, OpCode::Ret as u8])
+				.unwrap();
+		assert_eq!(script.instructions.len(), 2);
+		assert_eq!(script.get_instruction(1).unwrap_err(), ScriptError::InvalidInstrPointer(1));
+	}
+
+	#[test]
+	fn test_strict_mode_rejects_invalid_scripts() {
+		let valid = vec![OpCode::Jmp as u8, 0x02, OpCode::Ret as u8];
+		assert!(Script::new_with_mode(valid, true).unwrap().strict_mode());
+
+		// The JMP at 0 targets offset 5, past the end of the script.
+		let jump_past_end = vec![OpCode::Jmp as u8, 0x05, OpCode::Ret as u8];
+		assert_eq!(
+			Script::new_with_mode(jump_past_end.clone(), true).unwrap_err(),
+			ScriptError::InvalidJumpTarget { instruction_pointer: 0, target: 5 }
+		);
+		assert!(!Script::new_with_mode(jump_past_end, false).unwrap().strict_mode());
+
+		let convert_to_any = vec![OpCode::Push1 as u8, OpCode::Convert as u8, 0x00];
+		assert_eq!(
+			Script::new_strict(convert_to_any).unwrap_err(),
+			ScriptError::InvalidTypeCode { instruction_pointer: 1, type_code: 0x00 }
+		);
+	}
 }
@@ -1,66 +1,78 @@
-use crate::{null::Null, reference_counter::ReferenceCounter, stack_item::StackItem};
+use crate::{reference_counter::ReferenceCounter, stack_item::StackItem, vm_error::VMError};
 use std::{cell::RefCell, rc::Rc};
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+/// Used to store local variables, arguments and static fields in the VM.
+#[derive(Debug)]
 pub struct Slot {
-	items: Vec<Rc<RefCell<dyn StackItem>>>,
+	items: Vec<Rc<RefCell<StackItem>>>,
 	reference_counter: Rc<RefCell<ReferenceCounter>>,
 }
 
 impl Slot {
+	/// Creates a slot containing the specified items, e.g. the arguments popped by
+	/// `OpCode::InitSlot`, adding a stack reference for each of them.
 	pub fn new(
-		items: Vec<Rc<RefCell<dyn StackItem>>>,
+		items: Vec<Rc<RefCell<StackItem>>>,
 		reference_counter: Rc<RefCell<ReferenceCounter>>,
 	) -> Self {
-		let mut slot = Self { items, reference_counter };
-		for item in &slot.items {
-			slot.reference_counter.add_stack_reference(item, 1);
-		}
-		slot
+		reference_counter.borrow_mut().add_stack_reference(items.len());
+		Self { items, reference_counter }
 	}
 
-	pub fn new_with_count(count: i32, reference_counter: Rc<RefCell<ReferenceCounter>>) -> Self {
-		let mut items = Vec::new();
-		for _ in 0..count {
-			items.push(StackItem::from(Null::default()).into());
-		}
-
-		Self { items, reference_counter }
+	/// Creates a slot of the specified size, filled with `StackItem::Null`.
+	pub fn new_with_count(count: usize, reference_counter: Rc<RefCell<ReferenceCounter>>) -> Self {
+		let items = (0..count).map(|_| StackItem::Null.into()).collect();
+		Self::new(items, reference_counter)
 	}
 
-	pub fn with_capacity(
-		capacity: usize,
-		reference_counter: Rc<RefCell<ReferenceCounter>>,
-	) -> Self {
-		Self { items: Vec::with_capacity(capacity), reference_counter }
+	/// Gets the item at `index`, or `None` if it is out of range.
+	pub fn get(&self, index: usize) -> Option<Rc<RefCell<StackItem>>> {
+		self.items.get(index).cloned()
 	}
 
-	pub fn get(&self, index: usize) -> Rc<RefCell<dyn StackItem>> {
-		self.items[index].clone()
+	/// Replaces the item at `index` with `value`, failing if `index` is out of range. The
+	/// reference to the old item is released and one to `value` is added.
+	pub fn set(&mut self, index: usize, value: Rc<RefCell<StackItem>>) -> Result<(), VMError> {
+		let count = self.items.len();
+		let item = self.items.get_mut(index).ok_or_else(|| {
+			VMError::InvalidParameter(format!("Slot index out of range: {index}/{count}"))
+		})?;
+		let old = std::mem::replace(item, value);
+		let mut reference_counter = self.reference_counter.borrow_mut();
+		reference_counter.remove_stack_reference(&old);
+		reference_counter.add_stack_reference(1);
+		Ok(())
 	}
 
-	pub fn set(&mut self, index: usize, value: Rc<RefCell<dyn StackItem>>) {
-		let old_value = std::mem::replace(&mut self.items[index], value);
-		self.reference_counter.remove_stack_reference(&old_value);
-		self.reference_counter.add_stack_reference(&value, 1);
+	/// Iterates over the items in index order.
+	pub(crate) fn iter(&self) -> impl Iterator<Item = &Rc<RefCell<StackItem>>> {
+		self.items.iter()
 	}
 
-	pub fn len(&self) -> usize {
+	/// Gets the number of items in the slot.
+	pub fn count(&self) -> usize {
 		self.items.len()
 	}
 
 	pub fn clear_references(&mut self) {
 		for item in &self.items {
-			self.reference_counter.get_mut().remove_stack_reference(item.clone());
+			self.reference_counter.borrow_mut().remove_stack_reference(item);
 		}
 	}
 }
 
-impl IntoIterator for Slot {
-	type Item = Rc<RefCell<dyn StackItem>>;
-	type IntoIter = std::vec::IntoIter<Self::Item>;
+#[cfg(test)]
+mod tests {
+	use super::*;
 
-	fn into_iter(self) -> Self::IntoIter {
-		self.items.into_iter()
+	#[test]
+	fn test_get_and_set() {
+		let mut slot = Slot::new_with_count(2, Rc::new(RefCell::new(ReferenceCounter::new())));
+		slot.set(1, StackItem::from(7).into()).unwrap();
+		assert_eq!(*slot.get(1).unwrap().borrow(), StackItem::from(7));
+		assert_eq!(*slot.get(0).unwrap().borrow(), StackItem::Null);
+		assert!(slot.get(2).is_none());
+		assert!(matches!(slot.set(2, StackItem::Null.into()), Err(VMError::InvalidParameter(_))));
+		assert_eq!(slot.reference_counter.borrow().count(), 2);
 	}
 }
@@ -0,0 +1,203 @@
+use crate::{execution_engine::ExecutionEngine, stack_item::StackItem, vm_error::VMError};
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+/// The hash of `System.Storage.GetContext`.
+pub const STORAGE_GET_CONTEXT: u32 = 0xce67f69b;
+
+/// The hash of `System.Storage.Get`.
+pub const STORAGE_GET: u32 = 0x31e85d92;
+
+/// The hash of `System.Storage.Put`.
+pub const STORAGE_PUT: u32 = 0x84183fe6;
+
+/// The hash of `System.Storage.Delete`.
+pub const STORAGE_DELETE: u32 = 0xedc5582f;
+
+/// The maximum size of a storage key in bytes.
+pub const MAX_STORAGE_KEY_SIZE: usize = 64;
+
+/// The maximum size of a storage value in bytes.
+pub const MAX_STORAGE_VALUE_SIZE: usize = u16::MAX as usize;
+
+/// The key-value store read and written by the `System.Storage` interop services.
+pub trait StorageBackend {
+	/// Returns the value stored under `key`, if any.
+	fn get(&self, key: &[u8]) -> Option<Vec<u8>>;
+
+	/// Stores `value` under `key`, replacing any existing value.
+	fn put(&mut self, key: Vec<u8>, value: Vec<u8>);
+
+	/// Removes the value stored under `key`, if any.
+	fn delete(&mut self, key: &[u8]);
+}
+
+/// A `StorageBackend` keeping its entries in memory.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HashMapStorage {
+	entries: HashMap<Vec<u8>, Vec<u8>>,
+}
+
+impl HashMapStorage {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn len(&self) -> usize {
+		self.entries.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.entries.is_empty()
+	}
+}
+
+impl StorageBackend for HashMapStorage {
+	fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+		self.entries.get(key).cloned()
+	}
+
+	fn put(&mut self, key: Vec<u8>, value: Vec<u8>) {
+		self.entries.insert(key, value);
+	}
+
+	fn delete(&mut self, key: &[u8]) {
+		self.entries.remove(key);
+	}
+}
+
+/// The handle pushed by `System.Storage.GetContext` and expected by the other storage services.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StorageContext;
+
+impl ExecutionEngine {
+	/// Registers the `System.Storage` interop services backed by `storage`.
+	///
+	/// `GetContext` pushes a `StorageContext` interop interface. `Get` pops the context and a
+	/// key and pushes the stored `ByteString`, or `Null` when the key is absent. `Put` pops the
+	/// context, a key and a value, and `Delete` pops the context and a key.
+	pub fn register_storage<S: StorageBackend + 'static>(
+		&mut self,
+		storage: Rc<RefCell<S>>,
+	) -> &mut Self {
+		self.interop_service.register_named(
+			"System.Storage.GetContext",
+			STORAGE_GET_CONTEXT,
+			Rc::new(|engine: &mut ExecutionEngine| {
+				engine.push(StackItem::InteropInterface(Rc::new(StorageContext)).into())
+			}),
+		);
+		let backend = storage.clone();
+		self.interop_service.register_named(
+			"System.Storage.Get",
+			STORAGE_GET,
+			Rc::new(move |engine: &mut ExecutionEngine| {
+				pop_context(engine)?;
+				let key = engine.pop()?.borrow().get_span()?;
+				let value = match backend.borrow().get(&key) {
+					Some(value) => StackItem::ByteString(value),
+					None => StackItem::Null,
+				};
+				engine.push(value.into())
+			}),
+		);
+		let backend = storage.clone();
+		self.interop_service.register_named(
+			"System.Storage.Put",
+			STORAGE_PUT,
+			Rc::new(move |engine: &mut ExecutionEngine| {
+				pop_context(engine)?;
+				let key = engine.pop()?.borrow().get_span()?;
+				let value = engine.pop()?.borrow().get_span()?;
+				if key.len() > MAX_STORAGE_KEY_SIZE {
+					return Err(VMError::InvalidParameter(format!(
+						"Key length too big: {}",
+						key.len()
+					)))
+				}
+				if value.len() > MAX_STORAGE_VALUE_SIZE {
+					return Err(VMError::InvalidParameter(format!(
+						"Value length too big: {}",
+						value.len()
+					)))
+				}
+				backend.borrow_mut().put(key, value);
+				Ok(())
+			}),
+		);
+		self.interop_service.register_named(
+			"System.Storage.Delete",
+			STORAGE_DELETE,
+			Rc::new(move |engine: &mut ExecutionEngine| {
+				pop_context(engine)?;
+				let key = engine.pop()?.borrow().get_span()?;
+				storage.borrow_mut().delete(&key);
+				Ok(())
+			}),
+		);
+		self
+	}
+}
+
+/// Pops the `StorageContext` passed to a storage service.
+fn pop_context(engine: &mut ExecutionEngine) -> Result<(), VMError> {
+	match &*engine.pop()?.borrow() {
+		StackItem::InteropInterface(value) if value.is::<StorageContext>() => Ok(()),
+		item => Err(VMError::InvalidType(format!(
+			"Expected a storage context, found {:?}",
+			item.get_type()
+		))),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{op_code::OpCode, vm::script::Script, vm_state::VMState};
+
+	fn syscall(script: &mut Vec<u8>, hash: u32) {
+		script.push(OpCode::Syscall as u8);
+		script.extend(hash.to_le_bytes());
+	}
+
+	#[test]
+	fn test_put_get_delete() {
+		let mut script = Vec::new();
+		// Put(context, "k", "value")
+		script.extend([OpCode::PushData1 as u8, 0x05]);
+		script.extend(b"value");
+		script.extend([OpCode::PushData1 as u8, 0x01, b'k']);
+		syscall(&mut script, STORAGE_GET_CONTEXT);
+		syscall(&mut script, STORAGE_PUT);
+		// Get(context, "k")
+		script.extend([OpCode::PushData1 as u8, 0x01, b'k']);
+		syscall(&mut script, STORAGE_GET_CONTEXT);
+		syscall(&mut script, STORAGE_GET);
+		// Delete(context, "k"), then Get(context, "k") again
+		script.extend([OpCode::PushData1 as u8, 0x01, b'k']);
+		syscall(&mut script, STORAGE_GET_CONTEXT);
+		syscall(&mut script, STORAGE_DELETE);
+		script.extend([OpCode::PushData1 as u8, 0x01, b'k']);
+		syscall(&mut script, STORAGE_GET_CONTEXT);
+		syscall(&mut script, STORAGE_GET);
+
+		let storage = Rc::new(RefCell::new(HashMapStorage::new()));
+		let mut engine = ExecutionEngine::new();
+		engine.register_storage(storage.clone());
+		engine.load_script(Script::new(script), -1, 0).unwrap();
+		assert_eq!(engine.execute(), VMState::Halt);
+		let result = engine.result_stack.borrow();
+		assert_eq!(*result.peek(0).unwrap().borrow(), StackItem::Null);
+		assert_eq!(*result.peek(1).unwrap().borrow(), StackItem::ByteString(b"value".to_vec()));
+		assert!(storage.borrow().is_empty());
+	}
+
+	#[test]
+	fn test_get_without_context_faults() {
+		let mut script = vec![OpCode::PushData1 as u8, 0x01, b'k', OpCode::Push1 as u8];
+		syscall(&mut script, STORAGE_GET);
+		let mut engine = ExecutionEngine::new();
+		engine.register_storage(Rc::new(RefCell::new(HashMapStorage::new())));
+		engine.load_script(Script::new(script), -1, 0).unwrap();
+		assert_eq!(engine.execute(), VMState::Fault);
+	}
+}
@@ -0,0 +1,121 @@
+/// Finds the strongly connected components of a directed graph with Tarjan's algorithm.
+///
+/// The vertices are the indexes into `successors`, which lists the vertices each vertex has an
+/// edge to. The search is iterative, so deeply nested graphs can't overflow the call stack.
+pub struct Tarjan<'a> {
+	successors: &'a [Vec<usize>],
+	vertices: Vec<Vertex>,
+	stack: Vec<usize>,
+	components: Vec<Vec<usize>>,
+	index: usize,
+}
+
+/// The search state of a vertex.
+#[derive(Clone, Copy, Debug, Default)]
+struct Vertex {
+	/// The order in which the vertex was discovered, once it has been.
+	dfn: Option<usize>,
+	/// The smallest `dfn` reachable from the vertex through the search stack.
+	low_link: usize,
+	/// Indicates whether the vertex is on the search stack.
+	on_stack: bool,
+}
+
+impl<'a> Tarjan<'a> {
+	pub fn new(successors: &'a [Vec<usize>]) -> Self {
+		Self {
+			successors,
+			vertices: vec![Vertex::default(); successors.len()],
+			stack: Vec::new(),
+			components: Vec::new(),
+			index: 0,
+		}
+	}
+
+	/// Returns the strongly connected components. A component is listed after every component
+	/// it has an edge to, i.e. in reverse topological order.
+	pub fn invoke(mut self) -> Vec<Vec<usize>> {
+		for vertex in 0..self.successors.len() {
+			if self.vertices[vertex].dfn.is_none() {
+				self.strong_connect(vertex);
+			}
+		}
+		self.components
+	}
+
+	fn strong_connect(&mut self, root: usize) {
+		// Each frame is a vertex being visited and the position of its next successor.
+		let mut frames = vec![(root, 0)];
+		self.visit(root);
+		while let Some(&mut (vertex, ref mut next)) = frames.last_mut() {
+			if let Some(&successor) = self.successors[vertex].get(*next) {
+				*next += 1;
+				match self.vertices[successor].dfn {
+					None => {
+						self.visit(successor);
+						frames.push((successor, 0));
+					},
+					Some(dfn) if self.vertices[successor].on_stack => {
+						let low_link = &mut self.vertices[vertex].low_link;
+						*low_link = (*low_link).min(dfn);
+					},
+					Some(_) => {},
+				}
+				continue
+			}
+			frames.pop();
+			let low_link = self.vertices[vertex].low_link;
+			if let Some(&(parent, _)) = frames.last() {
+				let parent_low_link = &mut self.vertices[parent].low_link;
+				*parent_low_link = (*parent_low_link).min(low_link);
+			}
+			if Some(low_link) == self.vertices[vertex].dfn {
+				let mut component = Vec::new();
+				while let Some(member) = self.stack.pop() {
+					self.vertices[member].on_stack = false;
+					component.push(member);
+					if member == vertex {
+						break
+					}
+				}
+				self.components.push(component);
+			}
+		}
+	}
+
+	fn visit(&mut self, vertex: usize) {
+		self.vertices[vertex] =
+			Vertex { dfn: Some(self.index), low_link: self.index, on_stack: true };
+		self.index += 1;
+		self.stack.push(vertex);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn sorted(mut components: Vec<Vec<usize>>) -> Vec<Vec<usize>> {
+		for component in &mut components {
+			component.sort_unstable();
+		}
+		components
+	}
+
+	#[test]
+	fn test_components() {
+		// 0 -> 1 -> 2 -> 0 form a cycle that points at 3, and 4 points at itself.
+		let successors = vec![vec![1], vec![2], vec![0, 3], vec![], vec![4]];
+		let components = sorted(Tarjan::new(&successors).invoke());
+		assert_eq!(components, [vec![3], vec![0, 1, 2], vec![4]]);
+	}
+
+	#[test]
+	fn test_deep_chain() {
+		let successors: Vec<Vec<usize>> =
+			(1..=100_000).map(|next| vec![next]).chain([vec![]]).collect();
+		let components = Tarjan::new(&successors).invoke();
+		assert_eq!(components.len(), successors.len());
+		assert_eq!(components[0], [100_000]);
+	}
+}
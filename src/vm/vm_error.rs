@@ -0,0 +1,89 @@
+use crate::{op_code::OpCode, vm::script::ScriptError};
+use std::{
+	error::Error,
+	fmt,
+	fmt::{Display, Formatter},
+};
+
+/// Represents errors during VM execution.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum VMError {
+	/// Trying to exceed invocation stack size limit.
+	InvocationStackOverflow(String),
+
+	/// Trying to exceed try nesting limit.
+	TryNestingOverflow(String),
+
+	/// Trying to exceed maximum stack size.
+	StackOverflow(String),
+
+	/// Trying to create a single item that exceeds size limit.
+	ItemTooLarge(String),
+
+	/// Invalid opcode encountered.
+	InvalidOpcode(String),
+
+	/// Trying to divide by zero.
+	DivisionByZero(String),
+
+	/// Invalid jump offset or pointer.
+	InvalidJump(String),
+
+	/// Unsupported token encountered.
+	InvalidToken(String),
+
+	/// Invalid parameter for operation.
+	InvalidParameter(String),
+
+	/// Item not found in collection.
+	ItemNotFound(String),
+
+	/// Type mismatch for operation.
+	InvalidType(String),
+
+	/// Trying to consume more GAS than the limit allows.
+	InsufficientGas(String),
+
+	/// Custom error with message.
+	Custom(String),
+
+	/// The error `reason` faulted the VM while executing `opcode` at `ip`.
+	FaultAt { ip: usize, opcode: OpCode, reason: Box<VMError> },
+}
+
+impl Display for VMError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::InvocationStackOverflow(msg) =>
+				write!(f, "invocation stack size limit exceeded: {msg}"),
+			Self::TryNestingOverflow(msg) => write!(f, "try nesting depth limit exceeded: {msg}"),
+			Self::StackOverflow(msg) => write!(f, "stack size limit exceeded: {msg}"),
+			Self::ItemTooLarge(msg) => write!(f, "item size exceeds limit: {msg}"),
+			Self::InvalidOpcode(msg) => write!(f, "encountered invalid opcode: {msg}"),
+			Self::DivisionByZero(msg) => write!(f, "tried to divide by zero: {msg}"),
+			Self::InvalidJump(msg) => write!(f, "invalid jump offset or pointer: {msg}"),
+			Self::InvalidToken(msg) => write!(f, "invalid token encountered: {msg}"),
+			Self::InvalidParameter(msg) => write!(f, "invalid parameter for operation: {msg}"),
+			Self::ItemNotFound(msg) => write!(f, "item not found in collection: {msg}"),
+			Self::InvalidType(msg) => write!(f, "type mismatch for operation: {msg}"),
+			Self::InsufficientGas(msg) => write!(f, "insufficient GAS: {msg}"),
+			Self::Custom(msg) => write!(f, "{msg}"),
+			Self::FaultAt { ip, opcode, reason } => write!(f, "fault at {ip} ({opcode}): {reason}"),
+		}
+	}
+}
+
+impl Error for VMError {
+	fn source(&self) -> Option<&(dyn Error + 'static)> {
+		match self {
+			Self::FaultAt { reason, .. } => Some(reason.as_ref()),
+			_ => None,
+		}
+	}
+}
+
+impl From<ScriptError> for VMError {
+	fn from(err: ScriptError) -> Self {
+		Self::InvalidOpcode(err.to_string())
+	}
+}
@@ -0,0 +1,27 @@
+use neo_vm_rs::{
+	execution_engine::ExecutionEngine, op_code::OpCode, stack_item::StackItem, vm_state::VMState,
+};
+
+#[test]
+fn test_run_arithmetic() {
+	let script = vec![
+		OpCode::Push2 as u8,
+		OpCode::Push3 as u8,
+		OpCode::Mul as u8,
+		OpCode::Push1 as u8,
+		OpCode::Sub as u8,
+		OpCode::Push0 as u8,
+	];
+	let (state, results) = ExecutionEngine::run(script);
+	assert_eq!(state, VMState::Halt);
+	let results: Vec<StackItem> = results.iter().map(|item| item.borrow().clone()).collect();
+	assert_eq!(results, vec![StackItem::from(5), StackItem::from(0)]);
+}
+
+#[test]
+fn test_run_fault() {
+	let (state, results) =
+		ExecutionEngine::run(vec![OpCode::Push1 as u8, OpCode::Push0 as u8, OpCode::Div as u8]);
+	assert_eq!(state, VMState::Fault);
+	assert!(results.is_empty());
+}
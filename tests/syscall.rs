@@ -0,0 +1,68 @@
+use neo_vm_rs::{
+	execution_engine::ExecutionEngine, op_code::OpCode, script_builder::ScriptBuilder,
+	stack_item::StackItem, vm::script::Script, vm_error::VMError, vm_state::VMState, BigInt,
+};
+
+/// A hash whose bytes differ in every position, so a byte-order mismatch can't go unnoticed.
+const HASH: u32 = 0x1234_5678;
+
+fn syscall_script(hash: u32) -> Vec<u8> {
+	let mut builder = ScriptBuilder::new();
+	builder.emit_syscall(hash);
+	builder.to_bytes()
+}
+
+fn push_forty_two(engine: &mut ExecutionEngine) -> Result<(), VMError> {
+	engine.push(StackItem::from(BigInt::from(42)).into())
+}
+
+#[test]
+fn test_emitted_syscall_reaches_registered_handler() {
+	let script = syscall_script(HASH);
+	assert_eq!(script[1..], HASH.to_le_bytes());
+
+	let mut engine = ExecutionEngine::new();
+	engine.register_syscall(HASH, push_forty_two);
+	engine.load_script(Script::new(script), -1, 0).unwrap();
+	assert_eq!(engine.execute(), VMState::Halt);
+	assert_eq!(*engine.result_stack.borrow().peek(0).unwrap().borrow(), StackItem::from(42));
+}
+
+#[test]
+fn test_syscall_with_swapped_hash_faults() {
+	let mut engine = ExecutionEngine::new();
+	engine.register_syscall(HASH.swap_bytes(), push_forty_two);
+	engine.load_script(Script::new(syscall_script(HASH)), -1, 0).unwrap();
+	assert_eq!(engine.execute(), VMState::Fault);
+}
+
+fn throw_boom(engine: &mut ExecutionEngine) -> Result<(), VMError> {
+	engine.throw(StackItem::ByteString(b"boom".to_vec()).into())
+}
+
+#[test]
+fn test_syscall_throw_is_caught() {
+	// TRY +10 (catch), 0; SYSCALL; ENDTRY +3; [catch] PUSH1
+	let mut script = vec![OpCode::Try as u8, 0x0A, 0x00];
+	script.extend(syscall_script(HASH));
+	script.extend([OpCode::EndTry as u8, 0x03, OpCode::Push1 as u8]);
+
+	let mut engine = ExecutionEngine::new();
+	engine.register_syscall(HASH, throw_boom);
+	engine.load_script(Script::new(script), -1, 0).unwrap();
+	assert_eq!(engine.execute(), VMState::Halt);
+	assert!(engine.uncaught_exception().is_none());
+	let result = engine.result_stack.borrow();
+	assert_eq!(*result.peek(0).unwrap().borrow(), StackItem::from(1));
+	assert_eq!(*result.peek(1).unwrap().borrow(), StackItem::ByteString(b"boom".to_vec()));
+}
+
+#[test]
+fn test_uncaught_syscall_throw_faults() {
+	let mut engine = ExecutionEngine::new();
+	engine.register_syscall(HASH, throw_boom);
+	engine.load_script(Script::new(syscall_script(HASH)), -1, 0).unwrap();
+	assert_eq!(engine.execute(), VMState::Fault);
+	let exception = engine.uncaught_exception().unwrap();
+	assert_eq!(*exception.borrow(), StackItem::ByteString(b"boom".to_vec()));
+}